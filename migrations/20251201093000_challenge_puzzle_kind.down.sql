@@ -0,0 +1,2 @@
+alter table public.challenge
+drop column puzzle_kind;
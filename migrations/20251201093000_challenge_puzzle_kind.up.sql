@@ -0,0 +1,2 @@
+alter table public.challenge
+add column puzzle_kind character varying not null default 'selection';
@@ -0,0 +1,7 @@
+create table public.consumed_response_token (
+    jti uuid not null,
+    expires_at timestamp with time zone not null,
+    constraint consumed_response_token_pkey primary key (jti)
+);
+
+create index consumed_response_token_expires_at_idx on public.consumed_response_token (expires_at);
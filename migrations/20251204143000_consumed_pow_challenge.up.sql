@@ -0,0 +1,11 @@
+create table public.consumed_pow_challenge (
+    site_key character varying not null,
+    nonce bigint not null,
+    timestamp bigint not null,
+    expires_at timestamp with time zone not null,
+    constraint consumed_pow_challenge_pkey primary key (site_key, nonce, timestamp),
+    constraint consumed_pow_challenge_site_key_fkey foreign key (site_key) references public.api_key (site_key)
+        on delete cascade
+);
+
+create index consumed_pow_challenge_expires_at_idx on public.consumed_pow_challenge (expires_at);
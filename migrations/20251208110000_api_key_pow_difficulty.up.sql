@@ -0,0 +1,3 @@
+alter table public.api_key
+add column pow_difficulty smallint not null default 3,
+add constraint api_key_pow_difficulty_range check (pow_difficulty between 1 and 32);
@@ -0,0 +1,3 @@
+alter table public.api_key
+add column pow_algorithm varchar not null default 'sha256-hex',
+add constraint api_key_pow_algorithm_check check (pow_algorithm in ('sha256-hex', 'sha256', 'argon2id'));
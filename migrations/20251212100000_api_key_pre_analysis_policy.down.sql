@@ -0,0 +1,6 @@
+drop table public.pre_analysis_window;
+
+alter table public.api_key
+drop column pre_analysis_min_score,
+drop column pre_analysis_required_signals,
+drop column pre_analysis_max_pass_rate;
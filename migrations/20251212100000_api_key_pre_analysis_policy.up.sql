@@ -0,0 +1,15 @@
+alter table public.api_key
+add column pre_analysis_min_score real not null default 0.7,
+add column pre_analysis_required_signals varchar[] not null default '{}',
+add column pre_analysis_max_pass_rate real not null default 1,
+add constraint api_key_pre_analysis_min_score_range check (pre_analysis_min_score between 0 and 1),
+add constraint api_key_pre_analysis_max_pass_rate_range check (pre_analysis_max_pass_rate between 0 and 1);
+
+create table public.pre_analysis_window (
+    site_key varchar not null references public.api_key (site_key) on delete cascade,
+    window_start timestamptz not null,
+    attempts integer not null,
+    passes integer not null,
+    last_passed boolean not null,
+    primary key (site_key, window_start)
+);
@@ -0,0 +1,5 @@
+alter table public.api_key
+drop column clearance_lifetime_secs,
+drop column clearance_ipv4_prefix,
+drop column clearance_ipv6_prefix,
+drop column clearance_bind_user_agent;
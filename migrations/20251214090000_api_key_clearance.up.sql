@@ -0,0 +1,8 @@
+alter table public.api_key
+add column clearance_lifetime_secs integer not null default 1800,
+add column clearance_ipv4_prefix smallint not null default 24,
+add column clearance_ipv6_prefix smallint not null default 64,
+add column clearance_bind_user_agent boolean not null default true,
+add constraint api_key_clearance_lifetime_secs_range check (clearance_lifetime_secs between 0 and 86400),
+add constraint api_key_clearance_ipv4_prefix_range check (clearance_ipv4_prefix between 0 and 32),
+add constraint api_key_clearance_ipv6_prefix_range check (clearance_ipv6_prefix between 0 and 128);
@@ -0,0 +1,3 @@
+alter table public.api_key
+add column min_score real not null default 0.5,
+add constraint api_key_min_score_range check (min_score between 0 and 1);
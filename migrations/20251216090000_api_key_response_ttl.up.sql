@@ -0,0 +1,3 @@
+alter table public.api_key
+add column response_ttl_secs integer not null default 30,
+add constraint api_key_response_ttl_secs_range check (response_ttl_secs between 10 and 900);
@@ -0,0 +1,2 @@
+alter table public.consumed_response_token
+drop column idempotency_key;
@@ -0,0 +1,2 @@
+alter table public.consumed_response_token
+add column idempotency_key uuid;
@@ -0,0 +1,4 @@
+alter table public.api_key
+drop column verifying_key,
+drop column signing_key,
+drop column signing_algorithm;
@@ -0,0 +1,8 @@
+alter table public.api_key
+add column signing_algorithm varchar not null default 'HS256',
+add column signing_key varchar,
+add column verifying_key varchar,
+add constraint api_key_signing_algorithm_check check (signing_algorithm in ('HS256', 'ES256', 'EdDSA')),
+add constraint api_key_signing_key_check check (
+    signing_algorithm = 'HS256' or (signing_key is not null and verifying_key is not null)
+);
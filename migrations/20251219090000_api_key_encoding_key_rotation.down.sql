@@ -0,0 +1,5 @@
+alter table public.api_key
+drop constraint api_key_previous_encoding_key_check,
+drop column previous_encoding_key_expires_at,
+drop column previous_encoding_key,
+drop column encoding_key_version;
@@ -0,0 +1,7 @@
+alter table public.api_key
+add column encoding_key_version integer not null default 1,
+add column previous_encoding_key varchar,
+add column previous_encoding_key_expires_at timestamptz,
+add constraint api_key_previous_encoding_key_check check (
+    (previous_encoding_key is null) = (previous_encoding_key_expires_at is null)
+);
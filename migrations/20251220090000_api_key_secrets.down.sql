@@ -0,0 +1,21 @@
+alter table public.api_key
+add column secret varchar;
+
+-- keeps the oldest active secret, or the oldest one if all were revoked
+update public.api_key
+set
+    secret = (
+        select secret from api_key_secret
+        where api_key_secret.site_key = api_key.site_key
+        order by revoked_at is not null, created_at
+        limit 1
+    );
+
+drop table api_key_secret;
+
+alter table public.api_key
+alter column secret
+set
+    not null;
+
+alter table public.api_key add constraint api_key_secret_unique unique (secret);
@@ -0,0 +1,22 @@
+create table api_key_secret (
+    id uuid not null default gen_random_uuid (),
+    site_key varchar not null,
+    label varchar,
+    secret varchar not null,
+    created_at timestamptz not null default now(),
+    last_used_at timestamptz,
+    revoked_at timestamptz,
+    constraint api_key_secret_pkey primary key (id),
+    constraint api_key_secret_site_key_fkey foreign key (site_key) references api_key (site_key)
+        on delete cascade
+);
+
+insert into api_key_secret (site_key, label, secret)
+select site_key, 'default', secret from api_key;
+
+alter table public.api_key
+drop column secret;
+
+alter table public.api_key_secret add constraint api_key_secret_unique unique (secret);
+
+create index api_key_secret_site_key_idx on api_key_secret (site_key);
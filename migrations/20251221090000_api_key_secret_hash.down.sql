@@ -0,0 +1,28 @@
+-- hashed secrets and sealed encoding keys can't be recovered without the master key,
+-- so those secrets are dropped and those encoding keys regenerated
+delete from public.api_key_secret where secret is null;
+
+update public.api_key
+set
+    encoding_key = encode(
+        decode(md5(random()::text) || md5(random()::text) || md5(random()::text), 'hex'),
+        'base64'
+    ),
+    previous_encoding_key = null,
+    previous_encoding_key_expires_at = null
+where encoding_key like 'v1.%';
+
+alter table public.api_key_secret drop constraint api_key_secret_hashed_check;
+
+alter table public.api_key_secret drop constraint api_key_secret_unique;
+
+alter table public.api_key_secret
+drop column secret_hash,
+drop column secret_hint;
+
+alter table public.api_key_secret
+alter column secret
+set
+    not null;
+
+alter table public.api_key_secret add constraint api_key_secret_unique unique (secret);
@@ -0,0 +1,24 @@
+-- the master key isn't available to the migrations, so the server hashes the remaining plaintext secrets
+-- and seals the plaintext encoding keys on startup
+alter table public.api_key_secret
+add column secret_hash varchar,
+add column secret_hint varchar;
+
+update public.api_key_secret set secret_hint = right(secret, 4);
+
+alter table public.api_key_secret
+alter column secret_hint
+set
+    not null;
+
+alter table public.api_key_secret
+alter column secret
+drop not null;
+
+alter table public.api_key_secret drop constraint api_key_secret_unique;
+
+alter table public.api_key_secret add constraint api_key_secret_unique unique (secret_hash);
+
+alter table public.api_key_secret add constraint api_key_secret_hashed_check check (
+    secret is not null or secret_hash is not null
+);
@@ -0,0 +1,13 @@
+alter table public.api_key
+add column allowed_domains text[] not null default '{}';
+
+-- keeps the pending domains too, as they were allowed before the verification
+update public.api_key
+set
+    allowed_domains = array(
+        select domain from api_key_domain
+        where api_key_domain.site_key = api_key.site_key
+        order by created_at
+    );
+
+drop table api_key_domain;
@@ -0,0 +1,23 @@
+create table api_key_domain (
+    site_key varchar not null,
+    domain varchar not null,
+    token uuid not null default gen_random_uuid (),
+    created_at timestamptz not null default now(),
+    checked_at timestamptz,
+    verified_at timestamptz,
+    backfilled boolean not null default false,
+    constraint api_key_domain_pkey primary key (site_key, domain),
+    constraint api_key_domain_site_key_fkey foreign key (site_key) references api_key (site_key)
+        on delete cascade
+);
+
+-- domains allowed before the verification are trusted until they're checked, see `verified_at`, and flagged as
+-- backfilled
+insert into api_key_domain (site_key, domain, verified_at, backfilled)
+select site_key, domain, now(), true from api_key, unnest(allowed_domains) as domain
+on conflict do nothing;
+
+alter table public.api_key
+drop column allowed_domains;
+
+create index api_key_domain_checked_at_idx on api_key_domain (checked_at nulls first);
@@ -0,0 +1,3 @@
+alter table public.api_key
+add column mode varchar not null default 'live',
+add constraint api_key_mode_check check (mode in ('live', 'test-pass', 'test-fail'));
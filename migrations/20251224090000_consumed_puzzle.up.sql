@@ -0,0 +1,11 @@
+create table public.consumed_puzzle (
+    site_key character varying not null,
+    seed bigint not null,
+    issued_at timestamp with time zone not null,
+    expires_at timestamp with time zone not null,
+    constraint consumed_puzzle_pkey primary key (site_key, seed, issued_at),
+    constraint consumed_puzzle_site_key_fkey foreign key (site_key) references public.api_key (site_key)
+        on delete cascade
+);
+
+create index consumed_puzzle_expires_at_idx on public.consumed_puzzle (expires_at);
@@ -0,0 +1,6 @@
+alter table public.api_key
+drop constraint api_key_previous_verifying_key_check,
+drop constraint api_key_previous_signing_algorithm_check,
+drop column previous_verifying_key_expires_at,
+drop column previous_verifying_key,
+drop column previous_signing_algorithm;
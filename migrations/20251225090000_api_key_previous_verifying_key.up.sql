@@ -0,0 +1,9 @@
+alter table public.api_key
+add column previous_signing_algorithm varchar,
+add column previous_verifying_key varchar,
+add column previous_verifying_key_expires_at timestamptz,
+add constraint api_key_previous_signing_algorithm_check check (previous_signing_algorithm in ('ES256', 'EdDSA')),
+add constraint api_key_previous_verifying_key_check check (
+    (previous_verifying_key is null) = (previous_verifying_key_expires_at is null)
+    and (previous_verifying_key is null) = (previous_signing_algorithm is null)
+);
@@ -0,0 +1,2 @@
+alter table api_key_domain
+drop column exempt;
@@ -0,0 +1,6 @@
+-- exempt domains are allowed without a proof of ownership and never checked, like the domains allowed before the
+-- verification existed, backfilled by `api_key_domain_verification`
+alter table api_key_domain
+add column exempt boolean not null default false;
+
+update api_key_domain set exempt = true where backfilled;
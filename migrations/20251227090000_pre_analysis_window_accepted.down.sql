@@ -0,0 +1,2 @@
+alter table public.pre_analysis_window
+drop column accepted;
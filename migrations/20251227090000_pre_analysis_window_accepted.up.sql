@@ -0,0 +1,2 @@
+alter table public.pre_analysis_window
+add column accepted integer not null default 0;
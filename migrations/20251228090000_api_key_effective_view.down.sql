@@ -0,0 +1 @@
+drop view public.api_key_effective;
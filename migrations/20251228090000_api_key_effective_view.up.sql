@@ -0,0 +1,16 @@
+-- settings of the api keys as they're enforced: the previous keys are dropped once their grace period is over
+-- and only the verified domains are allowed
+create view public.api_key_effective as
+select site_key, console_id, created_at, encoding_key, encoding_key_version,
+    case when previous_encoding_key_expires_at > now() then previous_encoding_key end as previous_encoding_key,
+    label, array(
+        select domain from api_key_domain
+        where api_key_domain.site_key = api_key.site_key and verified_at is not null
+        order by created_at
+    ) as allowed_domains, pow_difficulty, pow_algorithm, min_score,
+    response_ttl_secs, pre_analysis_min_score, pre_analysis_required_signals, pre_analysis_max_pass_rate,
+    clearance_lifetime_secs, clearance_ipv4_prefix, clearance_ipv6_prefix, clearance_bind_user_agent,
+    signing_algorithm, signing_key, verifying_key, previous_signing_algorithm,
+    case when previous_verifying_key_expires_at > now() then previous_verifying_key end as previous_verifying_key,
+    mode
+from public.api_key;
@@ -1,2 +1,6 @@
 pub mod interaction;
+pub mod policy;
 pub mod proof_of_work;
+pub mod puzzle;
+pub mod scorer;
+pub mod signals;
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, VecDeque, hash_map::Entry};
 
 use fitting::Gaussian;
 use rayon::prelude::*;
@@ -6,9 +6,13 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 /// Stores an interaction event at a certain point in time.
-/// An interaction refers to actions of the user with the computer and currently it includes
-/// any mouse movement, mouse clicks, mouse enter and exit out of the target and key presses.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+/// An interaction refers to actions of the user with the computer and it includes mouse movements, clicks,
+/// mouse enter and exit out of the target, key presses, touches, pointer events, wheel and scroll, focus changes
+/// and changes of the page visibility.
+///
+/// Clients should report either pointer events or mouse and touch events. When pointer events are present, they
+/// take precedence over mouse and touch movements to analyse the trajectory.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Interaction {
     #[serde(with = "time::serde::timestamp::milliseconds")]
     ts: OffsetDateTime,
@@ -16,7 +20,7 @@ pub struct Interaction {
 }
 
 /// Describes the kind of event for the interaction.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "kind")]
 enum Event {
     #[serde(rename = "mousemovement")]
@@ -37,6 +41,66 @@ enum Event {
         up_down: UpDown,
         key: String,
     },
+    #[serde(rename = "touchstart")]
+    TouchStart(Touch),
+    #[serde(rename = "touchmove")]
+    TouchMove(Touch),
+    #[serde(rename = "touchend")]
+    TouchEnd(Touch),
+    #[serde(rename = "pointerdown")]
+    PointerDown(Pointer),
+    #[serde(rename = "pointermove")]
+    PointerMove(Pointer),
+    #[serde(rename = "pointerup")]
+    PointerUp(Pointer),
+    #[serde(rename = "wheel")]
+    Wheel {
+        #[serde(rename = "deltaX")]
+        delta_x: f32,
+        #[serde(rename = "deltaY")]
+        delta_y: f32,
+    },
+    #[serde(rename = "scroll")]
+    Scroll { x: i32, y: i32 },
+    #[serde(rename = "focus")]
+    Focus,
+    #[serde(rename = "blur")]
+    Blur,
+    #[serde(rename = "visibilitychange")]
+    VisibilityChange { visible: bool },
+}
+
+/// Touch point of a touch event.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Touch {
+    x: i32,
+    y: i32,
+    /// Normalized pressure from 0f to 1f, 0 when not supported.
+    #[serde(default)]
+    pressure: f32,
+    /// Radius of the contact area in pixels, 0 when not supported.
+    #[serde(default)]
+    radius: f32,
+}
+
+/// Pointer of a pointer event, which unifies mouse, pen and touch input.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Pointer {
+    #[serde(rename = "pointerType")]
+    pointer_type: PointerType,
+    x: i32,
+    y: i32,
+    /// Normalized pressure from 0f to 1f.
+    #[serde(default)]
+    pressure: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum PointerType {
+    Mouse,
+    Pen,
+    Touch,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -53,28 +117,213 @@ enum InOut {
     Out,
 }
 
+/// Role of an event in a trajectory.
+enum Contact {
+    /// Movement without contact with the screen, e.g. a mouse.
+    Hover,
+    /// Start of a stroke, e.g. a finger touching the screen.
+    Start,
+    /// Movement while in contact with the screen.
+    Move,
+    /// End of a stroke.
+    End,
+}
+
+impl Event {
+    /// Events delimiting discrete actions. Every other event is either continuous, like movements, or a change of state.
+    fn is_action(&self) -> bool {
+        matches!(
+            self,
+            Event::MouseClick { .. }
+                | Event::MouseEnter { .. }
+                | Event::KeyPress { .. }
+                | Event::TouchStart(_)
+                | Event::TouchEnd(_)
+                | Event::PointerDown(_)
+                | Event::PointerUp(_)
+        )
+    }
+
+    /// Events of a pointer moving, a key being pressed or the page scrolling.
+    fn is_input(&self) -> bool {
+        matches!(
+            self,
+            Event::MouseMovement { .. }
+                | Event::TouchMove(_)
+                | Event::PointerMove(_)
+                | Event::KeyPress { .. }
+                | Event::Wheel { .. }
+                | Event::Scroll { .. }
+        )
+    }
+
+    fn is_pointer(&self) -> bool {
+        matches!(
+            self,
+            Event::PointerDown(_) | Event::PointerMove(_) | Event::PointerUp(_)
+        )
+    }
+
+    /// Position of the event in a trajectory. Only pointer events are considered when `pointer_events` is set,
+    /// otherwise only mouse and touch events.
+    fn trajectory_point(&self, pointer_events: bool) -> Option<(Contact, i32, i32)> {
+        match (self, pointer_events) {
+            (Event::MouseMovement { x, y }, false) => Some((Contact::Hover, *x, *y)),
+            (Event::TouchStart(t), false) => Some((Contact::Start, t.x, t.y)),
+            (Event::TouchMove(t), false) => Some((Contact::Move, t.x, t.y)),
+            (Event::TouchEnd(t), false) => Some((Contact::End, t.x, t.y)),
+            (Event::PointerMove(p), true) => match p.pointer_type {
+                PointerType::Mouse => Some((Contact::Hover, p.x, p.y)),
+                PointerType::Pen | PointerType::Touch => Some((Contact::Move, p.x, p.y)),
+            },
+            (Event::PointerDown(p), true) if p.pointer_type != PointerType::Mouse => {
+                Some((Contact::Start, p.x, p.y))
+            }
+            (Event::PointerUp(p), true) if p.pointer_type != PointerType::Mouse => {
+                Some((Contact::End, p.x, p.y))
+            }
+            _ => None,
+        }
+    }
+
+    /// Pressure and radius of touch contacts.
+    fn contact_area(&self) -> Option<(f32, f32)> {
+        match self {
+            Event::TouchStart(t) | Event::TouchMove(t) | Event::TouchEnd(t) => {
+                Some((t.pressure, t.radius))
+            }
+            Event::PointerDown(p) | Event::PointerMove(p) | Event::PointerUp(p)
+                if p.pointer_type == PointerType::Touch =>
+            {
+                Some((p.pressure, 0.))
+            }
+            _ => None,
+        }
+    }
+}
+
 /// A score from 0f to 1f of how confident thinks a human interacted with the computer.
 pub struct Score(pub f32);
 
+/// Minimum number of movements to analyse the trajectory.
+const MIN_TRAJECTORY_POINTS: usize = 5;
+/// Minimum number of touch contacts to analyse their pressure and radius.
+const MIN_TOUCH_CONTACTS: usize = 3;
+/// Minimum number of wheel or scroll events to analyse scrolling.
+const MIN_SCROLLS: usize = 3;
+/// Minimum number of intervals between events to compute the timing entropy.
+const MIN_TIMING_INTERVALS: usize = 8;
+/// Minimum number of key presses to analyse keystroke dynamics.
+const MIN_KEYSTROKES: usize = 3;
+/// Mouse velocity in px/ms above which the pointer is considered to have teleported.
+const TELEPORT_VELOCITY: f64 = 20.;
+/// Width in milliseconds of the buckets used to compute the timing entropy.
+const TIMING_BUCKET_MS: i64 = 2;
+/// Number of timing buckets, the last one holds every longer interval.
+const TIMING_BUCKETS: usize = 128;
+
+/// Features extracted from a group of ordered interactions, each one scored from 0f to 1f.
+/// A feature is `None` when there aren't enough interactions to compute it.
+///
+/// Every human interaction moves a pointer, types or scrolls, so the features of that input are what earns
+/// the score. The focus consistency is scored on the mere presence of focus events, so it can only take
+/// the score down.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InteractionFeatures {
+    /// Timing of discrete actions like clicks and key presses.
+    pub actions: Option<f32>,
+    /// Curvature of the mouse, pen or touch trajectory. Scripted movements tend to follow straight lines.
+    pub curvature: Option<f32>,
+    /// Variation of the velocity along the trajectory. Scripts move at constant speed or teleport.
+    pub velocity: Option<f32>,
+    /// Changes of the acceleration along the trajectory. Humans keep speeding up and slowing down.
+    pub acceleration: Option<f32>,
+    /// Small corrections and tremor of the hand along the trajectory.
+    pub jitter: Option<f32>,
+    /// Variation of pressure and radius of touch contacts. Synthetic touches report constant values.
+    pub touch: Option<f32>,
+    /// Variation of wheel and scroll deltas. Scripts scroll by fixed steps.
+    pub scrolling: Option<f32>,
+    /// Consistency of input with the focus and visibility of the page. Scripts can dispatch events to hidden pages.
+    pub focus: Option<f32>,
+    /// Entropy of the time between events. Scripts tend to dispatch events at fixed intervals.
+    pub timing_entropy: Option<f32>,
+    /// Hold times and rhythm of key presses.
+    pub keystrokes: Option<f32>,
+    /// Whether there are pointer movements, key presses or scrolling at all.
+    pub input: bool,
+}
+
+impl InteractionFeatures {
+    pub fn extract(interactions: &[Interaction]) -> Self {
+        let trajectory = Trajectory::new(interactions);
+        Self {
+            actions: action_timing(interactions),
+            curvature: trajectory.as_ref().map(Trajectory::curvature_score),
+            velocity: trajectory.as_ref().map(Trajectory::velocity_score),
+            acceleration: trajectory.as_ref().map(Trajectory::acceleration_score),
+            jitter: trajectory.as_ref().map(Trajectory::jitter_score),
+            touch: touch_contacts(interactions),
+            scrolling: scrolling(interactions),
+            focus: focus_consistency(interactions),
+            timing_entropy: timing_entropy(interactions),
+            keystrokes: keystroke_dynamics(interactions),
+            input: interactions.iter().any(|it| it.event.is_input()),
+        }
+    }
+
+    /// Averages the features that could be computed, with a feature scored 0 in place of missing input,
+    /// and scales it by the focus consistency. Without any feature the score is 0.
+    pub fn score(&self) -> Score {
+        let features = [
+            self.actions,
+            self.curvature,
+            self.velocity,
+            self.acceleration,
+            self.jitter,
+            self.touch,
+            self.scrolling,
+            self.timing_entropy,
+            self.keystrokes,
+            (!self.input).then_some(0.),
+        ];
+        let (sum, count) = features
+            .into_iter()
+            .flatten()
+            .fold((0., 0), |(sum, count), score| (sum + score, count + 1));
+
+        Score(match count {
+            0 => 0.,
+            count => (sum / count as f32 * self.focus.unwrap_or(1.)).clamp(0., 1.),
+        })
+    }
+}
+
 /// Takes a group of ordered interactions and computes the score.
 pub fn interaction_analysis(interactions: &[Interaction]) -> Score {
+    let features = InteractionFeatures::extract(interactions);
+    tracing::debug!(?features, "interaction features");
+    features.score()
+}
+
+/// Scores the duration of actions, i.e. interactions delimited by events of the same nature.
+fn action_timing(interactions: &[Interaction]) -> Option<f32> {
     let mut actions: Vec<&[Interaction]> = vec![];
     let mut events_stacks = HashMap::<String, VecDeque<usize>>::new();
     let mut curr_action = 0;
     for (i, it) in interactions.iter().enumerate() {
-        // generic action delimited by any interaction besides movement
-        match it.event {
-            Event::MouseMovement { .. } => {}
-            _ => {
-                actions.push(&interactions[curr_action..=i]);
-                curr_action = i;
-            }
+        // generic action delimited by any discrete interaction
+        if it.event.is_action() {
+            actions.push(&interactions[curr_action..=i]);
+            curr_action = i;
         }
-        // actions delimited by events of the same nature: click, key press, mouse enter
+        // actions delimited by events of the same nature: click, key press, mouse enter, touch, pointer
         let key_to_push = match it.event {
             Event::MouseEnter { in_out: InOut::In } => Some("mouseenter"),
             Event::MouseClick { up_down: UpDown::Down } => Some("click"),
             Event::KeyPress { ref key, up_down: UpDown::Down } => Some(key.as_str()),
+            Event::TouchStart(_) => Some("touch"),
+            Event::PointerDown(_) => Some("pointer"),
             _ => None,
         };
         if let Some(key) = key_to_push {
@@ -87,6 +336,8 @@ pub fn interaction_analysis(interactions: &[Interaction]) -> Score {
             Event::MouseEnter { in_out: InOut::Out } => Some("mouseenter"),
             Event::MouseClick { up_down: UpDown::Up } => Some("click"),
             Event::KeyPress { ref key, up_down: UpDown::Up } => Some(key.as_str()),
+            Event::TouchEnd(_) => Some("touch"),
+            Event::PointerUp(_) => Some("pointer"),
             _ => None,
         };
         if let Some(last_i) =
@@ -109,21 +360,345 @@ pub fn interaction_analysis(interactions: &[Interaction]) -> Score {
                 | (
                     Interaction { ts: ts1, event: Event::KeyPress { up_down: UpDown::Down, .. } },
                     Interaction { ts: ts2, event: Event::KeyPress { up_down: UpDown::Up, .. } },
-                ) => timing_score_for_click(
-                    (ts1.unix_timestamp_nanos() / 1_000_000) as i64,
-                    (ts2.unix_timestamp_nanos() / 1_000_000) as i64,
-                ),
+                )
+                | (
+                    Interaction { ts: ts1, event: Event::TouchStart(_) },
+                    Interaction { ts: ts2, event: Event::TouchEnd(_) },
+                )
+                | (
+                    Interaction { ts: ts1, event: Event::PointerDown(_) },
+                    Interaction { ts: ts2, event: Event::PointerUp(_) },
+                ) => timing_score_for_click(millis(ts1), millis(ts2)),
                 _ => 0.5,
             },
         })
         .sum();
 
-    Score(match actions.len() {
-        0 => 0.,
-        len => score_sum / (len as f32),
+    match actions.len() {
+        0 => None,
+        len => Some(score_sum / (len as f32)),
+    }
+}
+
+/// Trajectory of the mouse, pen or fingers split in strokes of segments between consecutive movements.
+/// Hovering movements make up a single stroke, while each contact with the screen is a stroke of its own.
+struct Trajectory {
+    strokes: Vec<Vec<Segment>>,
+}
+
+struct Segment {
+    dx: f64,
+    dy: f64,
+    dt: f64,
+}
+
+impl Segment {
+    fn length(&self) -> f64 {
+        self.dx.hypot(self.dy)
+    }
+
+    /// Absolute angle in radians between the direction of two segments.
+    fn turn(&self, next: &Segment) -> f64 {
+        let cross = self.dx * next.dy - self.dy * next.dx;
+        let dot = self.dx * next.dx + self.dy * next.dy;
+        cross.atan2(dot).abs()
+    }
+}
+
+impl Trajectory {
+    fn new(interactions: &[Interaction]) -> Option<Self> {
+        let pointer_events = interactions.iter().any(|it| it.event.is_pointer());
+        let mut hover = vec![];
+        let mut contact: Option<Vec<_>> = None;
+        let mut points = vec![];
+        for it in interactions {
+            let Some((kind, x, y)) = it.event.trajectory_point(pointer_events) else {
+                continue;
+            };
+            let point = (millis(&it.ts), x, y);
+            match kind {
+                Contact::Hover => hover.push(point),
+                Contact::Start => points.extend(contact.replace(vec![point])),
+                Contact::Move => contact.get_or_insert_default().push(point),
+                Contact::End => {
+                    let mut stroke = contact.take().unwrap_or_default();
+                    stroke.push(point);
+                    points.push(stroke);
+                }
+            }
+        }
+        points.extend(contact);
+        points.push(hover);
+
+        let strokes: Vec<Vec<_>> = points
+            .into_iter()
+            .map(|stroke| {
+                stroke
+                    .windows(2)
+                    .map(|w| Segment {
+                        dx: (w[1].1 - w[0].1) as f64,
+                        dy: (w[1].2 - w[0].2) as f64,
+                        dt: (w[1].0 - w[0].0) as f64,
+                    })
+                    .collect()
+            })
+            .collect();
+        match strokes.iter().map(Vec::len).sum::<usize>() {
+            n if n + 1 < MIN_TRAJECTORY_POINTS => None,
+            _ => Some(Self { strokes }),
+        }
+    }
+
+    fn moving(&self) -> impl Iterator<Item = &Segment> {
+        self.strokes.iter().flatten().filter(|s| s.length() > 0.)
+    }
+
+    fn stroke_velocities(&self) -> impl Iterator<Item = Vec<f64>> {
+        self.strokes.iter().map(|stroke| {
+            stroke
+                .iter()
+                .filter(|s| s.dt > 0.)
+                .map(|s| s.length() / s.dt)
+                .collect()
+        })
+    }
+
+    fn velocities(&self) -> Vec<f64> {
+        self.stroke_velocities().flatten().collect()
+    }
+
+    fn turns(&self) -> Vec<f64> {
+        self.strokes
+            .iter()
+            .flat_map(|stroke| {
+                let moving: Vec<_> = stroke.iter().filter(|s| s.length() > 0.).collect();
+                moving
+                    .windows(2)
+                    .map(|w| w[0].turn(w[1]))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Straight lines score 0, while erratic trajectories that keep changing direction are only half trusted.
+    fn curvature_score(&self) -> f32 {
+        let turns = self.turns();
+        let Some(mean_turn) = mean(&turns) else {
+            return 0.;
+        };
+        match mean_turn {
+            t if t > 1.2 => 0.5,
+            t => ramp(t, 0.02, 0.15),
+        }
+    }
+
+    /// Constant speed scores 0 and teleporting pointers are penalized.
+    fn velocity_score(&self) -> f32 {
+        let velocities = self.velocities();
+        let Some(cv) = coefficient_of_variation(&velocities) else {
+            return 0.;
+        };
+        let teleported = velocities.iter().any(|&v| v > TELEPORT_VELOCITY);
+        let score = ramp(cv, 0.1, 0.4);
+        match teleported {
+            true => score * 0.2,
+            false => score,
+        }
+    }
+
+    /// Scores how often the acceleration changes sign.
+    fn acceleration_score(&self) -> f32 {
+        let accelerations: Vec<f64> = self
+            .stroke_velocities()
+            .flat_map(|velocities| {
+                velocities
+                    .windows(2)
+                    .map(|w| w[1] - w[0])
+                    .filter(|a| a.abs() > 1e-3)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if accelerations.len() < 2 {
+            return 0.;
+        }
+        let sign_changes = accelerations
+            .windows(2)
+            .filter(|w| w[0].signum() != w[1].signum())
+            .count();
+        ramp(
+            sign_changes as f64 / (accelerations.len() - 1) as f64,
+            0.05,
+            0.25,
+        )
+    }
+
+    /// Scores the ratio of micro movements and direction reversals. A perfectly smooth trajectory is suspicious
+    /// but so is one made only of noise.
+    fn jitter_score(&self) -> f32 {
+        let moving = self.moving().count();
+        if moving == 0 {
+            return 0.;
+        }
+        let micro = self.moving().filter(|s| s.length() <= 2.).count();
+        let reversals = self
+            .turns()
+            .into_iter()
+            .filter(|&t| t > std::f64::consts::FRAC_PI_2)
+            .count();
+        match (micro + reversals) as f64 / moving as f64 {
+            0. => 0.25,
+            r if r > 0.8 => 0.5,
+            _ => 1.,
+        }
+    }
+}
+
+/// Scores the variation of pressure and radius of touch contacts. Devices that don't report them send
+/// constant values as well, so it's only partially penalized.
+fn touch_contacts(interactions: &[Interaction]) -> Option<f32> {
+    let contacts: Vec<_> = interactions
+        .iter()
+        .filter_map(|it| it.event.contact_area())
+        .collect();
+    if contacts.len() < MIN_TOUCH_CONTACTS {
+        return None;
+    }
+
+    let (pressure, radius) = contacts[0];
+    let constant = contacts.iter().all(|&(p, r)| p == pressure && r == radius);
+    Some(match constant {
+        true => 0.25,
+        false => 1.,
+    })
+}
+
+/// Scores the variation of the wheel deltas and scroll offsets.
+fn scrolling(interactions: &[Interaction]) -> Option<f32> {
+    let mut deltas = vec![];
+    let mut last_scroll = None;
+    for it in interactions {
+        match it.event {
+            Event::Wheel { delta_x, delta_y } => deltas.push(delta_x.hypot(delta_y) as f64),
+            Event::Scroll { x, y } => {
+                if let Some((last_x, last_y)) = last_scroll.replace((x, y)) {
+                    deltas.push(((x - last_x) as f64).hypot((y - last_y) as f64));
+                }
+            }
+            _ => {}
+        }
+    }
+    if deltas.len() < MIN_SCROLLS {
+        return None;
+    }
+
+    Some(coefficient_of_variation(&deltas).map_or(0., |cv| ramp(cv, 0.05, 0.3)))
+}
+
+/// Checks that input was only received while the page was visible and key presses while it had focus.
+/// Without focus or visibility events there's nothing to check.
+fn focus_consistency(interactions: &[Interaction]) -> Option<f32> {
+    let mut tracked = false;
+    let mut focused = true;
+    let mut visible = true;
+    let mut inconsistent = false;
+    for it in interactions {
+        match it.event {
+            Event::Focus => (tracked, focused) = (true, true),
+            Event::Blur => (tracked, focused) = (true, false),
+            Event::VisibilityChange { visible: v } => (tracked, visible) = (true, v),
+            Event::KeyPress { .. } => inconsistent |= !focused || !visible,
+            ref event if event.is_action() => inconsistent |= !visible,
+            _ => {}
+        }
+    }
+
+    tracked.then_some(match inconsistent {
+        true => 0.,
+        false => 1.,
     })
 }
 
+/// Shannon entropy of the intervals between events, bucketed in a histogram.
+fn timing_entropy(interactions: &[Interaction]) -> Option<f32> {
+    if interactions.len() <= MIN_TIMING_INTERVALS {
+        return None;
+    }
+
+    let mut histogram = [0usize; TIMING_BUCKETS];
+    for w in interactions.windows(2) {
+        let dt = (millis(&w[1].ts) - millis(&w[0].ts)).max(0);
+        let bucket = ((dt / TIMING_BUCKET_MS) as usize).min(TIMING_BUCKETS - 1);
+        histogram[bucket] += 1;
+    }
+
+    let total = (interactions.len() - 1) as f64;
+    let entropy: f64 = histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    Some(ramp(entropy, 0.5, 2.))
+}
+
+/// Scores how long keys are held and the rhythm between key presses.
+fn keystroke_dynamics(interactions: &[Interaction]) -> Option<f32> {
+    let mut pressed = HashMap::<&str, i64>::new();
+    let mut holds = vec![];
+    let mut downs = vec![];
+    for it in interactions {
+        match &it.event {
+            Event::KeyPress { up_down: UpDown::Down, key } => {
+                // auto repeat fires key downs without releasing the key, the hold starts at the first one
+                if let Entry::Vacant(entry) = pressed.entry(key) {
+                    let ts = *entry.insert(millis(&it.ts));
+                    downs.push(ts as f64);
+                }
+            }
+            Event::KeyPress { up_down: UpDown::Up, key } => {
+                if let Some(ts) = pressed.remove(key.as_str()) {
+                    holds.push(timing_score_for_click(ts, millis(&it.ts)));
+                }
+            }
+            _ => {}
+        }
+    }
+    if holds.len() < MIN_KEYSTROKES {
+        return None;
+    }
+
+    let hold_score = holds.iter().sum::<f32>() / holds.len() as f32;
+    let flights: Vec<f64> = downs.windows(2).map(|w| w[1] - w[0]).collect();
+    let rhythm_score = coefficient_of_variation(&flights)
+        .map(|cv| ramp(cv, 0.05, 0.25))
+        .unwrap_or(0.);
+    Some((hold_score + rhythm_score) / 2.)
+}
+
+fn millis(ts: &OffsetDateTime) -> i64 {
+    (ts.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// Linear interpolation of `value` from 0 at `low` to 1 at `high`, clamped.
+fn ramp(value: f64, low: f64, high: f64) -> f32 {
+    ((value - low) / (high - low)).clamp(0., 1.) as f32
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    match values.len() {
+        0 => None,
+        len => Some(values.iter().sum::<f64>() / len as f64),
+    }
+}
+
+fn coefficient_of_variation(values: &[f64]) -> Option<f64> {
+    let mean = mean(values).filter(|&m| m > 0.)?;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    Some(variance.sqrt() / mean)
+}
+
 // Two Gaussian curves for track pad and mouse click timings overlapped
 fn timing_score_for_click(ts1: i64, ts2: i64) -> f32 {
     match ts2 - ts1 {
@@ -142,6 +717,7 @@ fn timing_score_for_click(ts1: i64, ts2: i64) -> f32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_helpers::human_path;
 
     use time::{Duration, OffsetDateTime};
 
@@ -176,4 +752,325 @@ mod tests {
 
         Ok(())
     }
+
+    fn human_trajectory(start: OffsetDateTime) -> Vec<Interaction> {
+        human_path(start)
+            .into_iter()
+            .map(|(ts, x, y)| Interaction { ts, event: Event::MouseMovement { x, y } })
+            .collect()
+    }
+
+    /// Swipe along the human path, with the finger pressing harder in the middle of the gesture.
+    fn human_swipe(start: OffsetDateTime) -> Vec<Interaction> {
+        let path = human_path(start);
+        let last = path.len() - 1;
+        path.into_iter()
+            .enumerate()
+            .map(|(i, (ts, x, y))| {
+                let pressure = 0.3 + 0.4 * (std::f32::consts::PI * i as f32 / last as f32).sin();
+                let touch = Touch { x, y, pressure, radius: 10. + pressure * 8. };
+                let event = match i {
+                    0 => Event::TouchStart(touch),
+                    i if i == last => Event::TouchEnd(touch),
+                    _ => Event::TouchMove(touch),
+                };
+                Interaction { ts, event }
+            })
+            .collect()
+    }
+
+    fn key_press(ts: OffsetDateTime, key: &str, hold: i64) -> [Interaction; 2] {
+        [
+            Interaction { ts, event: Event::KeyPress { up_down: UpDown::Down, key: key.into() } },
+            Interaction {
+                ts: ts + Duration::milliseconds(hold),
+                event: Event::KeyPress { up_down: UpDown::Up, key: key.into() },
+            },
+        ]
+    }
+
+    #[test]
+    fn no_interactions() {
+        let Score(score) = interaction_analysis(&[]);
+        assert_eq!(score, 0.);
+    }
+
+    #[test]
+    fn human_trajectory_scores_high() {
+        let features = InteractionFeatures::extract(&human_trajectory(OffsetDateTime::now_utc()));
+
+        for feature in [
+            features.curvature,
+            features.velocity,
+            features.acceleration,
+            features.jitter,
+        ] {
+            assert!(feature.expect("trajectory feature") >= 0.5, "{features:?}");
+        }
+        let Score(score) = features.score();
+        assert!(score >= 0.5, "{features:?}");
+    }
+
+    #[test]
+    fn scripted_trajectory_scores_low() {
+        let now = OffsetDateTime::now_utc();
+        let interactions: Vec<_> = (0..40)
+            .map(|i| Interaction {
+                ts: now + Duration::milliseconds(i * 10),
+                event: Event::MouseMovement { x: i as i32 * 5, y: i as i32 * 3 },
+            })
+            .collect();
+
+        let features = InteractionFeatures::extract(&interactions);
+        assert_eq!(features.curvature, Some(0.));
+        assert_eq!(features.velocity, Some(0.));
+        assert_eq!(features.timing_entropy, Some(0.));
+        let Score(score) = features.score();
+        assert!(score < 0.5, "{features:?}");
+    }
+
+    #[test]
+    fn teleporting_pointer_scores_low() {
+        let now = OffsetDateTime::now_utc();
+        let mut interactions = human_trajectory(now);
+        interactions.push(Interaction {
+            ts: interactions.last().unwrap().ts + Duration::milliseconds(1),
+            event: Event::MouseMovement { x: 2000, y: 2000 },
+        });
+
+        let features = InteractionFeatures::extract(&interactions);
+        assert!(features.velocity.unwrap() < 0.5, "{features:?}");
+    }
+
+    #[test]
+    fn keystroke_dynamics_score() {
+        let now = OffsetDateTime::now_utc();
+        let human: Vec<_> = [
+            (0, "h", 95),
+            (180, "e", 80),
+            (310, "l", 110),
+            (520, "l", 70),
+            (600, "o", 130),
+        ]
+        .into_iter()
+        .flat_map(|(at, key, hold)| key_press(now + Duration::milliseconds(at), key, hold))
+        .collect();
+        let script: Vec<_> = ["h", "e", "l", "l", "o"]
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, key)| key_press(now + Duration::milliseconds(i as i64 * 50), key, 0))
+            .collect();
+
+        assert!(keystroke_dynamics(&human).unwrap() >= 0.5);
+        assert!(keystroke_dynamics(&script).unwrap() < 0.5);
+    }
+
+    #[test]
+    fn keystroke_auto_repeat() {
+        let now = OffsetDateTime::now_utc();
+        let key_down = |at: i64| Interaction {
+            ts: now + Duration::milliseconds(at),
+            event: Event::KeyPress { up_down: UpDown::Down, key: "a".into() },
+        };
+        let others = || {
+            [(900, "b", 90), (1100, "c", 120)]
+                .into_iter()
+                .flat_map(|(at, key, hold)| key_press(now + Duration::milliseconds(at), key, hold))
+        };
+        let pressed: Vec<_> = key_press(now, "a", 600)
+            .into_iter()
+            .chain(others())
+            .collect();
+        let mut repeated: Vec<_> = (0..20).map(|i| key_down(i * 30)).collect();
+        repeated.extend(key_press(now, "a", 600).into_iter().skip(1));
+        repeated.extend(others());
+
+        // the key is held from its first key down, not from the last repeat
+        assert_eq!(keystroke_dynamics(&repeated), keystroke_dynamics(&pressed));
+    }
+
+    #[test]
+    fn deserialize_mobile_and_page_events() -> anyhow::Result<()> {
+        let interactions: Vec<Interaction> = serde_json::from_str(
+            r#"[
+                {"ts": 0, "event": {"kind": "touchstart", "x": 1, "y": 2, "pressure": 0.5, "radius": 11.5}},
+                {"ts": 1, "event": {"kind": "touchmove", "x": 2, "y": 3}},
+                {"ts": 2, "event": {"kind": "pointerdown", "pointerType": "pen", "x": 2, "y": 3, "pressure": 0.2}},
+                {"ts": 3, "event": {"kind": "wheel", "deltaX": 0, "deltaY": -120.5}},
+                {"ts": 4, "event": {"kind": "scroll", "x": 0, "y": 300}},
+                {"ts": 5, "event": {"kind": "blur"}},
+                {"ts": 6, "event": {"kind": "visibilitychange", "visible": false}}
+            ]"#,
+        )?;
+
+        assert_eq!(
+            interactions[0].event,
+            Event::TouchStart(Touch { x: 1, y: 2, pressure: 0.5, radius: 11.5 })
+        );
+        assert_eq!(
+            interactions[1].event,
+            Event::TouchMove(Touch { x: 2, y: 3, pressure: 0., radius: 0. })
+        );
+        assert_eq!(
+            interactions[2].event,
+            Event::PointerDown(Pointer {
+                pointer_type: PointerType::Pen,
+                x: 2,
+                y: 3,
+                pressure: 0.2
+            })
+        );
+        assert_eq!(
+            interactions[3].event,
+            Event::Wheel { delta_x: 0., delta_y: -120.5 }
+        );
+        assert_eq!(interactions[4].event, Event::Scroll { x: 0, y: 300 });
+        assert_eq!(interactions[5].event, Event::Blur);
+        assert_eq!(
+            interactions[6].event,
+            Event::VisibilityChange { visible: false }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn human_swipe_scores_high() {
+        let now = OffsetDateTime::now_utc();
+        let mut interactions = human_swipe(now);
+        // tap
+        let ts = interactions.last().unwrap().ts + Duration::milliseconds(400);
+        let touch = || Touch { x: 150, y: 80, pressure: 0.4, radius: 12. };
+        interactions.push(Interaction { ts, event: Event::TouchStart(touch()) });
+        interactions.push(Interaction {
+            ts: ts + Duration::milliseconds(90),
+            event: Event::TouchEnd(touch()),
+        });
+
+        let features = InteractionFeatures::extract(&interactions);
+        for feature in [
+            features.actions,
+            features.curvature,
+            features.velocity,
+            features.acceleration,
+            features.jitter,
+            features.touch,
+        ] {
+            assert!(feature.expect("touch feature") >= 0.5, "{features:?}");
+        }
+        let Score(score) = features.score();
+        assert!(score >= 0.5, "{features:?}");
+    }
+
+    #[test]
+    fn human_pen_strokes_score_high() {
+        let now = OffsetDateTime::now_utc();
+        let path = human_path(now);
+        let last = path.len() - 1;
+        let interactions: Vec<_> = path
+            .into_iter()
+            .enumerate()
+            .map(|(i, (ts, x, y))| {
+                let pointer = Pointer { pointer_type: PointerType::Pen, x, y, pressure: 0.5 };
+                let event = match i {
+                    0 => Event::PointerDown(pointer),
+                    i if i == last => Event::PointerUp(pointer),
+                    _ => Event::PointerMove(pointer),
+                };
+                Interaction { ts, event }
+            })
+            .collect();
+
+        let Score(score) = interaction_analysis(&interactions);
+        assert!(score >= 0.5);
+    }
+
+    #[test]
+    fn synthetic_touches_score_low() {
+        let now = OffsetDateTime::now_utc();
+        let touch = |i: i32| Touch { x: i * 5, y: i * 3, pressure: 1., radius: 1. };
+        let interactions: Vec<_> = (0..40)
+            .map(|i| Interaction {
+                ts: now + Duration::milliseconds(i as i64 * 10),
+                event: match i {
+                    0 => Event::TouchStart(touch(i)),
+                    39 => Event::TouchEnd(touch(i)),
+                    _ => Event::TouchMove(touch(i)),
+                },
+            })
+            .collect();
+
+        let features = InteractionFeatures::extract(&interactions);
+        assert_eq!(features.curvature, Some(0.));
+        assert_eq!(features.touch, Some(0.25));
+        let Score(score) = features.score();
+        assert!(score < 0.5, "{features:?}");
+    }
+
+    #[test]
+    fn scrolling_score() {
+        let now = OffsetDateTime::now_utc();
+        let wheel = |deltas: &[f32]| -> Vec<Interaction> {
+            deltas
+                .iter()
+                .enumerate()
+                .map(|(i, &delta_y)| Interaction {
+                    ts: now + Duration::milliseconds(i as i64 * 16),
+                    event: Event::Wheel { delta_x: 0., delta_y },
+                })
+                .collect()
+        };
+
+        let inertial = wheel(&[4., 18., 42., 35., 21., 9., 3., 1.]);
+        let fixed = wheel(&[100.; 8]);
+
+        assert!(scrolling(&inertial).unwrap() >= 0.5);
+        assert_eq!(scrolling(&fixed), Some(0.));
+    }
+
+    #[test]
+    fn focus_alone_scores_nothing() -> anyhow::Result<()> {
+        let interactions: Vec<Interaction> =
+            serde_json::from_str(r#"[{"ts": 0, "event": {"kind": "focus"}}]"#)?;
+
+        let features = InteractionFeatures::extract(&interactions);
+        assert_eq!(features.focus, Some(1.));
+        let Score(score) = features.score();
+        assert_eq!(score, 0.);
+
+        // clicks without moving the pointer are penalized as well
+        let now = OffsetDateTime::now_utc();
+        let interactions = [
+            Interaction { ts: now, event: Event::MouseClick { up_down: UpDown::Down } },
+            Interaction {
+                ts: now + Duration::milliseconds(90),
+                event: Event::MouseClick { up_down: UpDown::Up },
+            },
+        ];
+        let features = InteractionFeatures::extract(&interactions);
+        let Score(score) = features.score();
+        assert!(score <= 0.5, "{features:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn input_on_hidden_page() {
+        let now = OffsetDateTime::now_utc();
+        let mut interactions =
+            vec![Interaction { ts: now, event: Event::VisibilityChange { visible: false } }];
+        interactions.extend(key_press(now + Duration::milliseconds(100), "a", 80));
+
+        assert_eq!(focus_consistency(&interactions), Some(0.));
+        assert_eq!(focus_consistency(&key_press(now, "a", 80)), None);
+
+        interactions.insert(
+            1,
+            Interaction {
+                ts: now + Duration::milliseconds(50),
+                event: Event::VisibilityChange { visible: true },
+            },
+        );
+        assert_eq!(focus_consistency(&interactions), Some(1.));
+    }
 }
@@ -0,0 +1,103 @@
+//! Policy configured per api key that decides whether a visitor can skip the challenge after the pre analysis.
+
+use serde::{Deserialize, Serialize};
+
+use super::scorer::{SignalKind, Verdict};
+
+/// Pre analysis policy of an api key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreAnalysisPolicy {
+    /// Minimum score to skip the challenge.
+    pub min_score: f32,
+    /// Signals that must be assessed without any reason of concern.
+    #[serde(default)]
+    pub required_signals: Vec<SignalKind>,
+    /// Maximum fraction of the pre analyses that can skip the challenge within an hour, so a share of
+    /// the visitors keeps being challenged even if the signals are fooled.
+    pub max_pass_rate: f32,
+}
+
+impl Default for PreAnalysisPolicy {
+    fn default() -> Self {
+        Self { min_score: 0.7, required_signals: vec![], max_pass_rate: 1. }
+    }
+}
+
+impl PreAnalysisPolicy {
+    /// Validates the policy, describing the first invalid field.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0. ..=1.).contains(&self.min_score) {
+            return Err("min_score out of range [0:1]".into());
+        }
+        if !(0. ..=1.).contains(&self.max_pass_rate) {
+            return Err("max_pass_rate out of range [0:1]".into());
+        }
+        if self.required_signals.contains(&SignalKind::Challenge) {
+            return Err("challenge signal can't be required in the pre analysis".into());
+        }
+        Ok(())
+    }
+
+    /// Whether the verdict has enough score and every required signal is clean.
+    /// The pass rate is enforced separately, since it depends on the previous pre analyses.
+    pub fn accepts(&self, verdict: &Verdict) -> bool {
+        verdict.score >= self.min_score
+            && self
+                .required_signals
+                .iter()
+                .all(|&kind| verdict.is_clean(kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::scorer::{Assessment, Reason};
+
+    fn verdict(score: f32) -> Verdict {
+        Verdict {
+            score,
+            assessments: vec![
+                (SignalKind::Interaction, Assessment::trusted()),
+                (
+                    SignalKind::Headers,
+                    Assessment::new(0.5, vec![Reason::MissingAcceptLanguage]),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn accepts_score_above_threshold() {
+        let policy = PreAnalysisPolicy::default();
+
+        assert!(policy.accepts(&verdict(0.7)));
+        assert!(!policy.accepts(&verdict(0.69)));
+    }
+
+    #[test]
+    fn rejects_unclean_required_signals() {
+        let policy =
+            |required_signals| PreAnalysisPolicy { required_signals, ..Default::default() };
+
+        assert!(policy(vec![SignalKind::Interaction]).accepts(&verdict(1.)));
+        assert!(!policy(vec![SignalKind::Headers]).accepts(&verdict(1.)));
+        // not assessed
+        assert!(!policy(vec![SignalKind::ProofOfWork]).accepts(&verdict(1.)));
+    }
+
+    #[test]
+    fn validate_policy() {
+        assert!(PreAnalysisPolicy::default().validate().is_ok());
+        for policy in [
+            PreAnalysisPolicy { min_score: 1.1, ..Default::default() },
+            PreAnalysisPolicy { max_pass_rate: -0.1, ..Default::default() },
+            PreAnalysisPolicy {
+                required_signals: vec![SignalKind::Challenge],
+                ..Default::default()
+            },
+        ] {
+            assert!(policy.validate().is_err(), "{policy:?}");
+        }
+    }
+}
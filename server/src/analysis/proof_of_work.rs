@@ -1,61 +1,186 @@
-//! A proof of work is useful for preventing replay attacks. This proof of work is a simple challenge of finding X number of prefix zeros,
-//! that is determined by the difficulty.
+//! A proof of work is useful for preventing replay attacks. This proof of work is a challenge of finding a digest with X number of
+//! leading zero bits, that is determined by the difficulty. The digest is computed by the challenge's algorithm, which can be a
+//! memory-hard function to level the field between regular devices and GPU farms.
 
+use std::{fmt::Display, ops::RangeInclusive, str::FromStr};
+
+use argon2::{Algorithm, Argon2, Params, Version};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
 
+/// Hash function used to solve a proof of work challenge.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowAlgorithm {
+    /// SHA-256 with the difficulty counted in leading hex zeros, i.e. 4 bits per level.
+    /// Challenges without an algorithm are of this kind, for clients that predate the field.
+    #[default]
+    Sha256Hex,
+    /// SHA-256 with the difficulty counted in leading zero bits.
+    Sha256,
+    /// Argon2id with the difficulty counted in leading zero bits.
+    Argon2id,
+}
+
+impl PowAlgorithm {
+    /// Memory cost of each Argon2id hash in KiB.
+    pub const ARGON2_MEMORY_KIB: u32 = 4 * 1024;
+    /// Number of Argon2id passes.
+    pub const ARGON2_ITERATIONS: u32 = 1;
+    /// Degree of parallelism of Argon2id.
+    pub const ARGON2_PARALLELISM: u32 = 1;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowAlgorithm::Sha256Hex => "sha256-hex",
+            PowAlgorithm::Sha256 => "sha256",
+            PowAlgorithm::Argon2id => "argon2id",
+        }
+    }
+
+    /// Most leading zero bits a challenge of the algorithm can require and still be solved by a browser in a
+    /// few seconds. Each Argon2id hash is far more expensive than a SHA-256 one.
+    pub fn max_required_bits(&self) -> u32 {
+        match self {
+            PowAlgorithm::Sha256Hex | PowAlgorithm::Sha256 => 24,
+            PowAlgorithm::Argon2id => 6,
+        }
+    }
+
+    /// Highest difficulty of the algorithm, within its [`Self::max_required_bits`].
+    pub fn max_difficulty(&self) -> u16 {
+        (self.max_required_bits() / self.bits_per_difficulty()) as u16
+    }
+
+    /// Number of leading zero bits required by each level of difficulty.
+    fn bits_per_difficulty(&self) -> u32 {
+        match self {
+            PowAlgorithm::Sha256Hex => 4,
+            PowAlgorithm::Sha256 | PowAlgorithm::Argon2id => 1,
+        }
+    }
+}
+
 /// Proof of work challnge that it's sent to the client. The `nonce` and `timestamp` are the sources of randomness and uniqueness,
-/// while difficulty dictates how much prefixing zeros the solver has to find.
+/// while difficulty dictates how much leading zeros the solver has to find with the given `algorithm`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PowChallenge {
     pub nonce: u32,
     pub difficulty: u16,
     pub timestamp: i64,
+    #[serde(default)]
+    pub algorithm: PowAlgorithm,
 }
 
 impl PowChallenge {
-    pub fn random(difficulty: u16) -> Self {
+    /// Difficulty used when not configured otherwise.
+    pub const DEFAULT_DIFFICULTY: u16 = 3;
+    /// Range of difficulties a challenge can have, whatever its algorithm. Api keys are further limited by the
+    /// [`PowAlgorithm::max_difficulty`] of their algorithm.
+    pub const DIFFICULTY_RANGE: RangeInclusive<u16> = 1..=32;
+
+    pub fn random(algorithm: PowAlgorithm, difficulty: u16) -> Self {
         Self {
             nonce: rand::rng().random::<u32>(),
             difficulty,
             timestamp: OffsetDateTime::now_utc().unix_timestamp(),
+            algorithm,
         }
     }
 
     pub fn verify_solution(&self, solution: u32) -> bool {
-        if self.difficulty == 0 || self.difficulty > 32 {
+        if !Self::DIFFICULTY_RANGE.contains(&self.difficulty) {
             return false;
         }
 
-        let hash = self.hash_solution(solution);
-        Self::is_solution(&hash, self.difficulty)
+        let digest = self.digest(solution);
+        self.is_solution(&digest)
     }
 
-    pub fn hash_solution(&self, solution: u32) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(self.nonce.to_be_bytes());
-        hasher.update(self.difficulty.to_be_bytes());
-        hasher.update(self.timestamp.to_be_bytes());
-        hasher.update(solution.to_be_bytes());
-        format!("{:x}", hasher.finalize())
+    /// Digest of the challenge concatenated with the solution, all in big endian bytes.
+    /// For Argon2id the challenge is the salt and the solution is the password.
+    pub fn digest(&self, solution: u32) -> [u8; 32] {
+        let mut challenge = [0; 14];
+        challenge[..4].copy_from_slice(&self.nonce.to_be_bytes());
+        challenge[4..6].copy_from_slice(&self.difficulty.to_be_bytes());
+        challenge[6..].copy_from_slice(&self.timestamp.to_be_bytes());
+
+        match self.algorithm {
+            PowAlgorithm::Sha256Hex | PowAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                hasher.update(challenge);
+                hasher.update(solution.to_be_bytes());
+                hasher.finalize().into()
+            }
+            PowAlgorithm::Argon2id => {
+                let params = Params::new(
+                    PowAlgorithm::ARGON2_MEMORY_KIB,
+                    PowAlgorithm::ARGON2_ITERATIONS,
+                    PowAlgorithm::ARGON2_PARALLELISM,
+                    Some(32),
+                )
+                .expect("valid argon2 parameters");
+                let mut digest = [0; 32];
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(&solution.to_be_bytes(), &challenge, &mut digest)
+                    .expect("salt and output lengths are valid");
+                digest
+            }
+        }
     }
 
     pub fn solve(&self) -> u32 {
         let mut solution = 0;
         loop {
-            let hash = self.hash_solution(solution);
-            if Self::is_solution(&hash, self.difficulty) {
+            let digest = self.digest(solution);
+            if self.is_solution(&digest) {
                 return solution;
             }
             solution += 1;
         }
     }
 
-    fn is_solution(hash: &str, difficulty: u16) -> bool {
-        let prefix = "0".repeat(difficulty as usize);
-        hash.starts_with(&prefix)
+    /// Number of leading zero bits a solution must have.
+    pub fn required_bits(&self) -> u32 {
+        self.difficulty as u32 * self.algorithm.bits_per_difficulty()
+    }
+
+    fn is_solution(&self, digest: &[u8]) -> bool {
+        leading_zero_bits(digest) >= self.required_bits()
+    }
+}
+
+fn leading_zero_bits(digest: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+impl FromStr for PowAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256-hex" => Ok(PowAlgorithm::Sha256Hex),
+            "sha256" => Ok(PowAlgorithm::Sha256),
+            "argon2id" => Ok(PowAlgorithm::Argon2id),
+            other => Err(format!(
+                "{other} is not a supported proof of work algorithm"
+            )),
+        }
+    }
+}
+
+impl Display for PowAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
@@ -65,17 +190,23 @@ mod tests {
 
     #[test]
     fn successful_verify_solution() {
-        let challenge = PowChallenge::random(4);
+        for (algorithm, difficulty) in [
+            (PowAlgorithm::Sha256Hex, 4),
+            (PowAlgorithm::Sha256, 12),
+            (PowAlgorithm::Argon2id, 2),
+        ] {
+            let challenge = PowChallenge::random(algorithm, difficulty);
 
-        let solution = challenge.solve();
+            let solution = challenge.solve();
 
-        let result = challenge.verify_solution(solution);
-        assert!(result);
+            let result = challenge.verify_solution(solution);
+            assert!(result, "{algorithm} challenge");
+        }
     }
 
     #[test]
     fn failed_verify_solution() {
-        let challenge = PowChallenge::random(4);
+        let challenge = PowChallenge::random(PowAlgorithm::Sha256Hex, 4);
 
         let solution = challenge.solve() - 1;
 
@@ -83,14 +214,80 @@ mod tests {
         assert!(!result);
     }
 
+    #[test]
+    fn legacy_challenge_without_algorithm() {
+        let challenge: PowChallenge =
+            serde_json::from_str(r#"{"nonce":4077096492,"difficulty":4,"timestamp":1739555092}"#)
+                .unwrap();
+        assert_eq!(challenge.algorithm, PowAlgorithm::Sha256Hex);
+
+        let solution = challenge.solve();
+        let digest = challenge.digest(solution);
+        let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+
+        assert!(hex.starts_with("0000"));
+        assert!(challenge.verify_solution(solution));
+    }
+
+    #[test]
+    fn max_difficulty() {
+        assert_eq!(PowAlgorithm::Sha256Hex.max_difficulty(), 6);
+        assert_eq!(PowAlgorithm::Sha256.max_difficulty(), 24);
+        assert_eq!(PowAlgorithm::Argon2id.max_difficulty(), 6);
+        for algorithm in [
+            PowAlgorithm::Sha256Hex,
+            PowAlgorithm::Sha256,
+            PowAlgorithm::Argon2id,
+        ] {
+            let challenge = PowChallenge::random(algorithm, algorithm.max_difficulty());
+            assert_eq!(challenge.required_bits(), algorithm.max_required_bits());
+            assert!(PowChallenge::DIFFICULTY_RANGE.contains(&challenge.difficulty));
+        }
+    }
+
+    #[test]
+    fn count_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x10]), 11);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    /// Same challenge as the widget's proof of work test, so both agree on the Argon2id parameters.
+    #[test]
+    fn argon2id_widget_solution() {
+        let challenge = PowChallenge {
+            nonce: 4077096492,
+            difficulty: 4,
+            timestamp: 1739555092,
+            algorithm: PowAlgorithm::Argon2id,
+        };
+
+        let digest: String = challenge
+            .digest(0)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        assert_eq!(
+            digest,
+            "f53e957da654b03bdb1a57529c8ea02e86618570abfd4680ae1b6bc4061a7664"
+        );
+        assert_eq!(challenge.solve(), 6);
+        assert!(challenge.verify_solution(6));
+    }
+
     #[test]
     #[ignore = "useful for manually test values"]
     fn verify_specific_solution() {
-        let challenge = PowChallenge { nonce: 4077096492, difficulty: 4, timestamp: 1739555092 };
+        let challenge = PowChallenge {
+            nonce: 4077096492,
+            difficulty: 4,
+            timestamp: 1739555092,
+            algorithm: PowAlgorithm::Sha256Hex,
+        };
 
         let solution = 13062;
-        let hash = challenge.hash_solution(solution);
-        eprintln!("{hash}");
+        let digest = challenge.digest(solution);
+        eprintln!("{digest:02x?}");
 
         let actual_solution = challenge.solve();
         eprintln!("{actual_solution}");
@@ -0,0 +1,193 @@
+//! Server-generated puzzle instances. When a challenge is served, the server picks a random `seed` and signs it
+//! together with the challenge URL, the kind of puzzle and the layout the challenge renders. The widget submits the
+//! user's answer along with the signed instance, which is checked here by the validator of that kind of puzzle and
+//! can only be answered once.
+//!
+//! The seed is expanded with [Mulberry32](https://gist.github.com/tommyettinger/46a874533244883189143505d203312c)
+//! and a Fisher-Yates shuffle.
+
+use std::{fmt::Display, str::FromStr};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Kind of puzzle a challenge renders. It selects how the instance is generated and how the answer is validated.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PuzzleKind {
+    /// A set of items out of which the user has to pick the highlighted targets, e.g. the stars of a constellation.
+    /// The answer is the set of target indices, in any order.
+    #[default]
+    Selection,
+    /// A shuffled sequence of items the user has to put back in order, e.g. stacking cups by size.
+    /// The answer is the list of positions holding each item, from the smallest to the largest.
+    Ordering,
+}
+
+impl PuzzleKind {
+    /// Number of items in a selection puzzle.
+    pub const SELECTION_ITEMS: u16 = 9;
+    /// Number of targets to pick in a selection puzzle.
+    pub const SELECTION_TARGETS: usize = 3;
+    /// Number of items in an ordering puzzle.
+    pub const ORDERING_ITEMS: u16 = 5;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PuzzleKind::Selection => "selection",
+            PuzzleKind::Ordering => "ordering",
+        }
+    }
+}
+
+/// Puzzle instance that it's sent signed to the client. The `seed` is the source of randomness from which the
+/// puzzle is generated, while `challenge` binds the instance to the challenge it was issued for.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub challenge: Url,
+    pub kind: PuzzleKind,
+    pub seed: u32,
+}
+
+impl Puzzle {
+    pub fn random(challenge: Url, kind: PuzzleKind) -> Self {
+        Self { challenge, kind, seed: rand::rng().random::<u32>() }
+    }
+
+    /// Expands the seed into the puzzle layout the challenge renders.
+    /// - `Selection`: the indices of the targets, sorted.
+    /// - `Ordering`: the item shown at each position.
+    pub fn layout(&self) -> Vec<u16> {
+        let mut rng = Mulberry32(self.seed);
+        match self.kind {
+            PuzzleKind::Selection => {
+                let mut items = shuffled(PuzzleKind::SELECTION_ITEMS, &mut rng);
+                items.truncate(PuzzleKind::SELECTION_TARGETS);
+                items.sort_unstable();
+                items
+            }
+            PuzzleKind::Ordering => shuffled(PuzzleKind::ORDERING_ITEMS, &mut rng),
+        }
+    }
+
+    /// Checks the answer submitted by the widget against the validator of the puzzle kind.
+    pub fn verify_answer(&self, answer: &[u16]) -> bool {
+        match self.kind {
+            PuzzleKind::Selection => {
+                let mut answer = answer.to_vec();
+                answer.sort_unstable();
+                answer == self.layout()
+            }
+            PuzzleKind::Ordering => answer == self.solve(),
+        }
+    }
+
+    pub fn solve(&self) -> Vec<u16> {
+        let layout = self.layout();
+        match self.kind {
+            PuzzleKind::Selection => layout,
+            PuzzleKind::Ordering => {
+                let mut positions = vec![0; layout.len()];
+                for (position, &item) in layout.iter().enumerate() {
+                    positions[item as usize] = position as u16;
+                }
+                positions
+            }
+        }
+    }
+}
+
+/// Mulberry32 pseudo random generator.
+struct Mulberry32(u32);
+
+impl Mulberry32 {
+    fn next_u32(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(0x6D2B79F5);
+        let mut t = self.0;
+        t = (t ^ (t >> 15)).wrapping_mul(t | 1);
+        t ^= t.wrapping_add((t ^ (t >> 7)).wrapping_mul(t | 61));
+        t ^ (t >> 14)
+    }
+}
+
+/// Fisher-Yates shuffle of the items `0..len`.
+fn shuffled(len: u16, rng: &mut Mulberry32) -> Vec<u16> {
+    let mut items: Vec<u16> = (0..len).collect();
+    for i in (1..items.len()).rev() {
+        let j = rng.next_u32() as usize % (i + 1);
+        items.swap(i, j);
+    }
+    items
+}
+
+impl FromStr for PuzzleKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "selection" => Ok(PuzzleKind::Selection),
+            "ordering" => Ok(PuzzleKind::Ordering),
+            other => Err(format!("{other} is not a supported puzzle kind")),
+        }
+    }
+}
+
+impl Display for PuzzleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge() -> Url {
+        Url::parse("https://gotcha.test.com/constellation").unwrap()
+    }
+
+    #[test]
+    fn successful_verify_answer() {
+        for kind in [PuzzleKind::Selection, PuzzleKind::Ordering] {
+            let puzzle = Puzzle::random(challenge(), kind);
+
+            let answer = puzzle.solve();
+
+            assert!(puzzle.verify_answer(&answer), "{kind} puzzle");
+        }
+    }
+
+    #[test]
+    fn failed_verify_answer() {
+        for kind in [PuzzleKind::Selection, PuzzleKind::Ordering] {
+            let puzzle = Puzzle::random(challenge(), kind);
+
+            let mut answer = puzzle.solve();
+            answer[0] = (answer[0] + 1) % PuzzleKind::SELECTION_ITEMS;
+
+            assert!(!puzzle.verify_answer(&answer), "{kind} puzzle");
+            assert!(!puzzle.verify_answer(&[]), "{kind} puzzle");
+        }
+    }
+
+    #[test]
+    fn selection_answer_in_any_order() {
+        let puzzle = Puzzle::random(challenge(), PuzzleKind::Selection);
+
+        let mut answer = puzzle.solve();
+        answer.reverse();
+
+        assert!(puzzle.verify_answer(&answer));
+    }
+
+    #[test]
+    fn layout_is_deterministic() {
+        let puzzle = Puzzle { challenge: challenge(), kind: PuzzleKind::Ordering, seed: 42 };
+
+        assert_eq!(puzzle.layout(), puzzle.layout());
+        let mut sorted = puzzle.layout();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..PuzzleKind::ORDERING_ITEMS).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,295 @@
+//! Combines several risk signals into the score of a response. Each signal assesses one aspect of the request
+//! with a score from 0f to 1f, along with the reasons that drove it, and the scorer computes the weighted
+//! average of every assessment. A signal can also veto the request, which brings the score down to 0.
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Reason codes reported by the risk signals.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+    /// No interactions were reported.
+    NoInteractions,
+    /// The interactions look automated.
+    AutomatedInteractions,
+    /// The proof of work was solved faster than a regular device can.
+    ProofOfWorkTooFast,
+    /// The address is in the deny list.
+    DeniedAddress,
+    /// The `Accept-Language` header is missing.
+    MissingAcceptLanguage,
+    /// The client hints contradict the user agent.
+    InconsistentClientHints,
+    /// The challenge was not solved.
+    ChallengeFailed,
+}
+
+impl Reason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Reason::NoInteractions => "no-interactions",
+            Reason::AutomatedInteractions => "automated-interactions",
+            Reason::ProofOfWorkTooFast => "proof-of-work-too-fast",
+            Reason::DeniedAddress => "denied-address",
+            Reason::MissingAcceptLanguage => "missing-accept-language",
+            Reason::InconsistentClientHints => "inconsistent-client-hints",
+            Reason::ChallengeFailed => "challenge-failed",
+        }
+    }
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Kinds of risk signals.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignalKind {
+    Interaction,
+    ProofOfWork,
+    IpReputation,
+    Headers,
+    Challenge,
+}
+
+impl SignalKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignalKind::Interaction => "interaction",
+            SignalKind::ProofOfWork => "proof-of-work",
+            SignalKind::IpReputation => "ip-reputation",
+            SignalKind::Headers => "headers",
+            SignalKind::Challenge => "challenge",
+        }
+    }
+}
+
+impl FromStr for SignalKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interaction" => Ok(SignalKind::Interaction),
+            "proof-of-work" => Ok(SignalKind::ProofOfWork),
+            "ip-reputation" => Ok(SignalKind::IpReputation),
+            "headers" => Ok(SignalKind::Headers),
+            "challenge" => Ok(SignalKind::Challenge),
+            other => Err(format!("{other} is not a supported risk signal")),
+        }
+    }
+}
+
+impl Display for SignalKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Assessment of a risk signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assessment {
+    /// Score from 0f to 1f, the higher the more confident it is a human.
+    pub score: f32,
+    /// Reasons that drove the score.
+    pub reasons: Vec<Reason>,
+    /// Whether the signal alone is enough to reject the request.
+    pub veto: bool,
+}
+
+impl Assessment {
+    pub fn new(score: f32, reasons: Vec<Reason>) -> Self {
+        Self { score: score.clamp(0., 1.), reasons, veto: false }
+    }
+
+    /// Nothing suspicious was found.
+    pub fn trusted() -> Self {
+        Self::new(1., vec![])
+    }
+
+    /// Rejects the request regardless of the other signals.
+    pub fn veto(reason: Reason) -> Self {
+        Self { score: 0., reasons: vec![reason], veto: true }
+    }
+}
+
+/// Analyzer of one aspect of a request.
+pub trait RiskSignal {
+    fn kind(&self) -> SignalKind;
+
+    fn assess(&self) -> Assessment;
+}
+
+/// Weights of each risk signal in the final score. A weight of 0 disables the signal.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ScoreWeights {
+    pub interaction: f32,
+    pub proof_of_work: f32,
+    pub ip_reputation: f32,
+    pub headers: f32,
+    pub challenge: f32,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            interaction: 3.,
+            proof_of_work: 1.,
+            ip_reputation: 1.,
+            headers: 1.,
+            challenge: 2.,
+        }
+    }
+}
+
+/// Final score of a request with the assessment of every signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub score: f32,
+    pub assessments: Vec<(SignalKind, Assessment)>,
+}
+
+impl Verdict {
+    /// Reasons reported by every signal.
+    pub fn reasons(&self) -> Vec<Reason> {
+        self.assessments
+            .iter()
+            .flat_map(|(_, assessment)| assessment.reasons.iter().copied())
+            .collect()
+    }
+
+    /// Whether the signal was assessed without reporting any reason.
+    pub fn is_clean(&self, kind: SignalKind) -> bool {
+        self.assessments
+            .iter()
+            .any(|(k, assessment)| *k == kind && assessment.reasons.is_empty() && !assessment.veto)
+    }
+}
+
+/// Runs a set of weighted risk signals and combines them.
+#[derive(Default)]
+pub struct Scorer<'a> {
+    signals: Vec<(f32, Box<dyn RiskSignal + 'a>)>,
+}
+
+impl<'a> Scorer<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a signal with the given weight. Signals with no weight are not run.
+    pub fn with(mut self, weight: f32, signal: impl RiskSignal + 'a) -> Self {
+        if weight > 0. {
+            self.signals.push((weight, Box::new(signal)));
+        }
+        self
+    }
+
+    /// Computes the weighted average of the assessments. Without signals the score is 0.
+    pub fn evaluate(&self) -> Verdict {
+        let mut assessments = vec![];
+        let mut veto = false;
+        let (mut sum, mut total_weight) = (0., 0.);
+        for (weight, signal) in &self.signals {
+            let assessment = signal.assess();
+            tracing::debug!(signal = %signal.kind(), ?assessment, "risk signal assessed");
+            sum += weight * assessment.score;
+            total_weight += weight;
+            veto |= assessment.veto;
+            assessments.push((signal.kind(), assessment));
+        }
+
+        let score = match (veto, total_weight > 0.) {
+            (false, true) => (sum / total_weight).clamp(0., 1.),
+            _ => 0.,
+        };
+        Verdict { score, assessments }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(SignalKind, Assessment);
+
+    impl RiskSignal for Fixed {
+        fn kind(&self) -> SignalKind {
+            self.0
+        }
+
+        fn assess(&self) -> Assessment {
+            self.1.clone()
+        }
+    }
+
+    #[test]
+    fn weighted_average() {
+        let verdict = Scorer::new()
+            .with(3., Fixed(SignalKind::Interaction, Assessment::trusted()))
+            .with(
+                1.,
+                Fixed(
+                    SignalKind::Headers,
+                    Assessment::new(0., vec![Reason::MissingAcceptLanguage]),
+                ),
+            )
+            .evaluate();
+
+        assert_eq!(verdict.score, 0.75);
+        assert_eq!(verdict.reasons(), vec![Reason::MissingAcceptLanguage]);
+        assert!(verdict.is_clean(SignalKind::Interaction));
+        assert!(!verdict.is_clean(SignalKind::Headers));
+        assert!(!verdict.is_clean(SignalKind::ProofOfWork));
+    }
+
+    #[test]
+    fn veto_overrides_score() {
+        let verdict = Scorer::new()
+            .with(10., Fixed(SignalKind::Interaction, Assessment::trusted()))
+            .with(
+                1.,
+                Fixed(
+                    SignalKind::Challenge,
+                    Assessment::veto(Reason::ChallengeFailed),
+                ),
+            )
+            .evaluate();
+
+        assert_eq!(verdict.score, 0.);
+        assert_eq!(verdict.reasons(), vec![Reason::ChallengeFailed]);
+    }
+
+    #[test]
+    fn disabled_signals() {
+        let verdict = Scorer::new()
+            .with(
+                0.,
+                Fixed(
+                    SignalKind::Challenge,
+                    Assessment::veto(Reason::ChallengeFailed),
+                ),
+            )
+            .evaluate();
+
+        assert_eq!(verdict, Verdict { score: 0., assessments: vec![] });
+
+        let verdict = Scorer::new()
+            .with(1., Fixed(SignalKind::Interaction, Assessment::trusted()))
+            .with(
+                0.,
+                Fixed(
+                    SignalKind::Challenge,
+                    Assessment::veto(Reason::ChallengeFailed),
+                ),
+            )
+            .evaluate();
+
+        assert_eq!(verdict.score, 1.);
+    }
+}
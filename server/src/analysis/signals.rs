@@ -0,0 +1,260 @@
+//! Risk signals combined by the [`Scorer`](super::scorer::Scorer).
+
+use std::net::IpAddr;
+
+use axum::http::{HeaderMap, header};
+use ipnetwork::IpNetwork;
+use time::OffsetDateTime;
+
+use super::{
+    interaction::{self, Interaction, Score},
+    proof_of_work::{PowAlgorithm, PowChallenge},
+    scorer::{Assessment, Reason, RiskSignal, SignalKind},
+};
+
+/// Heuristics on the interactions of the user, see [`interaction::interaction_analysis`].
+/// No human can go through a challenge without interacting, so the lack of interactions rejects the request.
+pub struct InteractionSignal<'a>(pub &'a [Interaction]);
+
+impl RiskSignal for InteractionSignal<'_> {
+    fn kind(&self) -> SignalKind {
+        SignalKind::Interaction
+    }
+
+    fn assess(&self) -> Assessment {
+        if self.0.is_empty() {
+            return Assessment::veto(Reason::NoInteractions);
+        }
+
+        // TODO: potentially heavy CPU operation - offload to a task
+        let Score(score) = interaction::interaction_analysis(self.0);
+        let reasons = match score {
+            s if s < 0.5 => vec![Reason::AutomatedInteractions],
+            _ => vec![],
+        };
+        Assessment::new(score, reasons)
+    }
+}
+
+/// Time it took to solve a proof of work challenge. Solving it much faster than a regular device can is a sign
+/// of dedicated hardware.
+pub struct ProofOfWorkLatency<'a> {
+    pub challenge: &'a PowChallenge,
+    pub solved_at: OffsetDateTime,
+}
+
+impl ProofOfWorkLatency<'_> {
+    /// Hashes per second of a fast regular device.
+    fn max_hash_rate(algorithm: PowAlgorithm) -> f64 {
+        match algorithm {
+            PowAlgorithm::Sha256Hex | PowAlgorithm::Sha256 => 1_000_000.,
+            PowAlgorithm::Argon2id => 500.,
+        }
+    }
+
+    /// Fraction of the expected solving time below which it's considered too fast. It is low enough to
+    /// allow lucky solvers.
+    const LUCK_FACTOR: f64 = 1. / 32.;
+}
+
+impl RiskSignal for ProofOfWorkLatency<'_> {
+    fn kind(&self) -> SignalKind {
+        SignalKind::ProofOfWork
+    }
+
+    fn assess(&self) -> Assessment {
+        let expected_attempts = 2f64.powi(self.challenge.required_bits() as i32);
+        let min_secs =
+            expected_attempts / Self::max_hash_rate(self.challenge.algorithm) * Self::LUCK_FACTOR;
+        let elapsed_secs = (self.solved_at.unix_timestamp() - self.challenge.timestamp) as f64;
+
+        match elapsed_secs < min_secs {
+            true => Assessment::new(0.25, vec![Reason::ProofOfWorkTooFast]),
+            false => Assessment::trusted(),
+        }
+    }
+}
+
+/// Reputation of the address of the client. Denied addresses are rejected.
+pub struct IpReputation<'a> {
+    pub addr: IpAddr,
+    pub denylist: &'a [IpNetwork],
+}
+
+impl RiskSignal for IpReputation<'_> {
+    fn kind(&self) -> SignalKind {
+        SignalKind::IpReputation
+    }
+
+    fn assess(&self) -> Assessment {
+        match self
+            .denylist
+            .iter()
+            .any(|network| network.contains(self.addr))
+        {
+            true => Assessment::veto(Reason::DeniedAddress),
+            false => Assessment::trusted(),
+        }
+    }
+}
+
+/// Consistency of the request headers with what browsers send.
+pub struct HeaderConsistency<'a>(pub &'a HeaderMap);
+
+impl HeaderConsistency<'_> {
+    /// Tokens of the user agent expected for each `Sec-CH-UA-Platform`.
+    const PLATFORMS: [(&'static str, &'static [&'static str]); 6] = [
+        ("Windows", &["Windows"]),
+        ("macOS", &["Macintosh", "Mac OS"]),
+        ("Linux", &["Linux", "X11"]),
+        ("Android", &["Android"]),
+        ("iOS", &["iPhone", "iPad", "iPod"]),
+        ("Chrome OS", &["CrOS"]),
+    ];
+
+    fn header(&self, name: impl header::AsHeaderName) -> Option<&str> {
+        self.0.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    fn inconsistent_client_hints(&self, user_agent: &str) -> bool {
+        let mobile_ua = ["Mobile", "Android", "iPhone", "iPad"]
+            .iter()
+            .any(|token| user_agent.contains(token));
+        let inconsistent_mobile = match self.header("sec-ch-ua-mobile") {
+            Some("?1") => !mobile_ua,
+            Some("?0") => mobile_ua && !user_agent.contains("iPad"),
+            _ => false,
+        };
+
+        let inconsistent_platform = self
+            .header("sec-ch-ua-platform")
+            .map(|platform| platform.trim_matches('"'))
+            .and_then(|platform| Self::PLATFORMS.iter().find(|(name, _)| *name == platform))
+            .is_some_and(|(_, tokens)| !tokens.iter().any(|token| user_agent.contains(token)));
+
+        inconsistent_mobile || inconsistent_platform
+    }
+}
+
+impl RiskSignal for HeaderConsistency<'_> {
+    fn kind(&self) -> SignalKind {
+        SignalKind::Headers
+    }
+
+    fn assess(&self) -> Assessment {
+        let mut reasons = vec![];
+        if self.header(header::ACCEPT_LANGUAGE).is_none() {
+            reasons.push(Reason::MissingAcceptLanguage);
+        }
+        if self
+            .header(header::USER_AGENT)
+            .is_some_and(|user_agent| self.inconsistent_client_hints(user_agent))
+        {
+            reasons.push(Reason::InconsistentClientHints);
+        }
+
+        Assessment::new(1. - 0.5 * reasons.len() as f32, reasons)
+    }
+}
+
+/// Outcome of the challenge. Failing it rejects the request.
+pub struct ChallengeOutcome {
+    pub solved: bool,
+}
+
+impl RiskSignal for ChallengeOutcome {
+    fn kind(&self) -> SignalKind {
+        SignalKind::Challenge
+    }
+
+    fn assess(&self) -> Assessment {
+        match self.solved {
+            true => Assessment::trusted(),
+            false => Assessment::veto(Reason::ChallengeFailed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    const CHROME_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn consistent_headers() {
+        let headers = headers(&[
+            ("user-agent", CHROME_WINDOWS),
+            ("accept-language", "en-US,en;q=0.9"),
+            ("sec-ch-ua-mobile", "?0"),
+            ("sec-ch-ua-platform", "\"Windows\""),
+        ]);
+
+        assert_eq!(HeaderConsistency(&headers).assess(), Assessment::trusted());
+    }
+
+    #[test]
+    fn inconsistent_headers() {
+        let headers = headers(&[
+            ("user-agent", CHROME_WINDOWS),
+            ("sec-ch-ua-mobile", "?1"),
+            ("sec-ch-ua-platform", "\"Android\""),
+        ]);
+
+        let assessment = HeaderConsistency(&headers).assess();
+        assert_eq!(assessment.score, 0.);
+        assert_eq!(
+            assessment.reasons,
+            vec![
+                Reason::MissingAcceptLanguage,
+                Reason::InconsistentClientHints
+            ]
+        );
+    }
+
+    #[test]
+    fn proof_of_work_latency() {
+        let pow_challenge = |algorithm, difficulty| PowChallenge {
+            nonce: 4077096492,
+            difficulty,
+            timestamp: 1739555092,
+            algorithm,
+        };
+        let solved_at = OffsetDateTime::from_unix_timestamp(1739555093).unwrap();
+
+        let challenge = pow_challenge(PowAlgorithm::Sha256, 32);
+        let assessment = ProofOfWorkLatency { challenge: &challenge, solved_at }.assess();
+        assert_eq!(assessment.reasons, vec![Reason::ProofOfWorkTooFast]);
+
+        let challenge = pow_challenge(PowAlgorithm::Sha256Hex, 3);
+        let assessment = ProofOfWorkLatency { challenge: &challenge, solved_at }.assess();
+        assert_eq!(assessment, Assessment::trusted());
+    }
+
+    #[test]
+    fn denied_address() {
+        let denylist = [
+            "10.0.0.0/8".parse().unwrap(),
+            "2001:db8::/32".parse().unwrap(),
+        ];
+
+        for (addr, denied) in [
+            ("10.1.2.3", true),
+            ("2001:db8::1", true),
+            ("192.168.1.1", false),
+        ] {
+            let assessment =
+                IpReputation { addr: addr.parse().unwrap(), denylist: &denylist }.assess();
+            assert_eq!(assessment.veto, denied, "{addr}");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Emulator of the public API for the integration tests of the backends, without a database.
+//!
+//! ```text
+//! gotcha-emulator [CONFIG_FILE] [--key SITE_KEY:SECRET[:MODE]]...
+//! ```
+//!
+//! The keys are read from the `keys` of the YAML configuration file and from the `--key` arguments.
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+use gotcha_server::configuration::{self, EmulatorKeyConfig};
+
+const USAGE: &str = "usage: gotcha-emulator [CONFIG_FILE] [--key SITE_KEY:SECRET[:MODE]]...";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    gotcha_server::init_tracing();
+
+    let mut config_file = None;
+    let mut keys = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => {
+                let key = args.next().context(USAGE)?;
+                keys.push(
+                    key.parse::<EmulatorKeyConfig>()
+                        .map_err(anyhow::Error::msg)?,
+                );
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if arg.starts_with('-') || config_file.is_some() => anyhow::bail!(USAGE),
+            _ => config_file = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let mut config = configuration::get_emulator_configuration(config_file.as_deref())
+        .context("failed to load emulator configuration")?;
+    config.keys.extend(keys);
+    anyhow::ensure!(!config.keys.is_empty(), "no keys given\n{USAGE}");
+
+    let addr = format!("{}:{}", config.host, config.port);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+
+    // the logs of the binary are filtered out by the tracing of the library
+    println!("Listening on {}", listener.local_addr()?);
+    axum::serve(
+        listener,
+        gotcha_server::emulator(config)
+            .into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
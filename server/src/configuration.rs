@@ -1,8 +1,20 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use ipnetwork::IpNetwork;
 use secrecy::Secret;
 use serde::Deserialize;
 
+use crate::{
+    analysis::scorer::ScoreWeights,
+    crypto::MasterKey,
+    domain::{allowed_domain::AllowedDomain, api_key_mode::ApiKeyMode},
+    encodings::{Base64, UrlSafe},
+    routes::verification::DEFAULT_MIN_SCORE,
+};
+
 /// Global configuration.
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -18,6 +30,44 @@ pub struct ApplicationConfig {
     pub port: u16,
     pub serve_dir: PathBuf,
     pub auth_origin: String,
+    /// Master key in standard base64 that protects the api key credentials at rest.
+    pub master_key: MasterKey,
+    #[serde(default)]
+    pub analysis: AnalysisConfig,
+    #[serde(default)]
+    pub domain_verification: DomainVerificationConfig,
+}
+
+/// Risk analysis configuration.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AnalysisConfig {
+    /// Weights of each risk signal in the score.
+    pub weights: ScoreWeights,
+    /// Networks whose requests are rejected.
+    pub ip_denylist: Vec<IpNetwork>,
+}
+
+/// Configuration of the verification of the ownership of the allowed domains.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DomainVerificationConfig {
+    /// Seconds between the runs of the verifier, which is also how often a pending domain is checked.
+    pub interval_secs: u32,
+    /// Seconds between the checks of a verified domain.
+    pub recheck_interval_secs: u32,
+    /// Seconds a verified domain can fail the checks before it goes back to pending.
+    pub grace_period_secs: u32,
+}
+
+impl Default for DomainVerificationConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 5 * 60,
+            recheck_interval_secs: 24 * 60 * 60,
+            grace_period_secs: 7 * 24 * 60 * 60,
+        }
+    }
 }
 
 /// Database configuration.
@@ -32,6 +82,95 @@ pub struct DatabaseConfig {
     pub require_ssl: bool,
 }
 
+/// Configuration of the emulator, see [`crate::routes::emulator`].
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct EmulatorConfig {
+    pub host: String,
+    #[serde(with = "crate::serde::as_string")]
+    pub port: u16,
+    /// Directory with the `api.js` script of the widget.
+    pub serve_dir: PathBuf,
+    /// Api keys of the emulator, only kept in memory.
+    pub keys: Vec<EmulatorKeyConfig>,
+}
+
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".into(),
+            port: 8080,
+            serve_dir: "./dist".into(),
+            keys: Vec::new(),
+        }
+    }
+}
+
+/// Api key of the emulator.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmulatorKeyConfig {
+    pub site_key: Base64<UrlSafe>,
+    pub secret: Base64,
+    /// Test keys always pass or always fail the verification.
+    #[serde(default)]
+    pub mode: ApiKeyMode,
+    /// Domains the widget is allowed on, `localhost` and `127.0.0.1` by default.
+    #[serde(default = "EmulatorKeyConfig::default_allowed_domains")]
+    pub allowed_domains: Vec<AllowedDomain>,
+    /// Score of the responses, since the challenges are solved automatically.
+    #[serde(default = "EmulatorKeyConfig::default_score")]
+    pub score: f32,
+    /// Minimum score of a response to pass the verification.
+    #[serde(default = "EmulatorKeyConfig::default_min_score")]
+    pub min_score: f32,
+}
+
+impl EmulatorKeyConfig {
+    fn default_allowed_domains() -> Vec<AllowedDomain> {
+        ["localhost", "127.0.0.1"]
+            .into_iter()
+            .map(|domain| domain.parse().expect("valid domain"))
+            .collect()
+    }
+
+    fn default_score() -> f32 {
+        0.9
+    }
+
+    fn default_min_score() -> f32 {
+        DEFAULT_MIN_SCORE
+    }
+}
+
+impl FromStr for EmulatorKeyConfig {
+    type Err = String;
+
+    /// Parses a key given as `SITE_KEY:SECRET[:MODE]`, with the defaults of the other fields.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let (Some(site_key), Some(secret), mode, None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("{s} is not a key like SITE_KEY:SECRET[:MODE]"));
+        };
+
+        Ok(Self {
+            site_key: site_key
+                .to_owned()
+                .try_into()
+                .map_err(|_| format!("site key {site_key} is not url safe base64"))?,
+            secret: secret
+                .to_owned()
+                .try_into()
+                .map_err(|_| format!("secret {secret} is not base64"))?,
+            mode: mode.map(str::parse).transpose()?.unwrap_or_default(),
+            allowed_domains: Self::default_allowed_domains(),
+            score: Self::default_score(),
+            min_score: Self::default_min_score(),
+        })
+    }
+}
+
 /// Returns the server directory.
 pub fn server_dir() -> PathBuf {
     std::env::var("SERVER_DIR").map_or(std::env::current_dir().unwrap(), |p| {
@@ -69,6 +208,25 @@ pub fn get_configuration() -> Result<Config, config::ConfigError> {
     settings.try_deserialize::<Config>()
 }
 
+/// Loads the configuration of the emulator from a YAML file, if given, and from environment variables with a prefix
+/// of EMULATOR, e.g. `EMULATOR_PORT=5001`.
+pub fn get_emulator_configuration(
+    path: Option<&Path>,
+) -> Result<EmulatorConfig, config::ConfigError> {
+    let mut settings = config::Config::builder();
+    if let Some(path) = path {
+        settings = settings.add_source(config::File::from(path));
+    }
+    settings
+        .add_source(
+            config::Environment::with_prefix("EMULATOR")
+                .prefix_separator("_")
+                .separator("__"),
+        )
+        .build()?
+        .try_deserialize::<EmulatorConfig>()
+}
+
 /// Supported environments.
 pub enum Environment {
     Local,
@@ -0,0 +1,277 @@
+//! Protection of the api key credentials at rest. Secrets are only stored as a keyed hash, while encoding keys and
+//! the private keys that sign the response tokens are sealed, both with keys derived from the master key of the
+//! configuration.
+
+use std::{fmt::Debug, sync::Arc};
+
+use anyhow::Context;
+use base64::prelude::*;
+use rand::Rng;
+use ring::{aead, hkdf, hmac};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Deserializer};
+
+use crate::encodings::{Base64, UrlSafe};
+
+/// Prefix of the sealed encoding and signing keys, followed by the nonce and ciphertext in standard base64.
+pub const SEALED_PREFIX: &str = "v1.";
+
+/// Minimum size in bytes of the master key.
+pub const MIN_MASTER_KEY_SIZE: usize = 32;
+
+/// Number of trailing characters of a secret kept as a hint to tell the secrets apart.
+const HINT_LEN: usize = 4;
+
+/// Master key that derives the key that hashes the secrets and the key that seals the encoding and signing keys.
+#[derive(Clone)]
+pub struct MasterKey {
+    hash_key: hmac::Key,
+    seal_key: Arc<aead::LessSafeKey>,
+}
+
+impl MasterKey {
+    /// Creates the master key from its standard base64 encoding.
+    pub fn from_base64(encoded: &Secret<String>) -> anyhow::Result<Self> {
+        let master_key = Secret::new(
+            BASE64_STANDARD
+                .decode(encoded.expose_secret())
+                .context("master key is not valid base64")?,
+        );
+        anyhow::ensure!(
+            master_key.expose_secret().len() >= MIN_MASTER_KEY_SIZE,
+            "master key must have at least {MIN_MASTER_KEY_SIZE} bytes"
+        );
+
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &[]).extract(master_key.expose_secret());
+        let hash_key = prk
+            .expand(&[b"gotcha api key secret hash"], hmac::HMAC_SHA256)
+            .map(hmac::Key::from)
+            .map_err(|_| anyhow::anyhow!("could not derive the secret hash key"))?;
+        let seal_key = prk
+            .expand(&[b"gotcha encoding key seal"], &aead::AES_256_GCM)
+            .map(aead::UnboundKey::from)
+            .map(aead::LessSafeKey::new)
+            .map_err(|_| anyhow::anyhow!("could not derive the encoding key seal key"))?;
+
+        Ok(Self { hash_key, seal_key: Arc::new(seal_key) })
+    }
+
+    /// Keyed hash of a secret, encoded in standard base64. Secrets are looked up by their hash.
+    pub fn hash_secret(&self, secret: &Base64) -> String {
+        BASE64_STANDARD.encode(hmac::sign(&self.hash_key, secret.as_str().as_bytes()))
+    }
+
+    /// Seals an encoding key of an api key. The site key is authenticated along, so a sealed key can't be
+    /// moved to another api key.
+    pub fn seal(&self, site_key: &Base64<UrlSafe>, key: &Base64) -> anyhow::Result<String> {
+        let sealed = seal_with(&self.seal_key, site_key, key.as_str().as_bytes())
+            .context("could not seal encoding key")?;
+        Ok(format!("{SEALED_PREFIX}{sealed}"))
+    }
+
+    /// Opens an encoding key sealed for the given site key.
+    pub fn open(&self, site_key: &Base64<UrlSafe>, sealed: &str) -> anyhow::Result<Base64> {
+        let sealed = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .context("encoding key is not sealed")?;
+        let key = open_with(&self.seal_key, site_key, sealed)
+            .context("could not open sealed encoding key")?;
+
+        String::from_utf8(key)
+            .context("opened encoding key is not utf8")?
+            .try_into()
+            .context("opened encoding key is not valid base64")
+    }
+
+    /// Seals the private key of a signing keypair of an api key, encoded in standard base64, like the encoding keys.
+    pub fn seal_signing_key(
+        &self,
+        site_key: &Base64<UrlSafe>,
+        private_key: &Secret<String>,
+    ) -> anyhow::Result<String> {
+        let sealed = seal_with(
+            &self.seal_key,
+            site_key,
+            private_key.expose_secret().as_bytes(),
+        )
+        .context("could not seal signing key")?;
+        Ok(format!("{SEALED_PREFIX}{sealed}"))
+    }
+
+    /// Opens the private key of a signing keypair sealed for the given site key.
+    pub fn open_signing_key(
+        &self,
+        site_key: &Base64<UrlSafe>,
+        sealed: &str,
+    ) -> anyhow::Result<Secret<String>> {
+        let sealed = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .context("signing key is not sealed")?;
+        let key = open_with(&self.seal_key, site_key, sealed)
+            .context("could not open sealed signing key")?;
+
+        String::from_utf8(key)
+            .map(Secret::new)
+            .context("opened signing key is not utf8")
+    }
+}
+
+/// Seals the plaintext with a random nonce and the site key as additional data. The result is the nonce followed by
+/// the ciphertext, in standard base64.
+fn seal_with(
+    key: &aead::LessSafeKey,
+    site_key: &Base64<UrlSafe>,
+    plaintext: &[u8],
+) -> anyhow::Result<String> {
+    let nonce_bytes: [u8; aead::NONCE_LEN] = rand::rng().random();
+    let mut sealed = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        aead::Nonce::assume_unique_for_key(nonce_bytes),
+        aead::Aad::from(site_key.as_str()),
+        &mut sealed,
+    )
+    .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+
+    let mut payload = nonce_bytes.to_vec();
+    payload.append(&mut sealed);
+    Ok(BASE64_STANDARD.encode(payload))
+}
+
+/// Opens the output of [`seal_with`] sealed for the given site key.
+fn open_with(
+    key: &aead::LessSafeKey,
+    site_key: &Base64<UrlSafe>,
+    sealed: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let mut payload = BASE64_STANDARD
+        .decode(sealed)
+        .context("sealed payload is not valid base64")?;
+    anyhow::ensure!(
+        payload.len() > aead::NONCE_LEN,
+        "sealed payload is too short"
+    );
+
+    let mut sealed = payload.split_off(aead::NONCE_LEN);
+    let nonce = aead::Nonce::try_assume_unique_for_key(&payload)
+        .map_err(|_| anyhow::anyhow!("invalid nonce of sealed payload"))?;
+    let plaintext = key
+        .open_in_place(nonce, aead::Aad::from(site_key.as_str()), &mut sealed)
+        .map_err(|_| anyhow::anyhow!("decryption failed"))?;
+    Ok(plaintext.to_vec())
+}
+
+/// Hint of a secret, its last characters, shown instead of the secret once it's generated.
+pub fn secret_hint(secret: &Base64) -> String {
+    let secret = secret.as_str();
+    secret[secret.len().saturating_sub(HINT_LEN)..].to_string()
+}
+
+impl Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MasterKey([REDACTED])")
+    }
+}
+
+impl<'de> Deserialize<'de> for MasterKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = Secret::<String>::deserialize(deserializer)?;
+        Self::from_base64(&encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encodings::{KEY_SIZE, Standard};
+
+    fn master_key() -> MasterKey {
+        MasterKey::from_base64(&Secret::new(
+            Base64::<Standard>::random::<32>().as_str().into(),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let master_key = master_key();
+        let site_key = Base64::<UrlSafe>::random::<KEY_SIZE>();
+        let key = Base64::<Standard>::random::<KEY_SIZE>();
+
+        let sealed = master_key.seal(&site_key, &key).unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains(key.as_str()));
+        assert_eq!(master_key.open(&site_key, &sealed).unwrap(), key);
+    }
+
+    #[test]
+    fn open_with_another_site_key_or_master_key() {
+        let master_key = master_key();
+        let site_key = Base64::<UrlSafe>::random::<KEY_SIZE>();
+        let sealed = master_key
+            .seal(&site_key, &Base64::<Standard>::random::<KEY_SIZE>())
+            .unwrap();
+
+        let other_site_key = Base64::<UrlSafe>::random::<KEY_SIZE>();
+        assert!(master_key.open(&other_site_key, &sealed).is_err());
+        assert!(self::master_key().open(&site_key, &sealed).is_err());
+    }
+
+    #[test]
+    fn open_plaintext_key() {
+        let key = Base64::<Standard>::random::<KEY_SIZE>();
+        let site_key = Base64::<UrlSafe>::random::<KEY_SIZE>();
+        assert!(master_key().open(&site_key, key.as_str()).is_err());
+    }
+
+    #[test]
+    fn seal_and_open_signing_key() {
+        let master_key = master_key();
+        let site_key = Base64::<UrlSafe>::random::<KEY_SIZE>();
+        let private_key = Secret::new(BASE64_STANDARD.encode([7; 138]));
+
+        let sealed = master_key
+            .seal_signing_key(&site_key, &private_key)
+            .unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains(private_key.expose_secret()));
+        assert_eq!(
+            master_key
+                .open_signing_key(&site_key, &sealed)
+                .unwrap()
+                .expose_secret(),
+            private_key.expose_secret()
+        );
+        assert!(
+            master_key
+                .open_signing_key(&Base64::<UrlSafe>::random::<KEY_SIZE>(), &sealed)
+                .is_err()
+        );
+        assert!(
+            master_key
+                .open_signing_key(&site_key, private_key.expose_secret())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn hash_secret() {
+        let master_key = master_key();
+        let secret = Base64::<Standard>::random::<KEY_SIZE>();
+
+        assert_eq!(
+            master_key.hash_secret(&secret),
+            master_key.hash_secret(&secret)
+        );
+        assert_ne!(
+            master_key.hash_secret(&secret),
+            self::master_key().hash_secret(&secret)
+        );
+        assert_ne!(master_key.hash_secret(&secret), secret.as_str());
+    }
+
+    #[test]
+    fn short_master_key() {
+        let encoded = Secret::new(Base64::<Standard>::random::<16>().as_str().into());
+        assert!(MasterKey::from_base64(&encoded).is_err());
+    }
+}
@@ -92,6 +92,10 @@ impl<T, E> MapNested<T, E> for std::result::Result<Vec<T>, E> {
     }
 }
 
-fn api_key_decode_err(err: anyhow::Error) -> sqlx::Error {
-    sqlx::Error::Decode(err.into_boxed_dyn_error())
+fn api_key_decode_err(err: impl Into<anyhow::Error>) -> sqlx::Error {
+    sqlx::Error::Decode(err.into().into_boxed_dyn_error())
+}
+
+fn api_key_encode_err(err: anyhow::Error) -> sqlx::Error {
+    sqlx::Error::Encode(err.into_boxed_dyn_error())
 }
@@ -3,13 +3,30 @@
 use std::{fmt::Debug, ops::DerefMut};
 
 use anyhow::Context;
-use sqlx::{PgExecutor, Postgres, Transaction};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::{
+    analysis::{
+        policy::PreAnalysisPolicy,
+        proof_of_work::{PowAlgorithm, PowChallenge},
+        puzzle::{Puzzle, PuzzleKind},
+    },
+    crypto::{self, MasterKey, SEALED_PREFIX},
     db::MapNested,
-    domain::hostname::Hostname,
+    domain::{
+        allowed_domain::{self, AllowedDomain},
+        api_key_mode::ApiKeyMode,
+        hostname::Hostname,
+    },
     encodings::{Base64, UrlSafe},
+    tokens::{
+        clearance::ClearancePolicy,
+        keys::{DecodingKeys, VersionedKey},
+        signing::{SigningAlgorithm, SigningKeyPair, VerifyingKey},
+    },
 };
 
 use super::Error;
@@ -20,14 +37,33 @@ pub type Result<T> = ::core::result::Result<T, Error>;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RowsAffected(pub u64);
 
-/// Internal representation to benefit from sqlx compile time checks on queries
+/// Internal representation to benefit from sqlx compile time checks on queries, selected from the
+/// `api_key_effective` view. Its columns are all nullable to sqlx, hence the overrides in the queries.
 #[derive(Debug)]
 struct DbApiKeyInternal {
     pub label: Option<String>,
     pub site_key: String,
     pub encoding_key: String,
-    pub secret: String,
+    pub encoding_key_version: i32,
+    pub previous_encoding_key: Option<String>,
     pub allowed_domains: Vec<String>,
+    pub pow_difficulty: i16,
+    pub pow_algorithm: String,
+    pub min_score: f32,
+    pub response_ttl_secs: i32,
+    pub pre_analysis_min_score: f32,
+    pub pre_analysis_required_signals: Vec<String>,
+    pub pre_analysis_max_pass_rate: f32,
+    pub clearance_lifetime_secs: i32,
+    pub clearance_ipv4_prefix: i16,
+    pub clearance_ipv6_prefix: i16,
+    pub clearance_bind_user_agent: bool,
+    pub signing_algorithm: String,
+    pub signing_key: Option<String>,
+    pub verifying_key: Option<String>,
+    pub previous_signing_algorithm: Option<String>,
+    pub previous_verifying_key: Option<String>,
+    pub mode: String,
 }
 
 /// Database representation of an api key.
@@ -36,34 +72,139 @@ pub struct DbApiKey {
     pub label: Option<String>,
     pub site_key: Base64<UrlSafe>,
     pub encoding_key: Base64,
-    pub secret: Base64,
-    pub allowed_domains: Vec<Hostname>,
+    /// Version of the encoding key, incremented on each rotation.
+    pub encoding_key_version: i32,
+    /// Encoding key before the last rotation, present until its grace period ends.
+    pub previous_encoding_key: Option<Base64>,
+    /// Allowed domains whose ownership is verified. The pending ones aren't allowed yet.
+    pub allowed_domains: Vec<AllowedDomain>,
+    pub pow_difficulty: u16,
+    pub pow_algorithm: PowAlgorithm,
+    pub min_score: f32,
+    pub response_ttl_secs: u32,
+    pub pre_analysis_policy: PreAnalysisPolicy,
+    pub clearance_policy: ClearancePolicy,
+    /// Keypair that signs the response tokens, absent when they're signed with the encoding key (HS256).
+    pub signing_key: Option<SigningKeyPair>,
+    /// Public key of the keypair before the last change of signing algorithm, present until its grace period
+    /// ends.
+    pub previous_verifying_key: Option<VerifyingKey>,
+    /// Test keys always pass or always fail the verification.
+    pub mode: ApiKeyMode,
 }
 
-impl TryFrom<DbApiKeyInternal> for DbApiKey {
-    type Error = anyhow::Error;
+impl DbApiKey {
+    /// Current encoding key with its version, which encodes the tokens.
+    pub fn versioned_encoding_key(&self) -> VersionedKey<'_> {
+        VersionedKey::new(self.encoding_key_version, &self.encoding_key)
+    }
 
-    fn try_from(value: DbApiKeyInternal) -> std::result::Result<Self, Self::Error> {
-        Ok(Self {
-            label: value.label,
-            site_key: value
-                .site_key
-                .try_into()
-                .context("could not convert site_key from string")?,
-            encoding_key: value
-                .encoding_key
-                .try_into()
-                .context("could not convert encoding_key from string")?,
-            secret: value
-                .secret
-                .try_into()
-                .context("could not convert secret from string")?,
-            allowed_domains: value
+    /// Keys that decode the tokens, including the previous encoding key during its grace period.
+    pub fn decoding_keys(&self) -> DecodingKeys<'_> {
+        DecodingKeys {
+            current: self.versioned_encoding_key(),
+            previous: self
+                .previous_encoding_key
+                .as_ref()
+                .map(|key| VersionedKey::new(self.encoding_key_version - 1, key)),
+        }
+    }
+
+    /// Algorithm that signs the response tokens.
+    pub fn signing_algorithm(&self) -> SigningAlgorithm {
+        self.signing_key
+            .as_ref()
+            .map_or(SigningAlgorithm::HS256, SigningKeyPair::algorithm)
+    }
+
+    /// Public keys that verify the response tokens, including the previous one during its grace period.
+    pub fn verifying_keys(&self) -> impl Iterator<Item = &VerifyingKey> {
+        self.signing_key
+            .iter()
+            .map(SigningKeyPair::verifying_key)
+            .chain(&self.previous_verifying_key)
+    }
+}
+
+impl DbApiKeyInternal {
+    /// Converts the row into an api key, opening its sealed encoding and signing keys with the master key.
+    fn open(self, master_key: &MasterKey) -> anyhow::Result<DbApiKey> {
+        let signing_algorithm: SigningAlgorithm =
+            self.signing_algorithm.parse().map_err(anyhow::Error::msg)?;
+        let previous_verifying_key =
+            match (self.previous_signing_algorithm, self.previous_verifying_key) {
+                (Some(algorithm), Some(verifying_key)) => Some(
+                    VerifyingKey::from_base64(
+                        algorithm.parse().map_err(anyhow::Error::msg)?,
+                        &verifying_key,
+                    )
+                    .context("could not decode previous_verifying_key")?,
+                ),
+                _ => None,
+            };
+        let site_key: Base64<UrlSafe> = self
+            .site_key
+            .try_into()
+            .context("could not convert site_key from string")?;
+        let signing_key = match (signing_algorithm, self.signing_key, self.verifying_key) {
+            (SigningAlgorithm::HS256, _, _) => None,
+            (algorithm, Some(signing_key), Some(verifying_key)) => {
+                let private_key = master_key
+                    .open_signing_key(&site_key, &signing_key)
+                    .context("could not open signing_key")?;
+                Some(
+                    SigningKeyPair::from_base64(
+                        algorithm,
+                        private_key.expose_secret(),
+                        &verifying_key,
+                    )
+                    .context("could not decode signing keypair")?,
+                )
+            }
+            _ => anyhow::bail!("missing signing keypair for {signing_algorithm}"),
+        };
+
+        Ok(DbApiKey {
+            encoding_key: master_key
+                .open(&site_key, &self.encoding_key)
+                .context("could not open encoding_key")?,
+            encoding_key_version: self.encoding_key_version,
+            previous_encoding_key: self
+                .previous_encoding_key
+                .map(|key| master_key.open(&site_key, &key))
+                .transpose()
+                .context("could not open previous_encoding_key")?,
+            site_key,
+            label: self.label,
+            allowed_domains: self
                 .allowed_domains
                 .iter()
                 .map(String::as_str)
-                .map(Hostname::parse)
+                .map(AllowedDomain::parse)
                 .collect::<::core::result::Result<_, _>>()?,
+            pow_difficulty: self.pow_difficulty as u16,
+            pow_algorithm: self.pow_algorithm.parse().map_err(anyhow::Error::msg)?,
+            min_score: self.min_score,
+            response_ttl_secs: self.response_ttl_secs as u32,
+            pre_analysis_policy: PreAnalysisPolicy {
+                min_score: self.pre_analysis_min_score,
+                required_signals: self
+                    .pre_analysis_required_signals
+                    .iter()
+                    .map(|signal| signal.parse())
+                    .collect::<::core::result::Result<_, _>>()
+                    .map_err(anyhow::Error::msg)?,
+                max_pass_rate: self.pre_analysis_max_pass_rate,
+            },
+            clearance_policy: ClearancePolicy {
+                lifetime_secs: self.clearance_lifetime_secs as u32,
+                ipv4_prefix: self.clearance_ipv4_prefix as u8,
+                ipv6_prefix: self.clearance_ipv6_prefix as u8,
+                bind_user_agent: self.clearance_bind_user_agent,
+            },
+            signing_key,
+            previous_verifying_key,
+            mode: self.mode.parse().map_err(anyhow::Error::msg)?,
         })
     }
 }
@@ -71,72 +212,137 @@ impl TryFrom<DbApiKeyInternal> for DbApiKey {
 /// Tries to fetch the `api_key` given the `site_key`.
 pub async fn fetch_api_key_by_site_key(
     exec: impl PgExecutor<'_> + Send,
+    master_key: &MasterKey,
     site_key: &Base64<UrlSafe>,
 ) -> Result<Option<DbApiKey>> {
     sqlx::query_as!(
         DbApiKeyInternal,
-        "select site_key, encoding_key, secret, label, allowed_domains from api_key where site_key = $1",
+        r#"select site_key as "site_key!", encoding_key as "encoding_key!",
+            encoding_key_version as "encoding_key_version!", previous_encoding_key, label,
+            allowed_domains as "allowed_domains!", pow_difficulty as "pow_difficulty!",
+            pow_algorithm as "pow_algorithm!", min_score as "min_score!", response_ttl_secs as "response_ttl_secs!",
+            pre_analysis_min_score as "pre_analysis_min_score!",
+            pre_analysis_required_signals as "pre_analysis_required_signals!",
+            pre_analysis_max_pass_rate as "pre_analysis_max_pass_rate!",
+            clearance_lifetime_secs as "clearance_lifetime_secs!", clearance_ipv4_prefix as "clearance_ipv4_prefix!",
+            clearance_ipv6_prefix as "clearance_ipv6_prefix!",
+            clearance_bind_user_agent as "clearance_bind_user_agent!", signing_algorithm as "signing_algorithm!",
+            signing_key, verifying_key, previous_signing_algorithm, previous_verifying_key, mode as "mode!"
+        from api_key_effective where site_key = $1"#,
         site_key.as_str()
     )
     .fetch_optional(exec)
     .await
-    .map_nested_with(TryFrom::try_from, super::api_key_decode_err)
+    .map_nested_with(|key| key.open(master_key), super::api_key_decode_err)
     .map(Ok)?
 }
 
-/// Tries to fetch the `api_key` given the `secret`.
+/// Tries to fetch the `api_key` given any of its active `secret`s, looked up by their hash, recording when
+/// the secret was last used.
 pub async fn fetch_api_key_by_secret(
     exec: impl PgExecutor<'_> + Send,
+    master_key: &MasterKey,
     secret: &Base64,
 ) -> Result<Option<DbApiKey>> {
     sqlx::query_as!(
         DbApiKeyInternal,
-        "select site_key, encoding_key, secret, label, allowed_domains from api_key where secret = $1",
-        secret.as_str()
+        r#"with used_secret as (
+            update api_key_secret set last_used_at = now()
+            where secret_hash = $1 and revoked_at is null
+            returning site_key
+        )
+        select site_key as "site_key!", encoding_key as "encoding_key!",
+            encoding_key_version as "encoding_key_version!", previous_encoding_key, label,
+            allowed_domains as "allowed_domains!", pow_difficulty as "pow_difficulty!",
+            pow_algorithm as "pow_algorithm!", min_score as "min_score!", response_ttl_secs as "response_ttl_secs!",
+            pre_analysis_min_score as "pre_analysis_min_score!",
+            pre_analysis_required_signals as "pre_analysis_required_signals!",
+            pre_analysis_max_pass_rate as "pre_analysis_max_pass_rate!",
+            clearance_lifetime_secs as "clearance_lifetime_secs!", clearance_ipv4_prefix as "clearance_ipv4_prefix!",
+            clearance_ipv6_prefix as "clearance_ipv6_prefix!",
+            clearance_bind_user_agent as "clearance_bind_user_agent!", signing_algorithm as "signing_algorithm!",
+            signing_key, verifying_key, previous_signing_algorithm, previous_verifying_key, mode as "mode!"
+        from api_key_effective where site_key = (select site_key from used_secret)"#,
+        master_key.hash_secret(secret)
     )
     .fetch_optional(exec)
     .await
-    .map_nested_with(TryFrom::try_from, super::api_key_decode_err)
+    .map_nested_with(|key| key.open(master_key), super::api_key_decode_err)
     .map(Ok)?
 }
 
 /// Fetches all the `api_keys` for the given `console_id`.
 pub async fn fetch_api_keys(
     exec: impl PgExecutor<'_> + Send,
+    master_key: &MasterKey,
     console_id: &Uuid,
 ) -> Result<Vec<DbApiKey>> {
     sqlx::query_as!(
         DbApiKeyInternal,
-        "select site_key, encoding_key, secret, label, allowed_domains from api_key where console_id = $1 order by created_at",
+        r#"select site_key as "site_key!", encoding_key as "encoding_key!",
+            encoding_key_version as "encoding_key_version!", previous_encoding_key, label,
+            allowed_domains as "allowed_domains!", pow_difficulty as "pow_difficulty!",
+            pow_algorithm as "pow_algorithm!", min_score as "min_score!", response_ttl_secs as "response_ttl_secs!",
+            pre_analysis_min_score as "pre_analysis_min_score!",
+            pre_analysis_required_signals as "pre_analysis_required_signals!",
+            pre_analysis_max_pass_rate as "pre_analysis_max_pass_rate!",
+            clearance_lifetime_secs as "clearance_lifetime_secs!", clearance_ipv4_prefix as "clearance_ipv4_prefix!",
+            clearance_ipv6_prefix as "clearance_ipv6_prefix!",
+            clearance_bind_user_agent as "clearance_bind_user_agent!", signing_algorithm as "signing_algorithm!",
+            signing_key, verifying_key, previous_signing_algorithm, previous_verifying_key, mode as "mode!"
+        from api_key_effective where console_id = $1 order by created_at"#,
         console_id
     )
     .fetch_all(exec)
     .await
-    .map_nested_with(TryFrom::try_from, super::api_key_decode_err)
+    .map_nested_with(|key| key.open(master_key), super::api_key_decode_err)
     .map(Ok)?
 }
 
-/// Inserts a new `api_key`.
+/// Domains as the rows of `api_key_domain`, along with whether each one is exempt from the checks. The domains
+/// outside the policy of the allowed domains, like `localhost`, can't be proven.
+fn domain_rows(allowed_domains: &[AllowedDomain]) -> (Vec<String>, Vec<bool>) {
+    allowed_domains
+        .iter()
+        .map(|domain| (domain.to_string(), domain.validate_policy().is_err()))
+        .unzip()
+}
+
+/// Inserts a new `api_key` with its first `secret`, storing only its hash, and its sealed encoding key.
+/// The allowed domains are trusted, so they're inserted as verified. The ones outside the policy of the allowed
+/// domains, like `localhost`, can't be proven, so they're exempt from the checks.
 pub async fn insert_api_key(
     exec: impl PgExecutor<'_> + Send,
+    master_key: &MasterKey,
     site_key: &Base64<UrlSafe>,
     console_id: &Uuid,
     enc_key: &Base64,
     secret: &Base64,
-    allowed_domains: &[Hostname],
+    allowed_domains: &[AllowedDomain],
 ) -> Result<()> {
-    let allowed_domains = allowed_domains
-        .iter()
-        .map(|h| h.to_string())
-        .collect::<Vec<_>>();
+    let (allowed_domains, exempt_domains) = domain_rows(allowed_domains);
+    let sealed_enc_key = master_key
+        .seal(site_key, enc_key)
+        .map_err(super::api_key_encode_err)?;
 
     let _ = sqlx::query!(
-        "insert into api_key (site_key, console_id, encoding_key, secret, allowed_domains) values ($1, $2, $3, $4, $5)",
+        "with api_key as (
+            insert into api_key (site_key, console_id, encoding_key) values ($1, $2, $3)
+            returning site_key
+        ),
+        api_key_domain as (
+            insert into api_key_domain (site_key, domain, verified_at, exempt)
+            select site_key, domain, now(), exempt from api_key, unnest($6::text[], $7::bool[]) as t(domain, exempt)
+        )
+        insert into api_key_secret (site_key, label, secret_hash, secret_hint)
+        select site_key, 'default', $4, $5 from api_key",
         site_key.as_str(),
         console_id,
-        enc_key.as_str(),
-        secret.as_str(),
-        &allowed_domains
+        sealed_enc_key,
+        master_key.hash_secret(secret),
+        crypto::secret_hint(secret),
+        &allowed_domains,
+        &exempt_domains
     )
     .execute(exec)
     .await?;
@@ -161,90 +367,366 @@ pub async fn exists_api_key_for_console(
     .map(Ok)?
 }
 
-/// Checks if a domain is in the allowed list for a given `api_key`.
-pub async fn exists_allowed_domain_in_api_key(
+/// Allowed domains of an `api_key` whose ownership is verified, along with the mode of the `api_key`.
+#[derive(Debug)]
+pub struct DbAllowedDomains {
+    pub mode: ApiKeyMode,
+    pub domains: Vec<AllowedDomain>,
+}
+
+impl DbAllowedDomains {
+    /// Checks if a hostname is allowed by any of the domains. Test keys allow any hostname.
+    pub fn allows(&self, hostname: &Hostname) -> bool {
+        self.mode.is_test() || allowed_domain::is_allowed(&self.domains, hostname)
+    }
+}
+
+/// Fetches the allowed domains of an `api_key` whose ownership is verified.
+pub async fn fetch_allowed_domains(
     exec: impl PgExecutor<'_> + Send,
     site_key: &Base64<UrlSafe>,
-    domain: &Hostname,
-) -> Result<Option<bool>> {
-    sqlx::query_scalar!(
-        "select $2 = ANY(allowed_domains) as found_domain_for_site_key from api_key where site_key = $1",
+) -> Result<Option<DbAllowedDomains>> {
+    sqlx::query!(
+        r#"select mode, array(
+            select domain from api_key_domain
+            where api_key_domain.site_key = api_key.site_key and verified_at is not null
+        ) as "allowed_domains!"
+        from api_key where site_key = $1"#,
         site_key.as_str(),
-        domain.as_str(),
     )
     .fetch_optional(exec)
     .await
+    .map_nested_with(
+        |row| {
+            anyhow::Ok(DbAllowedDomains {
+                mode: row.mode.parse().map_err(anyhow::Error::msg)?,
+                domains: row
+                    .allowed_domains
+                    .iter()
+                    .map(String::as_str)
+                    .map(AllowedDomain::parse)
+                    .collect::<::core::result::Result<_, _>>()?,
+            })
+        },
+        super::api_key_decode_err,
+    )
+    .map(Ok)?
+}
+
+/// Internal representation to benefit from sqlx compile time checks on queries
+#[derive(Debug)]
+struct DbApiKeyDomainInternal {
+    pub site_key: String,
+    pub domain: String,
+    pub token: Uuid,
+    pub created_at: OffsetDateTime,
+    pub checked_at: Option<OffsetDateTime>,
+    pub verified_at: Option<OffsetDateTime>,
+    pub exempt: bool,
+}
+
+/// Database representation of an allowed domain of an api key and the verification of its ownership.
+#[derive(Debug)]
+pub struct DbApiKeyDomain {
+    pub site_key: Base64<UrlSafe>,
+    pub domain: AllowedDomain,
+    /// Token the owner of the domain serves to prove the ownership.
+    pub token: Uuid,
+    pub created_at: OffsetDateTime,
+    /// Last time the ownership was checked.
+    pub checked_at: Option<OffsetDateTime>,
+    /// Last time the ownership was proven. Absent while the domain is pending.
+    pub verified_at: Option<OffsetDateTime>,
+    /// Allowed without a proof of ownership and never checked, like the domains allowed before the verification
+    /// existed and the ones whose owner can't publish a proof, such as `localhost`.
+    pub exempt: bool,
+}
+
+impl TryFrom<DbApiKeyDomainInternal> for DbApiKeyDomain {
+    type Error = anyhow::Error;
+
+    fn try_from(value: DbApiKeyDomainInternal) -> ::core::result::Result<Self, Self::Error> {
+        Ok(DbApiKeyDomain {
+            site_key: value
+                .site_key
+                .try_into()
+                .context("could not convert site_key from string")?,
+            domain: AllowedDomain::parse(&value.domain)?,
+            token: value.token,
+            created_at: value.created_at,
+            checked_at: value.checked_at,
+            verified_at: value.verified_at,
+            exempt: value.exempt,
+        })
+    }
+}
+
+/// Fetches all the allowed domains of an `api_key`, including the pending ones.
+pub async fn fetch_api_key_domains(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+) -> Result<Vec<DbApiKeyDomain>> {
+    sqlx::query_as!(
+        DbApiKeyDomainInternal,
+        "select site_key, domain, token, created_at, checked_at, verified_at, exempt
+        from api_key_domain where site_key = $1 order by created_at",
+        site_key.as_str()
+    )
+    .fetch_all(exec)
+    .await
+    .map_nested_with(DbApiKeyDomain::try_from, super::api_key_decode_err)
+    .map(Ok)?
+}
+
+/// Claims the allowed domains whose ownership is due to be checked: the ones never checked, the pending ones
+/// checked more than `pending_interval_secs` ago and the verified ones checked more than `recheck_interval_secs`
+/// ago. The least recently checked come first. Exempt domains are never checked.
+///
+/// The domains are claimed by bumping when they were checked, so they aren't due anymore for the other instances
+/// of the verifier while they're being checked, without holding their rows locked.
+pub async fn claim_api_key_domains_to_check(
+    exec: impl PgExecutor<'_> + Send,
+    pending_interval_secs: u32,
+    recheck_interval_secs: u32,
+    limit: i64,
+) -> Result<Vec<DbApiKeyDomain>> {
+    sqlx::query_as!(
+        DbApiKeyDomainInternal,
+        "update api_key_domain set checked_at = now()
+        where (site_key, domain) in (
+            select site_key, domain from api_key_domain
+            where not exempt and (
+                checked_at is null
+                or (verified_at is null and checked_at < now() - make_interval(secs => $1))
+                or (verified_at is not null and checked_at < now() - make_interval(secs => $2))
+            )
+            order by checked_at nulls first
+            limit $3
+            for update skip locked
+        )
+        returning site_key, domain, token, created_at, checked_at, verified_at, exempt",
+        pending_interval_secs as f64,
+        recheck_interval_secs as f64,
+        limit
+    )
+    .fetch_all(exec)
+    .await
+    .map_nested_with(DbApiKeyDomain::try_from, super::api_key_decode_err)
+    .map(Ok)?
+}
+
+/// Records a check of the ownership of an allowed domain. A verified domain that fails the checks for longer than
+/// `grace_period_secs` goes back to pending, unless it's exempt.
+pub async fn update_api_key_domain_check(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+    domain: &AllowedDomain,
+    verified: bool,
+    grace_period_secs: u32,
+) -> Result<RowsAffected> {
+    let res = sqlx::query!(
+        "update api_key_domain set
+            checked_at = now(),
+            verified_at = case
+                when $3 then now()
+                when exempt then verified_at
+                when verified_at < now() - make_interval(secs => $4) then null
+                else verified_at
+            end
+        where site_key = $1 and domain = $2",
+        site_key.as_str(),
+        domain.to_string(),
+        verified,
+        grace_period_secs as f64,
+    )
+    .execute(exec)
+    .await?;
+    Ok(RowsAffected(res.rows_affected()))
+}
+
+/// Adds an allowed domain to an `api_key`, pending until its ownership is verified. Adding a domain that is
+/// already there returns it unchanged.
+pub async fn insert_allowed_domain(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+    domain: &AllowedDomain,
+) -> Result<DbApiKeyDomain> {
+    sqlx::query_as!(
+        DbApiKeyDomainInternal,
+        "insert into api_key_domain (site_key, domain) values ($1, $2)
+        on conflict on constraint api_key_domain_pkey do update set domain = excluded.domain
+        returning site_key, domain, token, created_at, checked_at, verified_at, exempt",
+        site_key.as_str(),
+        domain.to_string(),
+    )
+    .fetch_one(exec)
+    .await
+    .map(DbApiKeyDomain::try_from)?
+    .map_err(super::api_key_decode_err)
     .map_err(Error::from)
-    .map(|opt| opt.map(|result| result.unwrap_or(false)))
 }
 
+/// Removes an allowed domain from an `api_key`.
+pub async fn delete_allowed_domain(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+    domain: &AllowedDomain,
+) -> Result<RowsAffected> {
+    let res = sqlx::query!(
+        "delete from api_key_domain where site_key = $1 and domain = $2",
+        site_key.as_str(),
+        domain.to_string(),
+    )
+    .execute(exec)
+    .await?;
+    Ok(RowsAffected(res.rows_affected()))
+}
+
+/// Inserts a new console with an `api_key`, see [`insert_console`] and [`insert_api_key`].
+#[expect(clippy::too_many_arguments)]
 pub(crate) async fn with_console_insert_api_key(
-    exec: impl PgExecutor<'_> + Send + Clone,
+    pool: &PgPool,
+    master_key: &MasterKey,
     console_label: &str,
     user: &str,
     site_key: &Base64<UrlSafe>,
     enc_key: &Base64,
     secret: &Base64,
-    allowed_domains: &[Hostname],
+    allowed_domains: &[AllowedDomain],
 ) -> Result<Uuid> {
-    let allowed_domains = allowed_domains
-        .iter()
-        .map(|h| h.to_string())
-        .collect::<Vec<_>>();
-
-    let row = sqlx::query!(
-        r#"with
-      console as (insert into public.console (label, user_id) values ($1, $2) returning id)
-    insert into
-      public.api_key (site_key, console_id, encoding_key, secret, allowed_domains)
-    values
-      (
-        $3,
-        (select id from console),
-        $4, $5, $6
-      ) returning console_id"#,
-        console_label,
-        user,
-        site_key.as_str(),
-        enc_key.as_str(),
-        secret.as_str(),
-        &allowed_domains
+    let mut txn = pool.begin().await?;
+    let console_id = insert_console(&mut txn, console_label, user).await?;
+    insert_api_key(
+        &mut *txn,
+        master_key,
+        site_key,
+        &console_id,
+        enc_key,
+        secret,
+        allowed_domains,
     )
-    .fetch_one(exec.clone())
     .await?;
+    txn.commit().await?;
 
-    insert_challenge_customization(exec, &row.console_id, &DbChallengeCustomization::default())
-        .await?;
-
-    Ok(row.console_id)
+    Ok(console_id)
 }
 
 /// Holds fields to update an `api_key`.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct DbUpdateApiKey<'a> {
     /// Optionally update the label.
     pub label: Option<&'a str>,
-    /// Optionally update allowed domains list.
-    pub allowed_domains: Option<&'a [Hostname]>,
+    /// Optionally replace the allowed domains. The ones already allowed keep their status, while the new ones are
+    /// pending until their ownership is verified, or exempt like on insert, see [`insert_api_key`].
+    pub allowed_domains: Option<&'a [AllowedDomain]>,
+    /// Optionally update the proof of work difficulty.
+    pub pow_difficulty: Option<i16>,
+    /// Optionally update the proof of work algorithm.
+    pub pow_algorithm: Option<PowAlgorithm>,
+    /// Optionally update the minimum score to pass the verification.
+    pub min_score: Option<f32>,
+    /// Optionally update the lifetime of the response tokens.
+    pub response_ttl_secs: Option<i32>,
+    /// Optionally update the pre analysis policy.
+    pub pre_analysis_policy: Option<&'a PreAnalysisPolicy>,
+    /// Optionally update the clearance policy.
+    pub clearance_policy: Option<&'a ClearancePolicy>,
+    /// Optionally update the signing algorithm of the response tokens.
+    pub signing_algorithm: Option<SigningAlgorithm>,
+    /// Optionally update the keypair of an asymmetric signing algorithm.
+    pub signing_key: Option<&'a SigningKeyPair>,
+    /// Optionally keep the current verifying key until then, replacing any previous one, when the keypair of an
+    /// asymmetric signing algorithm is replaced.
+    pub previous_verifying_key_expires_at: Option<&'a OffsetDateTime>,
+    /// Optionally update the mode, to turn the api key into a test key or back.
+    pub mode: Option<ApiKeyMode>,
 }
 
-/// Updates an existing `api_keys`.
+/// Updates an existing `api_keys`, sealing the private key of a new signing keypair with the master key.
 pub async fn update_api_key(
     exec: impl PgExecutor<'_> + Send,
+    master_key: &MasterKey,
     site_key: &Base64<UrlSafe>,
     console_id: &Uuid,
     update: DbUpdateApiKey<'_>,
 ) -> Result<RowsAffected> {
-    let allowed_domains = update
-        .allowed_domains
-        .map(|domains| domains.iter().map(|h| h.to_string()).collect::<Vec<_>>());
+    let (allowed_domains, exempt_domains) = update.allowed_domains.map(domain_rows).unzip();
+
+    let policy = update.pre_analysis_policy;
+    let required_signals = policy.map(|policy| {
+        policy
+            .required_signals
+            .iter()
+            .map(|signal| signal.to_string())
+            .collect::<Vec<_>>()
+    });
+    let clearance = update.clearance_policy;
+    let signing_key = update.signing_key;
+    let sealed_signing_key = signing_key
+        .map(|key_pair| master_key.seal_signing_key(site_key, &key_pair.private_key_base64()))
+        .transpose()
+        .map_err(super::api_key_encode_err)?;
 
     let res = sqlx::query!(
-        "update api_key set label = coalesce($1, label), allowed_domains = coalesce($2, allowed_domains) where site_key = $3 and console_id = $4",
+        "with
+        updated_api_key as (select site_key from api_key where site_key = $17 and console_id = $18),
+        removed_domain as (
+            delete from api_key_domain
+            where site_key = (select site_key from updated_api_key) and not domain = any($2::text[])
+        ),
+        added_domain as (
+            insert into api_key_domain (site_key, domain, verified_at, exempt)
+            select site_key, domain, case when exempt then now() end, exempt
+            from updated_api_key, unnest($2::text[], $21::bool[]) as t(domain, exempt)
+            on conflict do nothing
+        )
+        update api_key set
+            label = coalesce($1, label),
+            pow_difficulty = coalesce($3, pow_difficulty),
+            pow_algorithm = coalesce($4, pow_algorithm),
+            pre_analysis_min_score = coalesce($5, pre_analysis_min_score),
+            pre_analysis_required_signals = coalesce($6, pre_analysis_required_signals),
+            pre_analysis_max_pass_rate = coalesce($7, pre_analysis_max_pass_rate),
+            clearance_lifetime_secs = coalesce($8, clearance_lifetime_secs),
+            clearance_ipv4_prefix = coalesce($9, clearance_ipv4_prefix),
+            clearance_ipv6_prefix = coalesce($10, clearance_ipv6_prefix),
+            clearance_bind_user_agent = coalesce($11, clearance_bind_user_agent),
+            min_score = coalesce($12, min_score),
+            response_ttl_secs = coalesce($13, response_ttl_secs),
+            signing_algorithm = coalesce($14, signing_algorithm),
+            signing_key = coalesce($15, signing_key),
+            verifying_key = coalesce($16, verifying_key),
+            previous_signing_algorithm =
+                case when $20::timestamptz is null then previous_signing_algorithm else signing_algorithm end,
+            previous_verifying_key =
+                case when $20::timestamptz is null then previous_verifying_key else verifying_key end,
+            previous_verifying_key_expires_at = coalesce($20, previous_verifying_key_expires_at),
+            mode = coalesce($19, mode)
+        where site_key = $17 and console_id = $18",
         update.label,
         allowed_domains.as_deref(),
+        update.pow_difficulty,
+        update.pow_algorithm.as_ref().map(PowAlgorithm::as_str),
+        policy.map(|policy| policy.min_score),
+        required_signals.as_deref(),
+        policy.map(|policy| policy.max_pass_rate),
+        clearance.map(|clearance| clearance.lifetime_secs as i32),
+        clearance.map(|clearance| i16::from(clearance.ipv4_prefix)),
+        clearance.map(|clearance| i16::from(clearance.ipv6_prefix)),
+        clearance.map(|clearance| clearance.bind_user_agent),
+        update.min_score,
+        update.response_ttl_secs,
+        update
+            .signing_algorithm
+            .as_ref()
+            .map(SigningAlgorithm::as_str),
+        sealed_signing_key,
+        signing_key.map(SigningKeyPair::public_key_base64),
         site_key.as_str(),
-        console_id
+        console_id,
+        update.mode.as_ref().map(ApiKeyMode::as_str),
+        update.previous_verifying_key_expires_at,
+        exempt_domains.as_deref(),
     )
     .execute(exec)
     .await?;
@@ -252,6 +734,38 @@ pub async fn update_api_key(
     Ok(RowsAffected(res.rows_affected()))
 }
 
+/// Rotates the encoding key of an existing `api_key`, returning the new version. The previous encoding key
+/// keeps decoding tokens until `previous_expires_at`, replacing any previous key of an earlier rotation.
+pub async fn rotate_encoding_key(
+    exec: impl PgExecutor<'_> + Send,
+    master_key: &MasterKey,
+    site_key: &Base64<UrlSafe>,
+    console_id: &Uuid,
+    enc_key: &Base64,
+    previous_expires_at: &OffsetDateTime,
+) -> Result<Option<i32>> {
+    let sealed_enc_key = master_key
+        .seal(site_key, enc_key)
+        .map_err(super::api_key_encode_err)?;
+
+    sqlx::query_scalar!(
+        "update api_key set
+            previous_encoding_key = encoding_key,
+            previous_encoding_key_expires_at = $1,
+            encoding_key = $2,
+            encoding_key_version = encoding_key_version + 1
+        where site_key = $3 and console_id = $4
+        returning encoding_key_version",
+        previous_expires_at,
+        sealed_enc_key,
+        site_key.as_str(),
+        console_id
+    )
+    .fetch_optional(exec)
+    .await
+    .map(Ok)?
+}
+
 /// Deletes an existing `api_key`.
 pub async fn delete_api_key(
     exec: impl PgExecutor<'_> + Send,
@@ -268,6 +782,145 @@ pub async fn delete_api_key(
     Ok(RowsAffected(res.rows_affected()))
 }
 
+/// Database representation of a secret of an api key. An api key can have many secrets, revoked independently.
+/// Only the hash of the secret is stored, along with a hint of its last characters.
+#[derive(Debug)]
+pub struct DbApiKeySecret {
+    pub id: Uuid,
+    pub label: Option<String>,
+    pub secret_hint: String,
+    pub created_at: OffsetDateTime,
+    /// Last time the secret verified a response.
+    pub last_used_at: Option<OffsetDateTime>,
+    /// Present once the secret is revoked, after which it can't verify responses.
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+/// Fetches all the `secret`s of an `api_key`, including the revoked ones.
+pub async fn fetch_api_key_secrets(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+) -> Result<Vec<DbApiKeySecret>> {
+    sqlx::query_as!(
+        DbApiKeySecret,
+        "select id, label, secret_hint, created_at, last_used_at, revoked_at
+        from api_key_secret where site_key = $1 order by created_at",
+        site_key.as_str()
+    )
+    .fetch_all(exec)
+    .await
+    .map(Ok)?
+}
+
+/// Inserts a new `secret` for an existing `api_key`, storing only its hash.
+pub async fn insert_api_key_secret(
+    exec: impl PgExecutor<'_> + Send,
+    master_key: &MasterKey,
+    site_key: &Base64<UrlSafe>,
+    label: Option<&str>,
+    secret: &Base64,
+) -> Result<DbApiKeySecret> {
+    sqlx::query_as!(
+        DbApiKeySecret,
+        "insert into api_key_secret (site_key, label, secret_hash, secret_hint) values ($1, $2, $3, $4)
+        returning id, label, secret_hint, created_at, last_used_at, revoked_at",
+        site_key.as_str(),
+        label,
+        master_key.hash_secret(secret),
+        crypto::secret_hint(secret)
+    )
+    .fetch_one(exec)
+    .await
+    .map(Ok)?
+}
+
+/// Revokes an active `secret` of an `api_key`.
+pub async fn revoke_api_key_secret(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+    secret_id: &Uuid,
+) -> Result<RowsAffected> {
+    let res = sqlx::query!(
+        "update api_key_secret set revoked_at = now()
+        where id = $1 and site_key = $2 and revoked_at is null",
+        secret_id,
+        site_key.as_str()
+    )
+    .execute(exec)
+    .await?;
+    Ok(RowsAffected(res.rows_affected()))
+}
+
+/// Hashes the plaintext secrets and seals the plaintext encoding and signing keys of the `api_key`s, left by the
+/// versions before the credentials were protected at rest. The master key isn't available to the migrations, so this runs
+/// on startup. Returns the number of credentials protected.
+pub async fn protect_plaintext_credentials(pool: &PgPool, master_key: &MasterKey) -> Result<u64> {
+    let mut txn = pool.begin().await?;
+    let mut protected = 0;
+
+    let secrets = sqlx::query!(
+        r#"select id, secret as "secret!" from api_key_secret where secret is not null for update"#
+    )
+    .fetch_all(&mut *txn)
+    .await?;
+    for row in secrets {
+        let secret: Base64 = row.secret.try_into().map_err(super::api_key_decode_err)?;
+        sqlx::query!(
+            "update api_key_secret set secret = null, secret_hash = $1 where id = $2",
+            master_key.hash_secret(&secret),
+            row.id
+        )
+        .execute(&mut *txn)
+        .await?;
+        protected += 1;
+    }
+
+    let sealed_pattern = format!("{SEALED_PREFIX}%");
+    let api_keys = sqlx::query!(
+        "select site_key, encoding_key, previous_encoding_key, signing_key from api_key
+        where encoding_key not like $1 or previous_encoding_key not like $1 or signing_key not like $1
+        for update",
+        sealed_pattern
+    )
+    .fetch_all(&mut *txn)
+    .await?;
+    for row in api_keys {
+        let site_key: Base64<UrlSafe> =
+            row.site_key.try_into().map_err(super::api_key_decode_err)?;
+        let seal = |key: String| match key.starts_with(SEALED_PREFIX) {
+            true => Ok(key),
+            false => master_key
+                .seal(
+                    &site_key,
+                    &key.try_into().map_err(super::api_key_decode_err)?,
+                )
+                .map_err(super::api_key_encode_err)
+                .map_err(Error::from),
+        };
+        let seal_signing_key = |key: String| match key.starts_with(SEALED_PREFIX) {
+            true => Ok(key),
+            false => master_key
+                .seal_signing_key(&site_key, &Secret::new(key))
+                .map_err(super::api_key_encode_err)
+                .map_err(Error::from),
+        };
+        sqlx::query!(
+            "update api_key set encoding_key = $1, previous_encoding_key = $2, signing_key = $3
+            where site_key = $4",
+            seal(row.encoding_key)?,
+            row.previous_encoding_key.map(seal).transpose()?,
+            row.signing_key.map(seal_signing_key).transpose()?,
+            site_key.as_str()
+        )
+        .execute(&mut *txn)
+        .await?;
+        protected += 1;
+    }
+
+    txn.commit().await?;
+    Ok(protected)
+}
+
 /// Database `console` representation.
 #[derive(Debug)]
 pub struct DbConsole {
@@ -395,6 +1048,7 @@ pub struct DbChallenge {
     pub small_width: i16,
     pub small_height: i16,
     pub logo_url: Option<String>,
+    pub puzzle_kind: String,
 }
 
 impl DbChallenge {
@@ -407,6 +1061,7 @@ impl DbChallenge {
             small_width: 360,
             small_height: 500,
             logo_url: None,
+            puzzle_kind: PuzzleKind::default().to_string(),
         }
     }
 }
@@ -422,7 +1077,8 @@ pub async fn fetch_challenges(exec: impl PgExecutor<'_> + Send) -> Result<Vec<Db
             default_height as height,
             default_width as small_width,
             default_height as small_height,
-            default_logo_url as logo_url
+            default_logo_url as logo_url,
+            puzzle_kind
         from challenge"
     )
     .fetch_all(exec)
@@ -445,7 +1101,8 @@ pub async fn fetch_challenges_with_customization(
             coalesce(cc.height, c.default_height) as "height!",
             coalesce(cc.small_width, c.default_width) as "small_width!",
             coalesce(cc.small_height, c.default_height) as "small_height!",
-            coalesce(cc.logo_url, c.default_logo_url) as logo_url
+            coalesce(cc.logo_url, c.default_logo_url) as logo_url,
+            c.puzzle_kind
         from public.challenge c
         left join public.challenge_customization cc on cc.console_id = (
             select console_id
@@ -469,11 +1126,12 @@ pub async fn insert_challenge(
     challenge: &DbChallenge,
 ) -> Result<()> {
     sqlx::query!(
-        "insert into challenge (url, default_width, default_height, default_logo_url) values ($1, $2, $3, $4) on conflict (url) do nothing",
+        "insert into challenge (url, default_width, default_height, default_logo_url, puzzle_kind) values ($1, $2, $3, $4, $5) on conflict (url) do nothing",
         challenge.url,
         challenge.width,
         challenge.height,
-        challenge.logo_url
+        challenge.logo_url,
+        challenge.puzzle_kind
     )
     .execute(exec)
     .await?;
@@ -653,3 +1311,104 @@ pub async fn update_challenge_customization(
 
     Ok(RowsAffected(res.rows_affected()))
 }
+
+/// Marks a response token as consumed until it expires. Returns `false` if the token was already consumed, unless
+/// it was consumed with the same `idempotency_key`, so a verification can be retried.
+/// Consumed tokens that are past their expiration are purged on the way.
+pub async fn consume_response_token(
+    exec: impl PgExecutor<'_> + Send,
+    jti: &Uuid,
+    expires_at: &OffsetDateTime,
+    idempotency_key: Option<&Uuid>,
+) -> Result<bool> {
+    let res = sqlx::query!(
+        "with purged as (delete from consumed_response_token where expires_at < now())
+        insert into consumed_response_token (jti, expires_at, idempotency_key) values ($1, $2, $3)
+        on conflict (jti) do update set jti = excluded.jti
+        where consumed_response_token.idempotency_key = excluded.idempotency_key",
+        jti,
+        expires_at,
+        idempotency_key
+    )
+    .execute(exec)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Marks a proof of work challenge issued for an `api_key` as consumed until it expires. Returns `false` if the
+/// challenge was already consumed. Consumed challenges that are past their expiration are purged on the way.
+pub async fn consume_pow_challenge(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+    challenge: &PowChallenge,
+    expires_at: &OffsetDateTime,
+) -> Result<bool> {
+    let res = sqlx::query!(
+        "with purged as (delete from consumed_pow_challenge where expires_at < now())
+        insert into consumed_pow_challenge (site_key, nonce, timestamp, expires_at) values ($1, $2, $3, $4)
+        on conflict (site_key, nonce, timestamp) do nothing",
+        site_key.as_str(),
+        challenge.nonce as i64,
+        challenge.timestamp,
+        expires_at
+    )
+    .execute(exec)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Marks a puzzle issued for an `api_key` at `issued_at` as consumed until it expires. Returns `false` if the
+/// puzzle was already consumed. Consumed puzzles that are past their expiration are purged on the way.
+pub async fn consume_puzzle(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+    puzzle: &Puzzle,
+    issued_at: &OffsetDateTime,
+    expires_at: &OffsetDateTime,
+) -> Result<bool> {
+    let res = sqlx::query!(
+        "with purged as (delete from consumed_puzzle where expires_at < now())
+        insert into consumed_puzzle (site_key, seed, issued_at, expires_at) values ($1, $2, $3, $4)
+        on conflict (site_key, seed, issued_at) do nothing",
+        site_key.as_str(),
+        i64::from(puzzle.seed),
+        issued_at,
+        expires_at
+    )
+    .execute(exec)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Records a pre analysis of the api key in the current hourly window. The pre analysis only passes if it was
+/// `accepted` by the policy and the passes in the window stay within `max_pass_rate` of the accepted attempts,
+/// rounded up so the first accepted attempt of a window can pass. Rejected attempts don't grow the budget.
+/// Windows older than a day are purged.
+pub async fn record_pre_analysis(
+    exec: impl PgExecutor<'_> + Send,
+    site_key: &Base64<UrlSafe>,
+    accepted: bool,
+    max_pass_rate: f32,
+) -> Result<bool> {
+    let passed = sqlx::query_scalar!(
+        "with purged as (delete from pre_analysis_window where window_start < now() - interval '1 day')
+        insert into pre_analysis_window as w (site_key, window_start, attempts, accepted, passes, last_passed)
+        values ($1, date_trunc('hour', now()), 1, $2::bool::int, ($2::bool and $3::real > 0)::int, $2::bool and $3::real > 0)
+        on conflict (site_key, window_start) do update set
+            attempts = w.attempts + 1,
+            accepted = w.accepted + $2::bool::int,
+            passes = w.passes + ($2::bool and w.passes < $3::real * (w.accepted + 1))::int,
+            last_passed = $2::bool and w.passes < $3::real * (w.accepted + 1)
+        returning last_passed",
+        site_key.as_str(),
+        accepted,
+        max_pass_rate
+    )
+    .fetch_one(exec)
+    .await?;
+
+    Ok(passed)
+}
@@ -1,2 +1,6 @@
+pub mod action;
+pub mod allowed_domain;
+pub mod api_key_mode;
+pub mod form_digest;
 pub mod hostname;
 pub mod serde;
@@ -0,0 +1,75 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+/// Name of the action the user performed when solving the challenge, such as `login` or `newsletter/subscribe`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Action(String);
+
+impl Action {
+    /// Maximum length of an action.
+    pub const MAX_LEN: usize = 100;
+
+    /// Checks if the action only contains alphanumeric characters, `/`, `_`, `-` or `.` and creates
+    /// a new one from a `&str`.
+    pub fn parse(action_str: &str) -> Result<Self, ActionError> {
+        if action_str.is_empty() || action_str.len() > Self::MAX_LEN {
+            return Err(ActionError::Length);
+        }
+        if let Some(c) = action_str
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '/' | '_' | '-' | '.'))
+        {
+            return Err(ActionError::InvalidCharacter(c));
+        }
+        Ok(Action(action_str.to_owned()))
+    }
+
+    /// Exposes the action as a `&str`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Errors parsing an action.
+#[derive(Debug, Error, PartialEq)]
+pub enum ActionError {
+    #[error("action must have between 1 and {} characters", Action::MAX_LEN)]
+    Length,
+    #[error("action contains invalid character `{0}`")]
+    InvalidCharacter(char),
+}
+
+impl Serialize for Action {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let str = <&str>::deserialize(deserializer)?;
+        Action::parse(str).map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for Action {
+    type Err = ActionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::parse(s)
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,231 @@
+use std::{borrow::Cow, fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use url::ParseError;
+
+use super::hostname::{Hostname, HostnamePolicyError};
+
+/// Prefix of a wildcard allowed domain.
+const WILDCARD_PREFIX: &str = "*.";
+
+/// Domain allowed to embed the challenge of an api key, either an exact hostname such as `example.com` or a
+/// wildcard such as `*.example.com`.
+///
+/// A wildcard matches every subdomain of its base domain at any depth, `a.example.com` and `a.b.example.com`,
+/// but neither the base domain itself nor domains which only end with the same characters, such as
+/// `badexample.com`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AllowedDomain {
+    Exact(Hostname),
+    Wildcard(Hostname),
+}
+
+impl AllowedDomain {
+    /// Checks if the allowed domain is a valid hostname or a wildcard over a domain that isn't a public suffix,
+    /// and creates a new one from a `&str`.
+    pub fn parse(domain_str: &str) -> Result<Self, AllowedDomainError> {
+        let Some(base) = domain_str.strip_prefix(WILDCARD_PREFIX) else {
+            if domain_str.contains('*') {
+                return Err(AllowedDomainError::Wildcard);
+            }
+            return Ok(AllowedDomain::Exact(Hostname::parse(domain_str)?));
+        };
+
+        if base.contains('*') {
+            return Err(AllowedDomainError::Wildcard);
+        }
+        let base = Hostname::parse(base)?;
+        match base.validate_policy() {
+            Ok(()) => Ok(AllowedDomain::Wildcard(base)),
+            Err(HostnamePolicyError::PublicSuffix) => {
+                Err(AllowedDomainError::PublicSuffix(base.to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Checks the policy of the hostname of an exact domain, see [`Hostname::validate_policy`]. The base of a
+    /// wildcard is already checked when parsing it.
+    ///
+    /// Not part of the parsing, so domains allowed before the policy, or for local development, like `localhost`,
+    /// keep working. The policy is enforced when the domains are changed.
+    pub fn validate_policy(&self) -> Result<(), AllowedDomainError> {
+        match self {
+            AllowedDomain::Exact(hostname) => Ok(hostname.validate_policy()?),
+            AllowedDomain::Wildcard(_) => Ok(()),
+        }
+    }
+
+    /// Hostname whose owner proves the ownership of the domain, the base of a wildcard or the exact hostname.
+    pub fn ownership_host(&self) -> &Hostname {
+        match self {
+            AllowedDomain::Exact(hostname) | AllowedDomain::Wildcard(hostname) => hostname,
+        }
+    }
+
+    /// Checks if a hostname is allowed by this domain.
+    pub fn matches(&self, hostname: &Hostname) -> bool {
+        match self {
+            AllowedDomain::Exact(domain) => domain == hostname,
+            AllowedDomain::Wildcard(base) => hostname
+                .as_str()
+                .strip_suffix(base.as_str())
+                .and_then(|subdomain| subdomain.strip_suffix('.'))
+                .is_some_and(|subdomain| !subdomain.is_empty()),
+        }
+    }
+}
+
+/// Checks if a hostname is allowed by any of the allowed domains.
+pub fn is_allowed(allowed_domains: &[AllowedDomain], hostname: &Hostname) -> bool {
+    allowed_domains
+        .iter()
+        .any(|domain| domain.matches(hostname))
+}
+
+/// Errors parsing an allowed domain.
+#[derive(Debug, Error, PartialEq)]
+pub enum AllowedDomainError {
+    #[error(transparent)]
+    Hostname(#[from] ParseError),
+    #[error("wildcard is only allowed as the leftmost label of a domain, like `*.example.com`")]
+    Wildcard,
+    #[error("wildcard over the public suffix `{0}` is not allowed")]
+    PublicSuffix(String),
+    #[error(transparent)]
+    Policy(#[from] HostnamePolicyError),
+}
+
+impl Serialize for AllowedDomain {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AllowedDomain {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let str = Cow::<'_, str>::deserialize(deserializer)?;
+        AllowedDomain::parse(&str).map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for AllowedDomain {
+    type Err = AllowedDomainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AllowedDomain::parse(s)
+    }
+}
+
+impl Display for AllowedDomain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllowedDomain::Exact(hostname) => f.write_str(hostname.as_str()),
+            AllowedDomain::Wildcard(base) => write!(f, "{WILDCARD_PREFIX}{base}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(domain: &str, hostname: &str) -> bool {
+        AllowedDomain::parse(domain)
+            .unwrap()
+            .matches(&Hostname::parse(hostname).unwrap())
+    }
+
+    #[test]
+    fn exact_domain() {
+        assert!(allowed("example.com", "example.com"));
+        assert!(allowed("example.com", "EXAMPLE.com"));
+        assert!(!allowed("example.com", "www.example.com"));
+        assert!(!allowed("www.example.com", "example.com"));
+    }
+
+    #[test]
+    fn wildcard_domain() {
+        assert!(allowed("*.example.com", "www.example.com"));
+        assert!(allowed("*.example.com", "pr-42.preview.example.com"));
+        assert!(allowed(
+            "*.preview.example.com",
+            "pr-42.preview.example.com"
+        ));
+        assert!(allowed("*.Example.com", "www.example.com"));
+        assert!(!allowed("*.example.com", "example.com"));
+        assert!(!allowed("*.example.com", "badexample.com"));
+        assert!(!allowed("*.example.com", "www.badexample.com"));
+        assert!(!allowed("*.example.com", "example.com.evil.com"));
+        assert!(!allowed("*.preview.example.com", "www.example.com"));
+    }
+
+    #[test]
+    fn wildcard_over_public_suffix() {
+        for domain in ["*.com", "*.co.uk", "*.github.io", "*.localhost"] {
+            assert!(
+                matches!(
+                    AllowedDomain::parse(domain),
+                    Err(AllowedDomainError::PublicSuffix(_))
+                ),
+                "{domain}"
+            );
+        }
+        assert!(AllowedDomain::parse("*.example.co.uk").is_ok());
+        assert!(AllowedDomain::parse("*.gotcha.github.io").is_ok());
+    }
+
+    #[test]
+    fn invalid_wildcard() {
+        for domain in [
+            "*",
+            "*.",
+            "www.*.example.com",
+            "*example.com",
+            "*.*.example.com",
+            "*.127.0.0.1",
+        ] {
+            assert!(AllowedDomain::parse(domain).is_err(), "{domain}");
+        }
+    }
+
+    #[test]
+    fn policy() {
+        for domain in ["example.com", "*.example.com", "bücher.example.com"] {
+            assert_eq!(
+                AllowedDomain::parse(domain).unwrap().validate_policy(),
+                Ok(()),
+                "{domain}"
+            );
+        }
+        for domain in ["localhost", "com", "127.0.0.1", "[::1]"] {
+            assert!(
+                AllowedDomain::parse(domain)
+                    .unwrap()
+                    .validate_policy()
+                    .is_err(),
+                "{domain}"
+            );
+        }
+        assert_eq!(
+            AllowedDomain::parse("*.Bücher.example")
+                .unwrap()
+                .to_string(),
+            "*.xn--bcher-kva.example"
+        );
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for domain in ["example.com", "*.example.com", "127.0.0.1"] {
+            assert_eq!(AllowedDomain::parse(domain).unwrap().to_string(), domain);
+        }
+    }
+}
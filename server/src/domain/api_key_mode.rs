@@ -0,0 +1,51 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Mode of an api key. Test keys, like Google's test keys, accept any hostname, never show a real puzzle and
+/// always pass or always fail the verification, so integrations can be tested deterministically.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiKeyMode {
+    /// Challenges and verifications are real.
+    #[default]
+    Live,
+    /// Every verification passes.
+    TestPass,
+    /// Every verification fails.
+    TestFail,
+}
+
+impl ApiKeyMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyMode::Live => "live",
+            ApiKeyMode::TestPass => "test-pass",
+            ApiKeyMode::TestFail => "test-fail",
+        }
+    }
+
+    /// Checks if the api key is a test key.
+    pub fn is_test(&self) -> bool {
+        !matches!(self, ApiKeyMode::Live)
+    }
+}
+
+impl FromStr for ApiKeyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "live" => Ok(ApiKeyMode::Live),
+            "test-pass" => Ok(ApiKeyMode::TestPass),
+            "test-fail" => Ok(ApiKeyMode::TestFail),
+            other => Err(format!("{other} is not a supported api key mode")),
+        }
+    }
+}
+
+impl Display for ApiKeyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,136 @@
+use std::{fmt::Display, str::FromStr};
+
+use base64::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use url::form_urlencoded;
+
+/// Digest of the protected fields of a form, so a response token is only valid for the data the user submitted.
+///
+/// The fields are sorted by name and value and serialized as `application/x-www-form-urlencoded`, then hashed
+/// with SHA-256 and encoded in URL-safe base64 without padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormDigest(String);
+
+impl FormDigest {
+    /// Computes the digest of the given form fields.
+    pub fn of<K, V>(fields: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut fields: Vec<_> = fields.into_iter().collect();
+        fields.sort_by(|(k1, v1), (k2, v2)| {
+            (k1.as_ref(), v1.as_ref()).cmp(&(k2.as_ref(), v2.as_ref()))
+        });
+        let canonical = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(fields)
+            .finish();
+
+        FormDigest(BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes())))
+    }
+
+    /// Computes the digest of the form fields encoded as `application/x-www-form-urlencoded`.
+    pub fn of_urlencoded(form_data: &str) -> Self {
+        Self::of(form_urlencoded::parse(form_data.as_bytes()))
+    }
+
+    /// Checks if it's a valid digest and creates a new one from a `&str`.
+    pub fn parse(digest_str: &str) -> Result<Self, FormDigestError> {
+        let mut digest = [0; 32];
+        match BASE64_URL_SAFE_NO_PAD.decode_slice(digest_str, &mut digest) {
+            Ok(32) => Ok(FormDigest(digest_str.to_owned())),
+            _ => Err(FormDigestError),
+        }
+    }
+
+    /// Exposes the digest as a `&str`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Error parsing a form digest.
+#[derive(Debug, Error, PartialEq)]
+#[error("form digest must be a SHA-256 hash encoded in URL-safe base64 without padding")]
+pub struct FormDigestError;
+
+impl Serialize for FormDigest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FormDigest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let str = <&str>::deserialize(deserializer)?;
+        FormDigest::parse(str).map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for FormDigest {
+    type Err = FormDigestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FormDigest::parse(s)
+    }
+}
+
+impl Display for FormDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_is_independent_of_field_order() {
+        let digest = FormDigest::of([("email", "user@example.com"), ("amount", "10")]);
+
+        assert_eq!(
+            digest,
+            FormDigest::of([("amount", "10"), ("email", "user@example.com")])
+        );
+        assert_eq!(
+            digest,
+            FormDigest::of_urlencoded("amount=10&email=user%40example.com")
+        );
+        assert_ne!(
+            digest,
+            FormDigest::of([("amount", "100"), ("email", "user@example.com")])
+        );
+        assert_eq!(
+            digest.as_str(),
+            "Y22obralVFus5MP3rmHVkmKsD_IpJfoDPW53Dzd2tfE"
+        );
+        assert_eq!(FormDigest::parse(digest.as_str()), Ok(digest));
+    }
+
+    #[test]
+    fn digest_encodes_fields() {
+        assert_ne!(
+            FormDigest::of([("a", "b&c=d")]),
+            FormDigest::of([("a", "b"), ("c", "d")])
+        );
+    }
+
+    #[test]
+    fn invalid_digest() {
+        assert_eq!(FormDigest::parse(""), Err(FormDigestError));
+        assert_eq!(FormDigest::parse("not a digest"), Err(FormDigestError));
+        assert_eq!(
+            FormDigest::parse(&BASE64_URL_SAFE_NO_PAD.encode([0; 16])),
+            Err(FormDigestError)
+        );
+    }
+}
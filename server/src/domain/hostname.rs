@@ -1,6 +1,7 @@
 use std::{fmt::Display, str::FromStr};
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
 use url::{Host, ParseError};
 
 /// Wraps a `String` for the representation of a hostname and serializes and deserializes to a `String` more efficiently.
@@ -8,12 +9,24 @@ use url::{Host, ParseError};
 pub struct Hostname(String);
 
 impl Hostname {
-    /// Checks if the hostname is valid and creates a new one from a `&str`.
+    /// Checks if the hostname is valid and creates a new one from a `&str`. The hostname is normalized, so
+    /// internationalized names are converted to punycode, letters are lowercased and a trailing dot is removed.
     pub fn parse(host_str: &str) -> Result<Self, ParseError> {
+        let host_str = host_str.strip_suffix('.').unwrap_or(host_str);
         let host = Host::parse(host_str)?;
         Ok(unsafe { Hostname::new_unchecked(host.to_string()) })
     }
 
+    /// Checks if the hostname can be allowed to embed the challenge of an api key, so it's neither an ip address
+    /// nor a bare public suffix such as `com` or `github.io`, which would allow sites of anyone.
+    pub fn validate_policy(&self) -> Result<(), HostnamePolicyError> {
+        match Host::parse(self.as_str()) {
+            Ok(Host::Ipv4(_) | Host::Ipv6(_)) => Err(HostnamePolicyError::IpAddress),
+            _ if psl::domain_str(self.as_str()).is_none() => Err(HostnamePolicyError::PublicSuffix),
+            _ => Ok(()),
+        }
+    }
+
     /// Creates a new hostname without checking if it's valid.
     pub unsafe fn new_unchecked(host_str: String) -> Self {
         Hostname(host_str)
@@ -25,6 +38,15 @@ impl Hostname {
     }
 }
 
+/// Errors of a hostname that can't be allowed to embed the challenge.
+#[derive(Debug, Error, PartialEq)]
+pub enum HostnamePolicyError {
+    #[error("ip addresses are not allowed, only domains")]
+    IpAddress,
+    #[error("a public suffix is not allowed, only a domain registered under it")]
+    PublicSuffix,
+}
+
 impl Serialize for Hostname {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -57,3 +79,54 @@ impl Display for Hostname {
         f.write_str(self.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        for (host, normalized) in [
+            ("Example.COM", "example.com"),
+            ("example.com.", "example.com"),
+            ("bücher.example", "xn--bcher-kva.example"),
+            ("XN--BCHER-KVA.example", "xn--bcher-kva.example"),
+            ("例え.テスト", "xn--r8jz45g.xn--zckzah"),
+        ] {
+            assert_eq!(Hostname::parse(host).unwrap().as_str(), normalized);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for host in [
+            "",
+            ".",
+            "exa mple.com",
+            "example.com/path",
+            "user@example.com",
+        ] {
+            assert!(Hostname::parse(host).is_err(), "{host}");
+        }
+    }
+
+    #[test]
+    fn policy() {
+        for (host, policy) in [
+            ("example.com", Ok(())),
+            ("www.example.co.uk", Ok(())),
+            ("bücher.example.com", Ok(())),
+            ("127.0.0.1", Err(HostnamePolicyError::IpAddress)),
+            ("[::1]", Err(HostnamePolicyError::IpAddress)),
+            ("com", Err(HostnamePolicyError::PublicSuffix)),
+            ("co.uk", Err(HostnamePolicyError::PublicSuffix)),
+            ("github.io", Err(HostnamePolicyError::PublicSuffix)),
+        ] {
+            assert_eq!(
+                Hostname::parse(host).unwrap().validate_policy(),
+                policy,
+                "{host}"
+            );
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use std::sync::{Arc, LazyLock};
 
 use axum::Router;
-use configuration::ApplicationConfig;
+use configuration::{AnalysisConfig, ApplicationConfig, DomainVerificationConfig, EmulatorConfig};
+use crypto::MasterKey;
 use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache, HttpCacheOptions};
+use ownership::OwnershipResolver;
 use reqwest::Client;
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use routes::emulator::EmulatorState;
 use sqlx::PgPool;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -19,9 +22,11 @@ use aws_lambda::*;
 
 pub mod analysis;
 pub mod configuration;
+pub mod crypto;
 pub mod db;
 mod domain;
 pub mod encodings;
+pub mod ownership;
 pub mod routes;
 pub mod test_helpers;
 pub mod tokens;
@@ -56,17 +61,36 @@ pub static HTTP_CACHE_CLIENT: LazyLock<ClientWithMiddleware> = LazyLock::new(||
 pub struct AppState {
     pub pool: PgPool,
     pub auth_origin: String,
+    pub analysis: AnalysisConfig,
+    pub master_key: MasterKey,
+    pub domain_verification: DomainVerificationConfig,
+    pub ownership_resolver: Arc<dyn OwnershipResolver>,
 }
 
 /// Builds the application router.
-pub fn app(config: ApplicationConfig, pool: PgPool) -> Router {
-    let state = AppState { pool, auth_origin: config.auth_origin };
+pub fn app(
+    config: ApplicationConfig,
+    pool: PgPool,
+    ownership_resolver: Arc<dyn OwnershipResolver>,
+) -> Router {
+    let state = Arc::new(AppState {
+        pool,
+        auth_origin: config.auth_origin,
+        analysis: config.analysis,
+        master_key: config.master_key,
+        domain_verification: config.domain_verification,
+        ownership_resolver,
+    });
 
-    let router = Router::new().nest("/api", api(state));
+    let router = Router::new()
+        .nest("/api", api(&state))
+        .nest("/recaptcha", routes::recaptcha(&state))
+        .nest("/hcaptcha", routes::hcaptcha(&state))
+        .nest("/turnstile", routes::turnstile(&state));
     #[cfg(not(feature = "aws-lambda"))]
     let router = {
         use configuration::server_dir;
-        use tower_http::services::ServeDir;
+        use tower_http::services::{ServeDir, ServeFile};
 
         let serve_dir = server_dir()
             .join(config.serve_dir)
@@ -74,7 +98,12 @@ pub fn app(config: ApplicationConfig, pool: PgPool) -> Router {
             .expect("serve dir not found");
         tracing::info!("Serving files from: {:?}", serve_dir);
 
-        router.fallback_service(ServeDir::new(serve_dir))
+        let script = ServeFile::new(serve_dir.join("api.js"));
+        router
+            .route_service("/recaptcha/api.js", script.clone())
+            .route_service("/hcaptcha/1/api.js", script.clone())
+            .route_service("/turnstile/v0/api.js", script)
+            .fallback_service(ServeDir::new(serve_dir))
     };
     let router = router.layer(TraceLayer::new_for_http());
     #[cfg(feature = "aws-lambda")]
@@ -83,13 +112,36 @@ pub fn app(config: ApplicationConfig, pool: PgPool) -> Router {
     router
 }
 
-fn api(state: AppState) -> Router {
-    let state = Arc::new(state);
+/// Builds the router of the emulator, which serves the widget script and the public api with the keys in memory.
+pub fn emulator(config: EmulatorConfig) -> Router {
+    use tower_http::services::ServeFile;
+
+    for key in &config.keys {
+        tracing::info!(site_key = %key.site_key, mode = %key.mode, "Emulating api key");
+    }
+    let state = Arc::new(EmulatorState::new(config.keys));
+    let script = configuration::server_dir()
+        .join(config.serve_dir)
+        .join("api.js");
+    if !script.is_file() {
+        tracing::warn!("widget script not found at {script:?}, /api.js won't be served");
+    }
+
+    Router::new()
+        .nest(
+            "/api",
+            routes::emulator::router(&state).layer(CorsLayer::permissive()),
+        )
+        .route_service("/api.js", ServeFile::new(script))
+        .layer(TraceLayer::new_for_http())
+}
+
+fn api(state: &Arc<AppState>) -> Router {
     Router::new()
-        .merge(routes::verification(&state))
-        .nest("/challenge", routes::challenge(&state))
-        .nest("/console", routes::console(&state))
-        .nest("/admin", routes::admin(&state))
+        .merge(routes::verification(state))
+        .nest("/challenge", routes::challenge(state))
+        .nest("/console", routes::console(state))
+        .nest("/admin", routes::admin(state))
         .layer(CorsLayer::permissive())
 }
 
@@ -105,9 +157,10 @@ pub fn init_tracing() {
 }
 
 /// Populates database with development data.
-pub async fn db_dev_populate(pool: &PgPool) -> db::Result<()> {
+pub async fn db_dev_populate(pool: &PgPool, master_key: &MasterKey) -> db::Result<()> {
     let _console_id = db::with_console_insert_api_key(
         pool,
+        master_key,
         "demo",
         "demo|user",
         &String::from("4BdwFU84HLqceCQbE90-U5mw7f0erayega3nFOYvp1T5qXd8IqnTHJfsh675Vb2q")
@@ -1,4 +1,7 @@
-use gotcha_server::{configuration::Config, db};
+use std::sync::Arc;
+
+use anyhow::Context;
+use gotcha_server::{configuration::Config, crypto::MasterKey, db, ownership::NetworkResolver};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -21,7 +24,16 @@ async fn hosted_main() -> anyhow::Result<()> {
     tracing::info!(config = ?db_conf, "Database config");
 
     let pool = db::connect_database(db_conf);
-    _ = gotcha_server::db_dev_populate(&pool).await;
+    protect_plaintext_credentials(&pool, &app_conf.master_key).await?;
+    _ = gotcha_server::db_dev_populate(&pool, &app_conf.master_key).await;
+
+    let ownership_resolver: Arc<dyn gotcha_server::ownership::OwnershipResolver> =
+        Arc::new(NetworkResolver::new()?);
+    tokio::spawn(gotcha_server::ownership::run_verifier(
+        pool.clone(),
+        Arc::clone(&ownership_resolver),
+        app_conf.domain_verification.clone(),
+    ));
 
     let addr = format!("{}:{}", app_conf.host, app_conf.port);
     let listener = tokio::net::TcpListener::bind(addr).await?;
@@ -29,7 +41,7 @@ async fn hosted_main() -> anyhow::Result<()> {
     tracing::info!("Listening on {}", listener.local_addr()?);
     axum::serve(
         listener,
-        gotcha_server::app(app_conf, pool)
+        gotcha_server::app(app_conf, pool, ownership_resolver)
             .into_make_service_with_connect_info::<std::net::SocketAddr>(),
     )
     .await
@@ -49,9 +61,27 @@ async fn lambda_main() -> anyhow::Result<()> {
     tracing::info!(?db_conf, "Database config");
 
     let pool = db::connect_database(db_conf);
+    protect_plaintext_credentials(&pool, &app_conf.master_key).await?;
+    // there's no background verifier on lambda, the domains are checked on demand from the console
+    let ownership_resolver = Arc::new(NetworkResolver::new()?);
 
-    lambda_http::run(gotcha_server::app(app_conf, pool))
+    lambda_http::run(gotcha_server::app(app_conf, pool, ownership_resolver))
         .await
         .unwrap();
     Ok(())
 }
+
+/// Protects the credentials stored in plaintext before the master key was introduced. Serving while credentials
+/// are left in plaintext isn't an option, so a failure aborts the startup.
+async fn protect_plaintext_credentials(
+    pool: &sqlx::PgPool,
+    master_key: &MasterKey,
+) -> anyhow::Result<()> {
+    let protected = db::protect_plaintext_credentials(pool, master_key)
+        .await
+        .context("could not protect plaintext credentials")?;
+    if protected > 0 {
+        tracing::info!(protected, "protected plaintext credentials");
+    }
+    Ok(())
+}
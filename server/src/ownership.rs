@@ -0,0 +1,328 @@
+//! Verification of the ownership of the allowed domains of the api keys. The owner of a domain proves it by
+//! serving the token of the domain at `https://{domain}/.well-known/gotcha-verification.txt` or in a TXT record
+//! at `_gotcha-verification.{domain}`. The ownership of a wildcard like `*.example.com` is proven on `example.com`.
+
+use std::{
+    fmt::Debug,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use futures::{StreamExt, stream};
+use hickory_resolver::{TokioResolver, proto::rr::RData};
+use reqwest::{
+    Client,
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use sqlx::PgPool;
+use url::Host;
+use uuid::Uuid;
+
+use crate::{
+    configuration::DomainVerificationConfig,
+    db::{self, DbApiKeyDomain},
+    domain::allowed_domain::AllowedDomain,
+    encodings::{Base64, UrlSafe},
+};
+
+/// Path of the file that proves the ownership of a domain over HTTPS.
+pub const WELL_KNOWN_PATH: &str = "/.well-known/gotcha-verification.txt";
+
+/// Label prepended to a domain for the TXT record that proves its ownership.
+pub const TXT_RECORD_LABEL: &str = "_gotcha-verification";
+
+/// Maximum size in bytes of the well known file.
+const MAX_WELL_KNOWN_SIZE: usize = 4096;
+
+/// Maximum number of domains checked on each run of the verifier.
+const CHECK_BATCH_SIZE: i64 = 100;
+
+/// Maximum number of domains checked at the same time.
+const CHECK_CONCURRENCY: usize = 16;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Looks up the proofs of ownership of a domain. Tests replace it with a local stand-in.
+pub trait OwnershipResolver: Debug + Send + Sync {
+    /// Fetches the well known file of a host.
+    fn fetch_well_known<'a>(&'a self, host: &'a str) -> BoxFuture<'a, anyhow::Result<String>>;
+
+    /// Looks up the TXT records of a name.
+    fn lookup_txt<'a>(&'a self, name: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<String>>>;
+}
+
+/// Resolves the proofs over the network, the well known file over HTTPS and the TXT records with the DNS
+/// resolver of the system.
+#[derive(Debug)]
+pub struct NetworkResolver {
+    client: Client,
+    dns: TokioResolver,
+}
+
+impl NetworkResolver {
+    pub fn new() -> anyhow::Result<Self> {
+        let dns = TokioResolver::builder_tokio()
+            .context("could not read the DNS configuration of the system")?
+            .build()
+            .context("could not build the DNS resolver")?;
+        let client = Client::builder()
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            // a redirect could lead to a file served by someone else
+            .redirect(redirect::Policy::none())
+            .timeout(Duration::from_secs(10))
+            // a proxy would resolve the names itself
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicAddressResolver(dns.clone())))
+            .build()
+            .context("could not build the ownership HTTP client")?;
+        Ok(Self { client, dns })
+    }
+}
+
+/// Resolves the names for the HTTP client with the DNS resolver, refusing the names that resolve to an address
+/// that isn't public. The domains are given by anyone, so they must not lead the server into its own network.
+#[derive(Debug)]
+struct PublicAddressResolver(TokioResolver);
+
+impl Resolve for PublicAddressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let dns = self.0.clone();
+        Box::pin(async move {
+            let lookup = dns.lookup_ip(format!("{}.", name.as_str())).await?;
+            let addrs: Vec<_> = lookup.iter().collect();
+            if let Some(addr) = addrs.iter().find(|&&addr| !is_public_address(addr)) {
+                return Err(format!(
+                    "{} resolves to the address {addr}, which isn't public",
+                    name.as_str()
+                )
+                .into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter().map(|addr| SocketAddr::new(addr, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// Checks if an address is reachable on the internet, so neither private, loopback, link local nor otherwise
+/// reserved.
+fn is_public_address(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, ..] = addr.octets();
+            !(addr.is_private()
+                || addr.is_loopback()
+                || addr.is_link_local()
+                || addr.is_unspecified()
+                || addr.is_broadcast()
+                || addr.is_multicast()
+                || addr.is_documentation()
+                // shared address space of the carrier grade NATs, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64)
+                // "this network", 0.0.0.0/8, and the reserved 240.0.0.0/4
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+            Some(addr) => is_public_address(addr.into()),
+            None => {
+                let first = addr.segments()[0];
+                !(addr.is_loopback()
+                    || addr.is_unspecified()
+                    || addr.is_multicast()
+                    // unique local, fc00::/7, and link local, fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+impl OwnershipResolver for NetworkResolver {
+    fn fetch_well_known<'a>(&'a self, host: &'a str) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move {
+            // addresses aren't resolved, so they're checked here
+            let addr: Option<IpAddr> = match Host::parse(host)? {
+                Host::Ipv4(addr) => Some(addr.into()),
+                Host::Ipv6(addr) => Some(addr.into()),
+                Host::Domain(_) => None,
+            };
+            anyhow::ensure!(addr.is_none_or(is_public_address), "address isn't public");
+            let response = self
+                .client
+                .get(format!("https://{host}{WELL_KNOWN_PATH}"))
+                .send()
+                .await?
+                .error_for_status()?;
+            anyhow::ensure!(
+                response
+                    .content_length()
+                    .is_none_or(|len| len <= MAX_WELL_KNOWN_SIZE as u64),
+                "well known file is too large"
+            );
+            let body = response.bytes().await?;
+            anyhow::ensure!(
+                body.len() <= MAX_WELL_KNOWN_SIZE,
+                "well known file is too large"
+            );
+            Ok(String::from_utf8_lossy(&body).into_owned())
+        })
+    }
+
+    fn lookup_txt<'a>(&'a self, name: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<String>>> {
+        Box::pin(async move {
+            // fully qualified, so the search domains of the system aren't tried
+            let lookup = self.dns.txt_lookup(format!("{name}.")).await?;
+            Ok(lookup
+                .answers()
+                .iter()
+                .filter_map(|record| match &record.data {
+                    RData::TXT(txt) => Some(txt.to_string()),
+                    _ => None,
+                })
+                .collect())
+        })
+    }
+}
+
+/// Checks if the owner of a domain serves its token, either in the well known file, one token per line, or in
+/// a TXT record.
+pub async fn prove_ownership(
+    resolver: &dyn OwnershipResolver,
+    domain: &AllowedDomain,
+    token: &Uuid,
+) -> bool {
+    let host = domain.ownership_host().as_str();
+    let txt_name = format!("{TXT_RECORD_LABEL}.{host}");
+    let token = token.to_string();
+
+    let (well_known, txt) = tokio::join!(
+        resolver.fetch_well_known(host),
+        resolver.lookup_txt(&txt_name)
+    );
+    let in_well_known = well_known
+        .inspect_err(|err| tracing::debug!(?err, %domain, "could not fetch well known file"))
+        .is_ok_and(|file| file.lines().any(|line| line.trim() == token));
+    let in_txt = txt
+        .inspect_err(|err| tracing::debug!(?err, %domain, "could not look up TXT records"))
+        .is_ok_and(|records| records.iter().any(|record| record.trim() == token));
+
+    in_well_known || in_txt
+}
+
+async fn check_domain<'a>(
+    resolver: &dyn OwnershipResolver,
+    domain: &'a DbApiKeyDomain,
+) -> (&'a DbApiKeyDomain, bool) {
+    (
+        domain,
+        prove_ownership(resolver, &domain.domain, &domain.token).await,
+    )
+}
+
+/// Checks the ownership of the given domains, a few at a time, and records each result as soon as it's known.
+/// No transaction is held while the domains are checked over the network. Exempt domains are skipped. Returns the
+/// number of domains verified.
+pub async fn check_domains(
+    pool: &PgPool,
+    resolver: &dyn OwnershipResolver,
+    config: &DomainVerificationConfig,
+    domains: &[DbApiKeyDomain],
+) -> db::Result<usize> {
+    // built upfront, a stream mapping the borrowed domains isn't found to be `Send` by the handlers
+    let checks: Vec<_> = domains
+        .iter()
+        .filter(|domain| !domain.exempt)
+        .map(|domain| check_domain(resolver, domain))
+        .collect();
+    let mut checks = stream::iter(checks).buffer_unordered(CHECK_CONCURRENCY);
+
+    let mut verified_count = 0;
+    while let Some((DbApiKeyDomain { site_key, domain, verified_at, .. }, verified)) =
+        checks.next().await
+    {
+        if verified != verified_at.is_some() {
+            tracing::info!(%site_key, %domain, verified, "ownership of domain checked");
+        }
+        db::update_api_key_domain_check(pool, site_key, domain, verified, config.grace_period_secs)
+            .await?;
+        verified_count += verified as usize;
+    }
+    Ok(verified_count)
+}
+
+/// Checks the ownership of all the domains of an api key right away.
+pub async fn check_api_key_domains(
+    pool: &PgPool,
+    resolver: &dyn OwnershipResolver,
+    config: &DomainVerificationConfig,
+    site_key: &Base64<UrlSafe>,
+) -> db::Result<usize> {
+    let domains = db::fetch_api_key_domains(pool, site_key).await?;
+    check_domains(pool, resolver, config, &domains).await
+}
+
+/// Periodically checks the ownership of the domains that are due, never returning. Every instance of the server
+/// runs a verifier, and the domains claimed by one are skipped by the others.
+pub async fn run_verifier(
+    pool: PgPool,
+    resolver: Arc<dyn OwnershipResolver>,
+    config: DomainVerificationConfig,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs.into()));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let result = async {
+            let domains = db::claim_api_key_domains_to_check(
+                &pool,
+                config.interval_secs,
+                config.recheck_interval_secs,
+                CHECK_BATCH_SIZE,
+            )
+            .await?;
+            check_domains(&pool, resolver.as_ref(), &config, &domains).await
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::error!(?err, "could not check the ownership of the domains");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_address() {
+        for addr in ["93.184.215.14", "2606:2800:21f:cb07:6820:80da:af6b:8b2c"] {
+            assert!(is_public_address(addr.parse().unwrap()), "{addr}");
+        }
+        for addr in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_address(addr.parse().unwrap()), "{addr}");
+        }
+    }
+}
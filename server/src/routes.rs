@@ -10,14 +10,19 @@ use challenge::{
     process_pre_analysis,
 };
 use console::{
-    add_challenge_to_api_key_pool, create_console, delete_console, gen_api_key,
-    get_api_key_challenge_pool, get_api_keys, get_consoles, remove_challenge_from_api_key_pool,
-    revoke_api_key, update_api_key, update_console,
+    add_allowed_domain, add_challenge_to_api_key_pool, create_api_key_secret, create_console,
+    delete_console, gen_api_key, get_allowed_domains, get_api_key_challenge_pool,
+    get_api_key_secrets, get_api_keys, get_consoles, remove_allowed_domain,
+    remove_challenge_from_api_key_pool, revoke_api_key, revoke_api_key_secret, rotate_encoding_key,
+    update_api_key, update_console, verify_allowed_domains,
 };
 use middleware::{
     block_bot_agent, require_admin, require_auth, validate_api_key, validate_console_id,
 };
-use verification::site_verify;
+use verification::{
+    get_jwks, hcaptcha::hcaptcha_site_verify, recaptcha::recaptcha_site_verify, site_verify,
+    turnstile::turnstile_site_verify,
+};
 
 use crate::{
     AppState,
@@ -32,6 +37,7 @@ pub mod admin;
 pub mod challenge;
 pub mod console;
 pub mod custom_headers;
+pub mod emulator;
 mod errors;
 pub mod extractors;
 pub mod middleware;
@@ -67,6 +73,33 @@ pub fn verification(state: &Arc<AppState>) -> Router {
     Router::new()
         .route("/siteverify", post(site_verify))
         .layer(axum::middleware::from_fn(block_bot_agent))
+        // fetched by the backends that verify the response tokens offline
+        .route("/jwks/{site_key}", get(get_jwks))
+        .with_state(state)
+}
+
+/// Router for the endpoints at reCAPTCHA's paths, to be used as a drop-in replacement.
+/// Bots aren't blocked since the verification is called by the backend HTTP client of the site.
+pub fn recaptcha(state: &Arc<AppState>) -> Router {
+    let state = Arc::clone(state);
+    Router::new()
+        .route("/api/siteverify", post(recaptcha_site_verify))
+        .with_state(state)
+}
+
+/// Router for the endpoints at hCaptcha's paths, to be used as a drop-in replacement.
+pub fn hcaptcha(state: &Arc<AppState>) -> Router {
+    let state = Arc::clone(state);
+    Router::new()
+        .route("/siteverify", post(hcaptcha_site_verify))
+        .with_state(state)
+}
+
+/// Router for the endpoints at Cloudflare Turnstile's paths, to be used as a drop-in replacement.
+pub fn turnstile(state: &Arc<AppState>) -> Router {
+    let state = Arc::clone(state);
+    Router::new()
+        .route("/v0/siteverify", post(turnstile_site_verify))
         .with_state(state)
 }
 
@@ -79,6 +112,17 @@ pub fn console(state: &Arc<AppState>) -> Router {
         .route("/", post(add_challenge_to_api_key_pool))
         .route("/", delete(remove_challenge_from_api_key_pool));
 
+    let secrets = Router::new()
+        .route("/", get(get_api_key_secrets))
+        .route("/", post(create_api_key_secret))
+        .route("/{secret_id}", delete(revoke_api_key_secret));
+
+    let allowed_domains = Router::new()
+        .route("/", get(get_allowed_domains))
+        .route("/", post(add_allowed_domain))
+        .route("/", delete(remove_allowed_domain))
+        .route("/verify", post(verify_allowed_domains));
+
     let api_key = Router::new()
         .route("/", get(get_api_keys))
         .route("/", post(gen_api_key))
@@ -87,7 +131,10 @@ pub fn console(state: &Arc<AppState>) -> Router {
             Router::new()
                 .route("/", patch(update_api_key))
                 .route("/", delete(revoke_api_key))
+                .route("/rotate-encoding-key", post(rotate_encoding_key))
                 .nest("/challenge-pool", challenge_pool)
+                .nest("/secrets", secrets)
+                .nest("/allowed-domains", allowed_domains)
                 .layer(axum::middleware::from_fn_with_state(
                     Arc::clone(&state),
                     validate_api_key,
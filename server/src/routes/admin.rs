@@ -9,6 +9,7 @@ use url::Url;
 
 use crate::{
     AppState,
+    analysis::puzzle::PuzzleKind,
     db::{self, RowsAffected},
 };
 
@@ -23,6 +24,9 @@ pub struct AddChallenge {
     pub width: u16,
     /// Default height.
     pub height: u16,
+    /// Kind of puzzle the challenge renders.
+    #[serde(default)]
+    pub puzzle_kind: PuzzleKind,
 }
 
 /// Adds a new challenge to the database.
@@ -31,7 +35,7 @@ pub async fn add_challenge(
     State(state): State<Arc<AppState>>,
     Json(challenge): Json<AddChallenge>,
 ) -> Result<(), AdminError> {
-    let AddChallenge { url, width, height } = challenge;
+    let AddChallenge { url, width, height, puzzle_kind } = challenge;
     let _ = Url::parse(&url).map_err(|_| AdminError::InvalidUrl)?;
 
     db::insert_challenge(
@@ -44,6 +48,7 @@ pub async fn add_challenge(
             small_height: height as i16,
             logo_url: None,
             label: None,
+            puzzle_kind: puzzle_kind.to_string(),
         },
     )
     .await?;
@@ -1,14 +1,17 @@
 //! `/api/challenge` routes.
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::Context;
 use axum::{
     Json,
     extract::{ConnectInfo, State},
+    http::{HeaderMap, header},
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use time::OffsetDateTime;
 use tracing::{Level, Span, instrument};
 use url::Url;
 
@@ -16,20 +19,31 @@ use super::errors::ChallengeError;
 use crate::{
     AppState,
     analysis::{
-        self,
-        interaction::{Interaction, Score},
+        interaction::Interaction,
         proof_of_work::PowChallenge,
+        puzzle::{Puzzle, PuzzleKind},
+        scorer::{Scorer, Verdict},
+        signals::{
+            ChallengeOutcome, HeaderConsistency, InteractionSignal, IpReputation,
+            ProofOfWorkLatency,
+        },
     },
-    db::{self, DbChallenge},
-    domain::hostname::Hostname,
-    encodings::Base64,
+    db::{self, DbApiKey, DbChallenge},
+    domain::{action::Action, form_digest::FormDigest, hostname::Hostname},
+    encodings::{Base64, UrlSafe},
     routes::extractors::SiteKey,
     tokens::{
-        self, pow_challenge,
+        self,
+        clearance::{self, ClearanceClaims},
+        keys::DecodingKeys,
+        pow_challenge, puzzle,
         response::{self, ResponseClaims},
     },
 };
 
+/// Response token of test keys, whose verification passes or fails regardless of the token.
+pub const TEST_RESPONSE_TOKEN: &str = "XXXX.TEST-KEY-RESPONSE.XXXX";
+
 /// Response payload of get challenge route.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetChallenge {
@@ -47,25 +61,50 @@ pub struct GetChallenge {
     pub small_height: u16,
     /// Custom logo URL.
     pub logo_url: Option<String>,
+    /// JWT with the puzzle instance to be solved. Only issued when the `site_key` is present.
+    pub puzzle: Option<String>,
 }
 
 /// Fetches challenges a responds with one of them randomly and its customization.
-/// If `site_key` param is absent it responds with the defaults.
+/// If `site_key` param is absent it responds with the defaults and without a puzzle instance, otherwise
+/// a new puzzle for the chosen challenge is signed with the encoding key of the `site_key`.
 #[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
 pub async fn get_challenge(
     site_key: Option<SiteKey>,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<GetChallenge>, ChallengeError> {
-    let challenges = match site_key {
-        Some(SiteKey(site_key)) => {
-            db::fetch_challenges_with_customization(&state.pool, &site_key).await
-        }
-        None => db::fetch_challenges(&state.pool).await,
-    }
-    .context("failed to fetch challenges")?;
+    let Some(SiteKey(site_key)) = site_key else {
+        let challenges = db::fetch_challenges(&state.pool)
+            .await
+            .context("failed to fetch challenges")?;
+        let challenge = choose_challenge(challenges).ok_or(ChallengeError::NoMatchingChallenge)?;
+        return Ok(Json(challenge.try_into()?));
+    };
+
+    let api_key = db::fetch_api_key_by_site_key(&state.pool, &state.master_key, &site_key)
+        .await
+        .context("failed to fetch api key by site key while getting challenge")?
+        .ok_or(ChallengeError::InvalidKey)?;
+    let challenges = db::fetch_challenges_with_customization(&state.pool, &site_key)
+        .await
+        .context("failed to fetch challenges")?;
     let challenge = choose_challenge(challenges).ok_or(ChallengeError::NoMatchingChallenge)?;
+    let puzzle_kind: PuzzleKind = challenge
+        .puzzle_kind
+        .parse()
+        .map_err(|e: String| anyhow::anyhow!(e))
+        .with_context(|| format!("malformed puzzle kind of challenge: {}", challenge.url))?;
+
+    let mut challenge: GetChallenge = challenge.try_into()?;
+    challenge.puzzle = Some(
+        puzzle::encode(
+            Puzzle::random(challenge.url.clone(), puzzle_kind),
+            api_key.versioned_encoding_key(),
+        )
+        .context("failed encoding jwt puzzle")?,
+    );
 
-    Ok(Json(challenge.try_into()?))
+    Ok(Json(challenge))
 }
 
 /// Fetches all available challenges.
@@ -93,34 +132,78 @@ pub struct PowResponse {
 }
 
 /// Constructs a unique proof of work challenge and encodes it in a JWT.
-/// Difficulty hardcoded to 3. Future work may include customizing it in an admin page.
+/// The algorithm and difficulty are configured per api key.
 #[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
 pub async fn get_proof_of_work_challenge(
     SiteKey(site_key): SiteKey,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<PowResponse>, ChallengeError> {
-    let enc_key = db::fetch_api_key_by_site_key(&state.pool, &site_key)
+    let api_key = db::fetch_api_key_by_site_key(&state.pool, &state.master_key, &site_key)
         .await
         .context("failed to fetch api key by site key while getting proof of work")?
-        .ok_or(ChallengeError::InvalidKey)?
-        .encoding_key;
+        .ok_or(ChallengeError::InvalidKey)?;
 
     Ok(Json(PowResponse {
-        token: pow_challenge::encode(PowChallenge::random(3), &enc_key)
-            .context("failed encoding jwt response")?,
+        token: pow_challenge::encode(
+            PowChallenge::random(api_key.pow_algorithm, api_key.pow_difficulty),
+            api_key.versioned_encoding_key(),
+        )
+        .context("failed encoding jwt response")?,
     }))
 }
 
 /// Expected payload for processing challenge route.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChallengeResults {
-    /// Wether or not successful.
+    /// Wether or not successful, as reported by the widget. It's advisory only, the outcome is decided
+    /// by validating the `solution` when there is one.
     pub success: bool,
     /// The challenge URL that it was solved.
     pub challenge: Url,
+    /// The answer to the puzzle issued with the challenge.
+    #[serde(default)]
+    pub solution: Option<PuzzleSolution>,
     /// The list of interactions performed while solving the challenge.
     #[serde(default)]
     pub interactions: Vec<Interaction>,
+    /// Action bound to the response token.
+    #[serde(default)]
+    pub action: Option<Action>,
+    /// Digest of the protected form fields bound to the response token.
+    #[serde(default)]
+    pub form_digest: Option<FormDigest>,
+}
+
+/// Puzzle solution containing the puzzle in JWT and the answer to verify.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PuzzleSolution {
+    /// JWT with the puzzle instance.
+    pub puzzle: String,
+    /// Answer given by the user.
+    pub answer: Vec<u16>,
+}
+
+impl PuzzleSolution {
+    /// Checks the answer against the puzzle, which must have been issued for the given `challenge`, and consumes
+    /// the puzzle, so it can't be answered again, whether the answer is right or not.
+    pub async fn verify(
+        &self,
+        exec: impl PgExecutor<'_> + Send,
+        site_key: &Base64<UrlSafe>,
+        challenge: &Url,
+        dec_keys: DecodingKeys<'_>,
+    ) -> Result<bool, ChallengeError> {
+        let puzzle = tokens::puzzle::decode(&self.puzzle, dec_keys)
+            .map_err(ChallengeError::InvalidPuzzle)?;
+        Span::current().record("puzzle_decoded", tracing::field::debug(&puzzle.other));
+
+        let first_use =
+            db::consume_puzzle(exec, site_key, &puzzle.other, puzzle.iat(), puzzle.exp()).await?;
+        if !first_use {
+            return Err(ChallengeError::ReplayedPuzzle);
+        }
+        Ok(&puzzle.other.challenge == challenge && puzzle.other.verify_answer(&self.answer))
+    }
 }
 
 /// Response payload of processing the challenge route.
@@ -128,15 +211,33 @@ pub struct ChallengeResults {
 pub struct ChallengeResponse {
     /// JWT as proof of the challenge solution.
     pub token: String,
+    /// JWT to skip the challenge in the next pre analyses, only issued after solving a challenge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearance: Option<String>,
+}
+
+impl ChallengeResponse {
+    /// Canned response of a test key, see [`TEST_RESPONSE_TOKEN`].
+    pub fn test_key() -> Self {
+        Self { token: TEST_RESPONSE_TOKEN.into(), clearance: None }
+    }
 }
 
 /// Proccesses the challenge results and responds with a proof in the form of a JWT.
-#[instrument(skip(state, results), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR),
+/// The challenge is only considered solved if the answer is accepted by the validator of the puzzle, which
+/// is answered only once, in which case the score combines the analysis of the interactions with the other risk signals.
+/// Challenges that don't submit an answer yet fall back to the outcome reported by the widget.
+/// Solving a puzzle without any signal vetoing the request also grants a clearance, if enabled for the api key.
+/// Test keys get the canned [`TEST_RESPONSE_TOKEN`] instead.
+#[instrument(skip(state, results, headers), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR),
     fields(
         ?addr,
         success = results.success,
         %challenge = results.challenge,
-        interaction_score,
+        puzzle_decoded,
+        solved,
+        score,
+        reasons,
     )
 )]
 pub async fn process_challenge(
@@ -144,29 +245,71 @@ pub async fn process_challenge(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     SiteKey(site_key): SiteKey,
     hostname: Hostname,
+    headers: HeaderMap,
     Json(results): Json<ChallengeResults>,
 ) -> Result<Json<ChallengeResponse>, ChallengeError> {
-    // TODO: potentially heavy CPU operation - offload to a task
-    let Score(score) = analysis::interaction::interaction_analysis(&results.interactions);
-    Span::current().record("interaction_score", score);
-    let score = match results.success {
-        true => {
-            tracing::warn!("interaction analysis disabled");
-            1.
+    let api_key = db::fetch_api_key_by_site_key(&state.pool, &state.master_key, &site_key)
+        .await
+        .context("failed to fetch api key by site key while processing challenge")?
+        .ok_or(ChallengeError::InvalidKey)?;
+    if api_key.mode.is_test() {
+        return Ok(Json(ChallengeResponse::test_key()));
+    }
+
+    let solved = match &results.solution {
+        Some(solution) => {
+            solution
+                .verify(
+                    &state.pool,
+                    &site_key,
+                    &results.challenge,
+                    api_key.decoding_keys(),
+                )
+                .await?
         }
-        false => 0.,
+        None => results.success,
+    };
+    Span::current().record("solved", solved);
+    if solved != results.success {
+        tracing::warn!("challenge outcome reported by the widget doesn't match the validation");
+    }
+
+    let weights = &state.analysis.weights;
+    let verdict = scorer(&state, addr, &headers)
+        .with(weights.challenge, ChallengeOutcome { solved })
+        .with(
+            weights.interaction,
+            InteractionSignal(&results.interactions),
+        )
+        .evaluate();
+    record_verdict(&verdict);
+    let score = verdict.score;
+
+    // a clearance skips the challenge later, so it takes a validated answer and the score the pre analysis would ask for
+    let cleared =
+        solved && results.solution.is_some() && score >= api_key.pre_analysis_policy.min_score;
+    let policy = &api_key.clearance_policy;
+    let clearance = match cleared && policy.enabled() {
+        true => Some(
+            clearance::encode(
+                ClearanceClaims::new(policy, site_key, addr.ip(), user_agent(&headers), score),
+                policy,
+                api_key.versioned_encoding_key(),
+            )
+            .context("failed encoding jwt clearance")?,
+        ),
+        false => None,
     };
 
     Ok(Json(ChallengeResponse {
-        token: response::encode(
-            ResponseClaims { score, addr: addr.ip(), host: hostname },
-            &db::fetch_api_key_by_site_key(&state.pool, &site_key)
-                .await
-                .context("failed to fetch api key by site key while processing challenge")?
-                .ok_or(ChallengeError::InvalidKey)?
-                .encoding_key,
+        token: encode_response(
+            ResponseClaims::new(score, addr.ip(), hostname)
+                .with_action(results.action)
+                .with_form_digest(results.form_digest),
+            &api_key,
         )
         .context("failed encoding jwt response")?,
+        clearance,
     }))
 }
 
@@ -177,6 +320,15 @@ pub struct PreAnalysisRequest {
     pub interactions: Vec<Interaction>,
     /// Proof of work computed by the client.
     pub proof_of_work: ProofOfWork,
+    /// JWT clearance granted by a previous challenge.
+    #[serde(default)]
+    pub clearance: Option<String>,
+    /// Action bound to the response token.
+    #[serde(default)]
+    pub action: Option<Action>,
+    /// Digest of the protected form fields bound to the response token.
+    #[serde(default)]
+    pub form_digest: Option<FormDigest>,
 }
 
 /// Proof of work containing the challenge in JWT and the solution to verify.
@@ -189,14 +341,39 @@ pub struct ProofOfWork {
 }
 
 impl ProofOfWork {
-    pub fn verify(&self, dec_key: &Base64) -> Result<bool, jsonwebtoken::errors::Error> {
-        let pow_challenge = tokens::pow_challenge::decode(&self.challenge, dec_key.as_str())
-            .inspect_err(|_| {
+    /// Redeems the challenge and verifies the solution, so the same proof of work can't be used again. Replayed
+    /// challenges are rejected before the solution is hashed, which is done off the async workers since Argon2id
+    /// is expensive.
+    pub async fn redeem(
+        &self,
+        exec: impl PgExecutor<'_> + Send,
+        site_key: &Base64<UrlSafe>,
+        dec_keys: DecodingKeys<'_>,
+    ) -> Result<PowChallenge, ChallengeError> {
+        let pow_challenge =
+            tokens::pow_challenge::decode(&self.challenge, dec_keys).inspect_err(|_| {
                 Span::current().record("pow_jwt", &self.challenge);
             })?;
-        Span::current().record("pow_decoded", tracing::field::debug(&pow_challenge));
+        Span::current().record("pow_decoded", tracing::field::debug(&pow_challenge.other));
+
+        let first_use =
+            db::consume_pow_challenge(exec, site_key, &pow_challenge.other, pow_challenge.exp())
+                .await?;
+        if !first_use {
+            return Err(ChallengeError::ReplayedProofOfWork);
+        }
 
-        Ok(pow_challenge.verify_solution(self.solution))
+        let (pow_challenge, solution) = (pow_challenge.other, self.solution);
+        let (pow_challenge, verified) = tokio::task::spawn_blocking(move || {
+            let verified = pow_challenge.verify_solution(solution);
+            (pow_challenge, verified)
+        })
+        .await
+        .context("failed to verify proof of work")?;
+        match verified {
+            true => Ok(pow_challenge),
+            false => Err(ChallengeError::FailedProofOfWork),
+        }
     }
 }
 
@@ -215,15 +392,20 @@ pub enum PreAnalysisResponse {
 /// If the pre analysis is successful it instantly responds with the token, otherwise the widget will
 /// prompt the user to solve a captcha challenge.
 ///
-/// The pre analysis consists on analysing user input and checking the proof of work. At the moment,
-/// it is configured to always fail thus forcing the user to solve a captcha every time.
+/// A visitor presenting a valid clearance of a recently solved challenge is trusted right away. Otherwise,
+/// the pre analysis consists on checking the proof of work and scoring the risk signals. The verdict is
+/// then decided by the pre analysis policy of the api key: the score threshold, the signals required to be clean
+/// and the maximum rate of visitors that can skip the challenge. Test keys always skip it.
 /// TODO: check fingerprint.
-#[instrument(skip(state, request), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR),
+#[instrument(skip(state, request, headers), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR),
     fields(
+        cleared,
         pow_jwt,
         pow_decoded,
         pow_solution = request.proof_of_work.solution,
-        interaction_score,
+        score,
+        reasons,
+        accepted,
     )
 )]
 pub async fn process_pre_analysis(
@@ -231,45 +413,81 @@ pub async fn process_pre_analysis(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     SiteKey(site_key): SiteKey,
     hostname: Hostname,
+    headers: HeaderMap,
     Json(request): Json<PreAnalysisRequest>,
 ) -> Result<Json<PreAnalysisResponse>, ChallengeError> {
     // TODO: look at cookies and other fingerprints
-    let crypt_key = db::fetch_api_key_by_site_key(&state.pool, &site_key)
+    let api_key = db::fetch_api_key_by_site_key(&state.pool, &state.master_key, &site_key)
         .await
         .context("failed to fetch api key by api secret while processing pre analysis")?
-        .ok_or(ChallengeError::InvalidKey)?
-        .encoding_key;
+        .ok_or(ChallengeError::InvalidKey)?;
+    if api_key.mode.is_test() {
+        return Ok(Json(PreAnalysisResponse::Success {
+            response: ChallengeResponse::test_key(),
+        }));
+    }
 
-    let verified = request.proof_of_work.verify(&crypt_key)?;
-    if !verified {
-        return Err(ChallengeError::FailedProofOfWork);
+    let clearance = request.clearance.as_deref().and_then(|jwt| {
+        clearance::decode(jwt, api_key.decoding_keys())
+            .inspect_err(|err| tracing::debug!(?err, "invalid clearance"))
+            .ok()
+            .filter(|claims| claims.permits(&site_key, addr.ip(), user_agent(&headers)))
+    });
+    Span::current().record("cleared", clearance.is_some());
+    if let Some(clearance) = clearance {
+        return Ok(Json(PreAnalysisResponse::Success {
+            response: ChallengeResponse {
+                token: encode_response(
+                    ResponseClaims::new(clearance.score, addr.ip(), hostname)
+                        .with_action(request.action)
+                        .with_form_digest(request.form_digest),
+                    &api_key,
+                )
+                .context("failed encoding jwt response")?,
+                clearance: None,
+            },
+        }));
     }
 
-    // TODO: potentially heavy CPU operation - offload to rayon
-    let Score(score) = analysis::interaction::interaction_analysis(&request.interactions);
-    Span::current().record("interaction_score", score);
-
-    let response = match score {
-        _ => PreAnalysisResponse::Failure,
-        // For now, assume pre-analysis always fails
-        #[expect(unreachable_patterns)]
-        0f32..0.5 => PreAnalysisResponse::Failure,
-        #[expect(unreachable_patterns)]
-        0.5..=1. => PreAnalysisResponse::Success {
+    let pow_challenge = request
+        .proof_of_work
+        .redeem(&state.pool, &site_key, api_key.decoding_keys())
+        .await?;
+
+    let weights = &state.analysis.weights;
+    let verdict = scorer(&state, addr, &headers)
+        .with(
+            weights.proof_of_work,
+            ProofOfWorkLatency { challenge: &pow_challenge, solved_at: OffsetDateTime::now_utc() },
+        )
+        .with(
+            weights.interaction,
+            InteractionSignal(&request.interactions),
+        )
+        .evaluate();
+    record_verdict(&verdict);
+
+    let policy = &api_key.pre_analysis_policy;
+    let accepted = policy.accepts(&verdict);
+    Span::current().record("accepted", accepted);
+    let passed = db::record_pre_analysis(&state.pool, &site_key, accepted, policy.max_pass_rate)
+        .await
+        .context("failed to record pre analysis")?;
+
+    let response = match passed {
+        true => PreAnalysisResponse::Success {
             response: ChallengeResponse {
-                token: response::encode(
-                    ResponseClaims { score, addr: addr.ip(), host: hostname },
-                    &crypt_key,
+                token: encode_response(
+                    ResponseClaims::new(verdict.score, addr.ip(), hostname)
+                        .with_action(request.action)
+                        .with_form_digest(request.form_digest),
+                    &api_key,
                 )
                 .context("failed encoding jwt response")?,
+                clearance: None,
             },
         },
-        #[expect(unreachable_patterns)]
-        _ => {
-            return Err(ChallengeError::Unexpected(anyhow::anyhow!(
-                "score not in range [0.0 .. 1.0]"
-            )));
-        }
+        false => PreAnalysisResponse::Failure,
     };
 
     Ok(Json(response))
@@ -280,16 +498,25 @@ pub async fn process_pre_analysis(
 pub struct AccessibilityRequest {
     /// Proof of work computed by the client.
     pub proof_of_work: ProofOfWork,
+    /// Action bound to the response token.
+    #[serde(default)]
+    pub action: Option<Action>,
+    /// Digest of the protected form fields bound to the response token.
+    #[serde(default)]
+    pub form_digest: Option<FormDigest>,
 }
 
-/// Alternative process for accessibility users. At the moment, just checks proof of work.
+/// Alternative process for accessibility users. At the moment, just checks proof of work and scores
+/// the risk signals that don't depend on interactions.
 /// TODO: check fingerprint.
-#[instrument(skip(state, request), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR),
+#[instrument(skip(state, request, headers), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR),
     fields(
         ?addr,
         pow_jwt,
         pow_decoded,
         solution = request.proof_of_work.solution,
+        score,
+        reasons,
     )
 )]
 pub async fn process_accessibility_challenge(
@@ -297,30 +524,86 @@ pub async fn process_accessibility_challenge(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     SiteKey(site_key): SiteKey,
     hostname: Hostname,
+    headers: HeaderMap,
     Json(request): Json<AccessibilityRequest>,
 ) -> Result<Json<PreAnalysisResponse>, ChallengeError> {
     // TODO: look at cookies and other fingerprints
-    let crypt_key = db::fetch_api_key_by_site_key(&state.pool, &site_key)
+    let api_key = db::fetch_api_key_by_site_key(&state.pool, &state.master_key, &site_key)
         .await
         .context("failed to fetch api key by api secret while processing accessility challenge")?
-        .ok_or(ChallengeError::InvalidKey)?
-        .encoding_key;
-
-    let verified = request.proof_of_work.verify(&crypt_key)?;
-    if !verified {
-        return Err(ChallengeError::FailedProofOfWork);
+        .ok_or(ChallengeError::InvalidKey)?;
+    if api_key.mode.is_test() {
+        return Ok(Json(PreAnalysisResponse::Success {
+            response: ChallengeResponse::test_key(),
+        }));
     }
 
-    let token = response::encode(
-        ResponseClaims { score: 1.0, addr: addr.ip(), host: hostname },
-        &crypt_key,
+    let pow_challenge = request
+        .proof_of_work
+        .redeem(&state.pool, &site_key, api_key.decoding_keys())
+        .await?;
+
+    let verdict = scorer(&state, addr, &headers)
+        .with(
+            state.analysis.weights.proof_of_work,
+            ProofOfWorkLatency { challenge: &pow_challenge, solved_at: OffsetDateTime::now_utc() },
+        )
+        .evaluate();
+    record_verdict(&verdict);
+    let score = verdict.score;
+    let token = encode_response(
+        ResponseClaims::new(score, addr.ip(), hostname)
+            .with_action(request.action)
+            .with_form_digest(request.form_digest),
+        &api_key,
     )?;
 
     Ok(Json(PreAnalysisResponse::Success {
-        response: ChallengeResponse { token },
+        response: ChallengeResponse { token, clearance: None },
     }))
 }
 
+/// Scorer with the risk signals common to every request: ip reputation and header consistency.
+fn scorer<'a>(state: &'a AppState, addr: SocketAddr, headers: &'a HeaderMap) -> Scorer<'a> {
+    let weights = &state.analysis.weights;
+    Scorer::new()
+        .with(
+            weights.ip_reputation,
+            IpReputation { addr: addr.ip(), denylist: &state.analysis.ip_denylist },
+        )
+        .with(weights.headers, HeaderConsistency(headers))
+}
+
+/// Encodes the response token with the lifetime and the signing algorithm configured for the api key.
+pub(crate) fn encode_response(
+    response_claims: ResponseClaims,
+    api_key: &DbApiKey,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let timeout = Duration::from_secs(api_key.response_ttl_secs.into());
+    match &api_key.signing_key {
+        Some(key_pair) => response::encode_with_key_pair(response_claims, key_pair, timeout),
+        None => response::encode_with_timeout(
+            response_claims,
+            api_key.versioned_encoding_key(),
+            timeout,
+        ),
+    }
+}
+
+/// User agent of the request, if valid.
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Records the score and its reasons in the current span.
+fn record_verdict(verdict: &Verdict) {
+    Span::current()
+        .record("score", verdict.score)
+        .record("reasons", tracing::field::debug(verdict.reasons()));
+}
+
 fn choose_challenge(mut challenges: Vec<DbChallenge>) -> Option<DbChallenge> {
     match &challenges[..] {
         [] => None,
@@ -344,6 +627,7 @@ impl TryFrom<DbChallenge> for GetChallenge {
             small_width: db_challenge.small_width as u16,
             small_height: db_challenge.small_height as u16,
             logo_url: db_challenge.logo_url,
+            puzzle: None,
         })
     }
 }
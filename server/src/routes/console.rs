@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Context;
 use axum::{
@@ -6,18 +6,29 @@ use axum::{
     extract::{Path, State},
 };
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use tracing::{Level, instrument};
 use uuid::Uuid;
 
-use super::{errors::ConsoleError, extractors::User};
+use super::{errors::ConsoleError, extractors::User, verification::DEFAULT_MIN_SCORE};
 use crate::{
     AppState,
+    analysis::{
+        policy::PreAnalysisPolicy,
+        proof_of_work::{PowAlgorithm, PowChallenge},
+    },
     db::{
-        self, DbApiKey, DbChallengeCustomization, DbConsole, DbUpdateApiKey,
-        DbUpdateChallengeCustomization, DbUpdateConsole, RowsAffected,
+        self, DbApiKey, DbApiKeyDomain, DbApiKeySecret, DbChallengeCustomization, DbConsole,
+        DbUpdateApiKey, DbUpdateChallengeCustomization, DbUpdateConsole, RowsAffected,
     },
-    domain::{hostname::Hostname, serde::nested_option},
+    domain::{allowed_domain::AllowedDomain, api_key_mode::ApiKeyMode, serde::nested_option},
     encodings::{Base64, KEY_SIZE, Standard, UrlSafe},
+    ownership,
+    tokens::{
+        clearance::ClearancePolicy,
+        keys, response,
+        signing::{SigningAlgorithm, SigningKeyPair},
+    },
 };
 
 /// Response payload of retrieving a console.
@@ -114,10 +125,30 @@ pub struct ApiKeyResponse {
     pub label: Option<String>,
     /// Public site key encoded in base64 url safe alphabet.
     pub site_key: Base64<UrlSafe>,
-    /// Secret site key encoded in base64 standard alphabet.
-    pub secret: Base64,
-    /// Allowed domains the api key is valid.
-    pub allowed_domains: Vec<Hostname>,
+    /// Secret site key encoded in base64 standard alphabet. Only present when the api key is generated, since
+    /// only its hash is stored. The secrets of an api key are listed in its own route with a hint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<Base64>,
+    /// Verified allowed domains the api key is valid. The pending ones are listed in its own route.
+    pub allowed_domains: Vec<AllowedDomain>,
+    /// Difficulty of the proof of work challenges.
+    pub pow_difficulty: u16,
+    /// Algorithm of the proof of work challenges.
+    pub pow_algorithm: PowAlgorithm,
+    /// Minimum score of a response to pass the verification.
+    pub min_score: f32,
+    /// Lifetime in seconds of the response tokens.
+    pub response_ttl_secs: u32,
+    /// Policy to skip the challenge after the pre analysis.
+    pub pre_analysis_policy: PreAnalysisPolicy,
+    /// Policy of the clearances issued after solving a challenge.
+    pub clearance_policy: ClearancePolicy,
+    /// Algorithm that signs the response tokens.
+    pub signing_algorithm: SigningAlgorithm,
+    /// Version of the encoding key, incremented on each rotation.
+    pub encoding_key_version: i32,
+    /// Live, or a test key that always passes or always fails the verification.
+    pub mode: ApiKeyMode,
 }
 
 /// Gets api keys for a console id given in the path.
@@ -126,7 +157,7 @@ pub async fn get_api_keys(
     State(state): State<Arc<AppState>>,
     Path(console_id): Path<Uuid>,
 ) -> Result<Json<Vec<ApiKeyResponse>>, ConsoleError> {
-    let keys = db::fetch_api_keys(&state.pool, &console_id)
+    let keys = db::fetch_api_keys(&state.pool, &state.master_key, &console_id)
         .await
         .with_context(|| format!("failed to fetch api keys for console id '{console_id}'"))?
         .into_iter()
@@ -136,8 +167,9 @@ pub async fn get_api_keys(
     Ok(Json(keys))
 }
 
-/// Generates a random api key for a console id given in the path.
-#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+/// Generates a random api key for a console id given in the path. Its secret is only returned in this response,
+/// so the response isn't traced.
+#[instrument(skip(state), err(Debug, level = Level::ERROR))]
 pub async fn gen_api_key(
     State(state): State<Arc<AppState>>,
     Path(console_id): Path<Uuid>,
@@ -147,9 +179,17 @@ pub async fn gen_api_key(
         let enc_key = Base64::<Standard>::random::<KEY_SIZE>();
         let secret = Base64::<Standard>::random::<KEY_SIZE>();
 
-        match db::insert_api_key(&state.pool, &site_key, &console_id, &enc_key, &secret, &[])
-            .await
-            .map_err(ConsoleError::from)
+        match db::insert_api_key(
+            &state.pool,
+            &state.master_key,
+            &site_key,
+            &console_id,
+            &enc_key,
+            &secret,
+            &[],
+        )
+        .await
+        .map_err(ConsoleError::from)
         {
             Ok(()) => break (site_key, secret),
             Err(ConsoleError::Duplicate) => continue,
@@ -159,20 +199,151 @@ pub async fn gen_api_key(
     Ok(Json(ApiKeyResponse {
         label: None,
         site_key,
-        secret,
+        secret: Some(secret),
         allowed_domains: Vec::new(),
+        pow_difficulty: PowChallenge::DEFAULT_DIFFICULTY,
+        pow_algorithm: PowAlgorithm::default(),
+        min_score: DEFAULT_MIN_SCORE,
+        response_ttl_secs: response::DEFAULT_TTL_SECS,
+        pre_analysis_policy: PreAnalysisPolicy::default(),
+        clearance_policy: ClearancePolicy::default(),
+        signing_algorithm: SigningAlgorithm::default(),
+        encoding_key_version: 1,
+        mode: ApiKeyMode::default(),
     }))
 }
 
 /// Expected payload for updating the api_key route.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct UpdateApiKeyRequest {
     /// Label. `None` means don't change.
     #[serde(default)]
     pub label: Option<String>,
-    /// Allowed domains. `None` means don't change.
+    /// Allowed domains, replacing the current ones. `None` means don't change.
+    #[serde(default)]
+    pub allowed_domains: Option<Vec<String>>,
+    /// Proof of work difficulty, up to the maximum of the algorithm. `None` means don't change.
+    #[serde(default)]
+    pub pow_difficulty: Option<u16>,
+    /// Proof of work algorithm, which requires the difficulty when it changes. `None` means don't change.
+    #[serde(default)]
+    pub pow_algorithm: Option<PowAlgorithm>,
+    /// Minimum score to pass the verification. `None` means don't change.
+    #[serde(default)]
+    pub min_score: Option<f32>,
+    /// Lifetime in seconds of the response tokens. `None` means don't change.
+    #[serde(default)]
+    pub response_ttl_secs: Option<u32>,
+    /// Pre analysis policy. `None` means don't change.
     #[serde(default)]
-    pub allowed_domains: Option<Vec<Hostname>>,
+    pub pre_analysis_policy: Option<PreAnalysisPolicy>,
+    /// Clearance policy. `None` means don't change.
+    #[serde(default)]
+    pub clearance_policy: Option<ClearancePolicy>,
+    /// Signing algorithm of the response tokens. `None` means don't change.
+    /// Changing to ES256 or EdDSA generates a new keypair, published in the JWKS of the site key. The public key
+    /// of the replaced keypair stays in the JWKS for the default grace period of a key rotation.
+    #[serde(default)]
+    pub signing_algorithm: Option<SigningAlgorithm>,
+    /// Mode, `test-pass` or `test-fail` for a test key. `None` means don't change.
+    #[serde(default)]
+    pub mode: Option<ApiKeyMode>,
+}
+
+/// Parses an allowed domain and checks its policy, with an error naming the domain.
+fn validate_allowed_domain(domain: &str) -> Result<AllowedDomain, String> {
+    AllowedDomain::parse(domain)
+        .and_then(|allowed| allowed.validate_policy().map(|_| allowed))
+        .map_err(|err| format!("allowed domain `{domain}`: {err}"))
+}
+
+/// Parses the allowed domains and checks the policy of the new ones, reporting the error of every invalid domain
+/// at once. The `stored` domains are kept as they are, like the exempt `localhost`, so the whole list can be sent
+/// back with changes.
+fn validate_allowed_domains_update(
+    value: Option<&[String]>,
+    stored: &[DbApiKeyDomain],
+) -> Result<Option<Vec<AllowedDomain>>, ConsoleError> {
+    let Some(domains) = value else {
+        return Ok(None);
+    };
+
+    let mut allowed_domains = Vec::new();
+    let mut errors = Vec::new();
+    for domain in domains {
+        let validated = AllowedDomain::parse(domain)
+            .and_then(|allowed| match stored.iter().any(|d| d.domain == allowed) {
+                true => Ok(allowed),
+                false => allowed.validate_policy().map(|_| allowed),
+            })
+            .map_err(|err| format!("allowed domain `{domain}`: {err}"));
+        match validated {
+            Ok(allowed) => allowed_domains.push(allowed),
+            Err(err) => errors.push(err),
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(Some(allowed_domains)),
+        false => Err(ConsoleError::InvalidInput { what: errors.join("; ") }),
+    }
+}
+
+/// Checks the proof of work update against the algorithm of the api key once updated. Changing the algorithm
+/// changes what a level of difficulty is worth, so the difficulty must be given along.
+fn validate_pow_update(
+    current_algorithm: PowAlgorithm,
+    algorithm: Option<PowAlgorithm>,
+    difficulty: Option<u16>,
+) -> Result<Option<i16>, ConsoleError> {
+    let algorithm = match algorithm {
+        Some(algorithm) if algorithm != current_algorithm && difficulty.is_none() => {
+            return Err(ConsoleError::InvalidInput {
+                what: "pow_difficulty is required to change the pow_algorithm".into(),
+            });
+        }
+        Some(algorithm) => algorithm,
+        None => current_algorithm,
+    };
+
+    let range = *PowChallenge::DIFFICULTY_RANGE.start()..=algorithm.max_difficulty();
+    difficulty
+        .map(|difficulty| match range.contains(&difficulty) {
+            true => Ok(difficulty as i16),
+            false => Err(ConsoleError::InvalidInput {
+                what: format!(
+                    "pow_difficulty out of range [{}:{}] for {algorithm}",
+                    range.start(),
+                    range.end()
+                ),
+            }),
+        })
+        .transpose()
+}
+
+fn validate_min_score_update(value: Option<f32>) -> Result<Option<f32>, ConsoleError> {
+    value
+        .map(|min_score| match (0. ..=1.).contains(&min_score) {
+            true => Ok(min_score),
+            false => {
+                Err(ConsoleError::InvalidInput { what: "min_score out of range [0:1]".into() })
+            }
+        })
+        .transpose()
+}
+
+fn validate_response_ttl_update(value: Option<u32>) -> Result<Option<i32>, ConsoleError> {
+    value
+        .map(|ttl| match response::TTL_SECS_RANGE.contains(&ttl) {
+            true => Ok(ttl as i32),
+            false => Err(ConsoleError::InvalidInput {
+                what: format!(
+                    "response_ttl_secs out of range [{}:{}]",
+                    response::TTL_SECS_RANGE.start(),
+                    response::TTL_SECS_RANGE.end()
+                ),
+            }),
+        })
+        .transpose()
 }
 
 /// Updates api key for a given site key that belongs to console.
@@ -182,15 +353,68 @@ pub async fn update_api_key(
     Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
     Json(request): Json<UpdateApiKeyRequest>,
 ) -> Result<(), ConsoleError> {
+    let api_key = db::fetch_api_key_by_site_key(&state.pool, &state.master_key, &site_key)
+        .await
+        .with_context(|| format!("failed to fetch api key '{site_key}' to update it"))?
+        .ok_or_else(|| ConsoleError::NotFound {
+            what: format!("sitekey {site_key} for console with id {console_id}"),
+        })?;
+    let signing_algorithm = request
+        .signing_algorithm
+        .filter(|&algorithm| algorithm != api_key.signing_algorithm());
+    let signing_key = signing_algorithm.and_then(SigningKeyPair::generate);
+    // tokens in flight were signed by the replaced keypair
+    let previous_verifying_key_expires_at =
+        (signing_algorithm.is_some() && api_key.signing_key.is_some()).then(|| {
+            OffsetDateTime::now_utc() + Duration::from_secs(keys::DEFAULT_GRACE_PERIOD_SECS.into())
+        });
+    let stored_domains = match request.allowed_domains {
+        Some(_) => db::fetch_api_key_domains(&state.pool, &site_key)
+            .await
+            .with_context(|| format!("failed to fetch allowed domains of api key '{site_key}'"))?,
+        None => Vec::new(),
+    };
+    let allowed_domains =
+        validate_allowed_domains_update(request.allowed_domains.as_deref(), &stored_domains)?;
     let update = DbUpdateApiKey {
         label: request.label.as_deref(),
-        allowed_domains: request.allowed_domains.as_deref(),
+        allowed_domains: allowed_domains.as_deref(),
+        pow_difficulty: validate_pow_update(
+            api_key.pow_algorithm,
+            request.pow_algorithm,
+            request.pow_difficulty,
+        )?,
+        pow_algorithm: request.pow_algorithm,
+        min_score: validate_min_score_update(request.min_score)?,
+        response_ttl_secs: validate_response_ttl_update(request.response_ttl_secs)?,
+        pre_analysis_policy: request
+            .pre_analysis_policy
+            .as_ref()
+            .map(|policy| policy.validate().map(|_| policy))
+            .transpose()
+            .map_err(|what| ConsoleError::InvalidInput { what })?,
+        clearance_policy: request
+            .clearance_policy
+            .as_ref()
+            .map(|policy| policy.validate().map(|_| policy))
+            .transpose()
+            .map_err(|what| ConsoleError::InvalidInput { what })?,
+        signing_algorithm,
+        signing_key: signing_key.as_ref(),
+        previous_verifying_key_expires_at: previous_verifying_key_expires_at.as_ref(),
+        mode: request.mode,
     };
-    let rows_affected = db::update_api_key(&state.pool, &site_key, &console_id, update)
-        .await
-        .with_context(|| {
-            format!("failed to update api key '{site_key}' for console id '{console_id}'")
-        })?;
+    let rows_affected = db::update_api_key(
+        &state.pool,
+        &state.master_key,
+        &site_key,
+        &console_id,
+        update,
+    )
+    .await
+    .with_context(|| {
+        format!("failed to update api key '{site_key}' for console id '{console_id}'")
+    })?;
 
     match rows_affected {
         RowsAffected(0) => Err(ConsoleError::NotFound {
@@ -200,6 +424,71 @@ pub async fn update_api_key(
     }
 }
 
+/// Expected payload for rotating the encoding key of an api key.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RotateEncodingKeyRequest {
+    /// Seconds the previous encoding key keeps decoding the tokens in flight. `None` means the default.
+    #[serde(default)]
+    pub grace_period_secs: Option<u32>,
+}
+
+/// Response payload of rotating the encoding key of an api key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateEncodingKeyResponse {
+    /// Version of the new encoding key.
+    pub encoding_key_version: i32,
+    /// End of the grace period of the previous encoding key.
+    #[serde(with = "time::serde::rfc3339")]
+    pub previous_key_expires_at: OffsetDateTime,
+}
+
+/// Rotates the encoding key of an api key for a given site key that belongs to console.
+/// Tokens encoded with the previous key are still accepted during the grace period.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+pub async fn rotate_encoding_key(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
+    Json(request): Json<RotateEncodingKeyRequest>,
+) -> Result<Json<RotateEncodingKeyResponse>, ConsoleError> {
+    let grace_period_secs = request
+        .grace_period_secs
+        .unwrap_or(keys::DEFAULT_GRACE_PERIOD_SECS);
+    if grace_period_secs > keys::MAX_GRACE_PERIOD_SECS {
+        return Err(ConsoleError::InvalidInput {
+            what: format!(
+                "grace_period_secs out of range [0:{}]",
+                keys::MAX_GRACE_PERIOD_SECS
+            ),
+        });
+    }
+
+    let enc_key = Base64::<Standard>::random::<KEY_SIZE>();
+    let previous_key_expires_at =
+        OffsetDateTime::now_utc() + Duration::from_secs(grace_period_secs.into());
+    let encoding_key_version = db::rotate_encoding_key(
+        &state.pool,
+        &state.master_key,
+        &site_key,
+        &console_id,
+        &enc_key,
+        &previous_key_expires_at,
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "failed to rotate encoding key of api key '{site_key}' for console id '{console_id}'"
+        )
+    })?
+    .ok_or_else(|| ConsoleError::NotFound {
+        what: format!("sitekey {site_key} for console with id {console_id}"),
+    })?;
+
+    Ok(Json(RotateEncodingKeyResponse {
+        encoding_key_version,
+        previous_key_expires_at,
+    }))
+}
+
 /// Revokes an existing api key for a given site key that belongs to console.
 #[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
 pub async fn revoke_api_key(
@@ -218,6 +507,108 @@ pub async fn revoke_api_key(
     }
 }
 
+/// Response payload of retrieving a secret of an api key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeySecretResponse {
+    /// Id of the secret, used to revoke it.
+    pub id: Uuid,
+    /// Label. Can be absent.
+    pub label: Option<String>,
+    /// Secret encoded in base64 standard alphabet. Only present when the secret is created, since only its
+    /// hash is stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<Base64>,
+    /// Last characters of the secret, to tell the secrets apart.
+    pub secret_hint: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Last time the secret verified a response. Absent if it was never used.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    /// When the secret was revoked. Absent if it's active.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+/// Gets the secrets of an api key for a given site key that belongs to console, including the revoked ones.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+pub async fn get_api_key_secrets(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
+) -> Result<Json<Vec<ApiKeySecretResponse>>, ConsoleError> {
+    let secrets = db::fetch_api_key_secrets(&state.pool, &site_key)
+        .await
+        .with_context(|| {
+            format!("failed to fetch secrets of api key '{site_key}' for console id '{console_id}'")
+        })?
+        .into_iter()
+        .map(ApiKeySecretResponse::from)
+        .collect();
+
+    Ok(Json(secrets))
+}
+
+/// Expected payload for creating a secret of an api key.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CreateApiKeySecretRequest {
+    /// Label, e.g. the name of the backend that uses the secret.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Generates a random secret for an api key for a given site key that belongs to console.
+/// The secret is only returned in this response, so the response isn't traced.
+#[instrument(skip(state), err(Debug, level = Level::ERROR))]
+pub async fn create_api_key_secret(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
+    Json(request): Json<CreateApiKeySecretRequest>,
+) -> Result<Json<ApiKeySecretResponse>, ConsoleError> {
+    let (db_secret, secret) = loop {
+        let secret = Base64::<Standard>::random::<KEY_SIZE>();
+
+        match db::insert_api_key_secret(
+            &state.pool,
+            &state.master_key,
+            &site_key,
+            request.label.as_deref(),
+            &secret,
+        )
+        .await
+        .map_err(ConsoleError::from)
+        {
+            Ok(db_secret) => break (db_secret, secret),
+            Err(ConsoleError::Duplicate) => continue,
+            Err(err) => return Err(err),
+        };
+    };
+    Ok(Json(ApiKeySecretResponse {
+        secret: Some(secret),
+        ..db_secret.into()
+    }))
+}
+
+/// Revokes a secret by id of an api key for a given site key that belongs to console.
+/// The other secrets of the api key keep verifying responses.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+pub async fn revoke_api_key_secret(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key, secret_id)): Path<(Uuid, Base64<UrlSafe>, Uuid)>,
+) -> Result<(), ConsoleError> {
+    match db::revoke_api_key_secret(&state.pool, &site_key, &secret_id)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to revoke secret '{secret_id}' of api key '{site_key}' for console id '{console_id}'"
+            )
+        })? {
+        RowsAffected(0) => Err(ConsoleError::NotFound {
+            what: format!("active secret {secret_id} of sitekey {site_key}"),
+        }),
+        RowsAffected(_) => Ok(()),
+    }
+}
+
 /// Response payload of retrieving challenge preferences.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct ChallengePreferences {
@@ -387,6 +778,151 @@ pub async fn remove_challenge_from_api_key_pool(
     }
 }
 
+/// State of the verification of the ownership of an allowed domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DomainStatus {
+    /// The ownership isn't proven yet, so the domain isn't allowed.
+    Pending,
+    /// The ownership is proven and the domain is allowed.
+    Verified,
+}
+
+/// Response payload of retrieving an allowed domain of an api key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AllowedDomainResponse {
+    pub domain: AllowedDomain,
+    pub status: DomainStatus,
+    /// Token that proves the ownership, served at `well_known_url` or in a TXT record at `txt_record_name`.
+    pub token: Uuid,
+    pub well_known_url: String,
+    pub txt_record_name: String,
+    /// Last time the ownership was checked. Absent if it was never checked.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub checked_at: Option<OffsetDateTime>,
+    /// Last time the ownership was proven. Absent while the domain is pending.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub verified_at: Option<OffsetDateTime>,
+}
+
+/// Response payload of retrieving the allowed domains of an api key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyAllowedDomainsResponse {
+    pub domains: Vec<AllowedDomainResponse>,
+}
+
+/// Gets the allowed domains of an api key, including the pending ones.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+pub async fn get_allowed_domains(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
+) -> Result<Json<ApiKeyAllowedDomainsResponse>, ConsoleError> {
+    let domains = db::fetch_api_key_domains(&state.pool, &site_key)
+        .await
+        .with_context(|| {
+            format!("failed to fetch allowed domains of api key '{site_key}' for console id '{console_id}'")
+        })?
+        .into_iter()
+        .map(AllowedDomainResponse::from)
+        .collect();
+    Ok(Json(ApiKeyAllowedDomainsResponse { domains }))
+}
+
+/// Checks the ownership of the allowed domains of an api key right away, instead of waiting for the background
+/// verifier, and gets them.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+pub async fn verify_allowed_domains(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
+) -> Result<Json<ApiKeyAllowedDomainsResponse>, ConsoleError> {
+    ownership::check_api_key_domains(
+        &state.pool,
+        state.ownership_resolver.as_ref(),
+        &state.domain_verification,
+        &site_key,
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "failed to verify allowed domains of api key '{site_key}' for console id '{console_id}'"
+        )
+    })?;
+    get_allowed_domains(State(state), Path((console_id, site_key))).await
+}
+
+/// Expected payload for add/remove allowed domain routes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AllowedDomainRequest {
+    /// Hostname like `example.com` or wildcard like `*.example.com`. Internationalized names are stored in
+    /// punycode.
+    pub domain: String,
+}
+
+/// Adds an allowed domain to an api key, pending until its ownership is proven with the token of the response.
+/// Adding a domain that is already there returns it unchanged.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+pub async fn add_allowed_domain(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
+    Json(request): Json<AllowedDomainRequest>,
+) -> Result<Json<AllowedDomainResponse>, ConsoleError> {
+    let domain = validate_allowed_domain(&request.domain)
+        .map_err(|what| ConsoleError::InvalidInput { what })?;
+    let db_domain = db::insert_allowed_domain(&state.pool, &site_key, &domain)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to add allowed domain '{domain}' to api key '{site_key}' for console id '{console_id}'"
+            )
+        })?;
+
+    Ok(Json(db_domain.into()))
+}
+
+/// Removes an allowed domain from an api key. The domain isn't checked against the policy, so domains allowed
+/// before it can still be removed.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+pub async fn remove_allowed_domain(
+    State(state): State<Arc<AppState>>,
+    Path((console_id, site_key)): Path<(Uuid, Base64<UrlSafe>)>,
+    Json(request): Json<AllowedDomainRequest>,
+) -> Result<(), ConsoleError> {
+    let domain = AllowedDomain::parse(&request.domain).map_err(|err| {
+        ConsoleError::InvalidInput { what: format!("allowed domain `{}`: {err}", request.domain) }
+    })?;
+    let rows_affected = db::delete_allowed_domain(&state.pool, &site_key, &domain)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to remove allowed domain '{domain}' from api key '{site_key}' for console id '{console_id}'"
+            )
+        })?;
+    match rows_affected {
+        RowsAffected(0) => Err(ConsoleError::NotFound {
+            what: format!("allowed domain '{domain}' of sitekey {site_key}"),
+        }),
+        RowsAffected(_) => Ok(()),
+    }
+}
+
+impl From<DbApiKeyDomain> for AllowedDomainResponse {
+    fn from(d: DbApiKeyDomain) -> Self {
+        let host = d.domain.ownership_host();
+        AllowedDomainResponse {
+            status: match d.verified_at {
+                Some(_) => DomainStatus::Verified,
+                None => DomainStatus::Pending,
+            },
+            token: d.token,
+            well_known_url: format!("https://{host}{}", ownership::WELL_KNOWN_PATH),
+            txt_record_name: format!("{}.{host}", ownership::TXT_RECORD_LABEL),
+            checked_at: d.checked_at,
+            verified_at: d.verified_at,
+            domain: d.domain,
+        }
+    }
+}
+
 impl From<DbConsole> for ConsoleResponse {
     fn from(c: DbConsole) -> Self {
         ConsoleResponse { id: c.id, label: c.label }
@@ -396,10 +932,33 @@ impl From<DbConsole> for ConsoleResponse {
 impl From<DbApiKey> for ApiKeyResponse {
     fn from(k: DbApiKey) -> Self {
         ApiKeyResponse {
+            signing_algorithm: k.signing_algorithm(),
+            encoding_key_version: k.encoding_key_version,
             label: k.label,
             site_key: k.site_key,
-            secret: k.secret,
+            secret: None,
             allowed_domains: k.allowed_domains,
+            pow_difficulty: k.pow_difficulty,
+            pow_algorithm: k.pow_algorithm,
+            min_score: k.min_score,
+            response_ttl_secs: k.response_ttl_secs,
+            pre_analysis_policy: k.pre_analysis_policy,
+            clearance_policy: k.clearance_policy,
+            mode: k.mode,
+        }
+    }
+}
+
+impl From<DbApiKeySecret> for ApiKeySecretResponse {
+    fn from(s: DbApiKeySecret) -> Self {
+        ApiKeySecretResponse {
+            id: s.id,
+            label: s.label,
+            secret: None,
+            secret_hint: s.secret_hint,
+            created_at: s.created_at,
+            last_used_at: s.last_used_at,
+            revoked_at: s.revoked_at,
         }
     }
 }
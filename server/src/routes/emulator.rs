@@ -0,0 +1,297 @@
+//! Emulator of the public API, for the integration tests of the backends that verify the responses. It needs no
+//! database: the api keys only live in memory and every challenge is solved automatically, while the response
+//! tokens and the verification have the same wire format as the server's.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+    routing::{get, post},
+};
+use axum_extra::{TypedHeader, extract::WithRejection, headers::Origin};
+use time::OffsetDateTime;
+use tracing::{Level, instrument};
+use uuid::Uuid;
+
+use super::{
+    challenge::{
+        self, AccessibilityRequest, ChallengeResponse, ChallengeResults, PowResponse,
+        PreAnalysisRequest, PreAnalysisResponse,
+    },
+    errors::{ChallengeError, VerificationError},
+    extractors::{FormOrJson, SiteKey},
+    verification::{self, ErrorCodes, VerificationRequest, VerificationResponse},
+};
+use crate::{
+    analysis::{
+        policy::PreAnalysisPolicy,
+        proof_of_work::{PowAlgorithm, PowChallenge},
+    },
+    configuration::EmulatorKeyConfig,
+    db::DbApiKey,
+    domain::{action::Action, allowed_domain, form_digest::FormDigest, hostname::Hostname},
+    encodings::{Base64, KEY_SIZE, Standard, UrlSafe},
+    tokens::{
+        clearance::ClearancePolicy,
+        pow_challenge,
+        response::{self, ResponseClaims},
+    },
+};
+
+/// Shared state of the emulator.
+#[derive(Debug)]
+pub struct EmulatorState {
+    keys: Vec<EmulatorKey>,
+    /// Response tokens consumed by the verification, with their expiration and idempotency key.
+    consumed_responses: Mutex<HashMap<Uuid, (OffsetDateTime, Option<Uuid>)>>,
+}
+
+/// Api key of the emulator, with a random encoding key.
+#[derive(Debug)]
+struct EmulatorKey {
+    api_key: DbApiKey,
+    secret: Base64,
+    score: f32,
+}
+
+impl EmulatorState {
+    pub fn new(keys: Vec<EmulatorKeyConfig>) -> Self {
+        let keys = keys
+            .into_iter()
+            .map(|key| EmulatorKey {
+                api_key: DbApiKey {
+                    label: None,
+                    site_key: key.site_key,
+                    encoding_key: Base64::<Standard>::random::<KEY_SIZE>(),
+                    encoding_key_version: 1,
+                    previous_encoding_key: None,
+                    allowed_domains: key.allowed_domains,
+                    // the proof of work isn't checked, so it's as cheap as possible for the widget
+                    pow_difficulty: *PowChallenge::DIFFICULTY_RANGE.start(),
+                    pow_algorithm: PowAlgorithm::default(),
+                    min_score: key.min_score,
+                    response_ttl_secs: response::DEFAULT_TTL_SECS,
+                    pre_analysis_policy: PreAnalysisPolicy::default(),
+                    clearance_policy: ClearancePolicy::default(),
+                    signing_key: None,
+                    previous_verifying_key: None,
+                    mode: key.mode,
+                },
+                secret: key.secret,
+                score: key.score,
+            })
+            .collect();
+        Self { keys, consumed_responses: Mutex::default() }
+    }
+
+    fn key_by_site_key(&self, site_key: &Base64<UrlSafe>) -> Result<&EmulatorKey, ChallengeError> {
+        self.keys
+            .iter()
+            .find(|key| &key.api_key.site_key == site_key)
+            .ok_or(ChallengeError::InvalidKey)
+    }
+
+    fn key_by_secret(&self, secret: &Base64) -> Option<&EmulatorKey> {
+        self.keys.iter().find(|key| &key.secret == secret)
+    }
+
+    /// Marks a response token as consumed, like [`crate::db::consume_response_token`].
+    fn consume_response_token(
+        &self,
+        jti: &Uuid,
+        expires_at: &OffsetDateTime,
+        idempotency_key: Option<&Uuid>,
+    ) -> bool {
+        let mut consumed = self
+            .consumed_responses
+            .lock()
+            .expect("consumed responses lock poisoned");
+        let now = OffsetDateTime::now_utc();
+        consumed.retain(|_, (expires_at, _)| *expires_at >= now);
+
+        match consumed.entry(*jti) {
+            Entry::Vacant(entry) => {
+                entry.insert((*expires_at, idempotency_key.copied()));
+                true
+            }
+            Entry::Occupied(entry) => {
+                idempotency_key.is_some() && entry.get().1.as_ref() == idempotency_key
+            }
+        }
+    }
+}
+
+impl EmulatorKey {
+    /// Response of a challenge solved automatically, with the score of the key. Test keys get their canned
+    /// response.
+    fn respond(
+        &self,
+        addr: SocketAddr,
+        hostname: Hostname,
+        action: Option<Action>,
+        form_digest: Option<FormDigest>,
+    ) -> Result<ChallengeResponse, ChallengeError> {
+        if self.api_key.mode.is_test() {
+            return Ok(ChallengeResponse::test_key());
+        }
+        let token = challenge::encode_response(
+            ResponseClaims::new(self.score, addr.ip(), hostname)
+                .with_action(action)
+                .with_form_digest(form_digest),
+            &self.api_key,
+        )
+        .context("failed encoding jwt response")?;
+        Ok(ChallengeResponse { token, clearance: None })
+    }
+}
+
+/// Router for the `/api` endpoints of the emulator.
+pub fn router(state: &Arc<EmulatorState>) -> Router {
+    let state = Arc::clone(state);
+    let challenge = Router::new()
+        .route("/proof-of-work", get(get_proof_of_work_challenge))
+        .merge(
+            Router::new()
+                .route("/process", post(process_challenge))
+                .route("/process-pre-analysis", post(process_pre_analysis))
+                .route(
+                    "/process-accessibility",
+                    post(process_accessibility_challenge),
+                )
+                .layer(axum::middleware::from_fn_with_state(
+                    Arc::clone(&state),
+                    validate_hostname,
+                )),
+        );
+    Router::new()
+        .route("/siteverify", post(site_verify))
+        .nest("/challenge", challenge)
+        .with_state(state)
+}
+
+/// Middleware to validate origin against allowed domains. Test keys accept any origin.
+#[instrument(skip_all, fields(%origin), err(Debug, level = Level::ERROR))]
+async fn validate_hostname(
+    State(state): State<Arc<EmulatorState>>,
+    SiteKey(site_key): SiteKey,
+    TypedHeader(origin): TypedHeader<Origin>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ChallengeError> {
+    let hostname = origin
+        .hostname()
+        .parse()
+        .map_err(|_| ChallengeError::InvalidOrigin)?;
+
+    let api_key = &state.key_by_site_key(&site_key)?.api_key;
+    if !api_key.mode.is_test() && !allowed_domain::is_allowed(&api_key.allowed_domains, &hostname) {
+        return Err(ChallengeError::DomainNotAllowed);
+    }
+    request.extensions_mut().insert(hostname);
+    Ok(next.run(request).await)
+}
+
+/// Issues a proof of work challenge like the server, so the widget works unchanged, but it's never checked.
+#[instrument(skip(state), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+async fn get_proof_of_work_challenge(
+    SiteKey(site_key): SiteKey,
+    State(state): State<Arc<EmulatorState>>,
+) -> Result<Json<PowResponse>, ChallengeError> {
+    let api_key = &state.key_by_site_key(&site_key)?.api_key;
+
+    Ok(Json(PowResponse {
+        token: pow_challenge::encode(
+            PowChallenge::random(api_key.pow_algorithm, api_key.pow_difficulty),
+            api_key.versioned_encoding_key(),
+        )
+        .context("failed encoding jwt response")?,
+    }))
+}
+
+/// Solves the challenge regardless of the results.
+#[instrument(skip(state, results), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+async fn process_challenge(
+    State(state): State<Arc<EmulatorState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    SiteKey(site_key): SiteKey,
+    hostname: Hostname,
+    Json(results): Json<ChallengeResults>,
+) -> Result<Json<ChallengeResponse>, ChallengeError> {
+    let key = state.key_by_site_key(&site_key)?;
+
+    Ok(Json(key.respond(
+        addr,
+        hostname,
+        results.action,
+        results.form_digest,
+    )?))
+}
+
+/// Passes the pre analysis, so the widget never shows a challenge.
+#[instrument(skip(state, request), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+async fn process_pre_analysis(
+    State(state): State<Arc<EmulatorState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    SiteKey(site_key): SiteKey,
+    hostname: Hostname,
+    Json(request): Json<PreAnalysisRequest>,
+) -> Result<Json<PreAnalysisResponse>, ChallengeError> {
+    let key = state.key_by_site_key(&site_key)?;
+
+    Ok(Json(PreAnalysisResponse::Success {
+        response: key.respond(addr, hostname, request.action, request.form_digest)?,
+    }))
+}
+
+/// Passes the accessibility challenge.
+#[instrument(skip(state, request), ret(Debug, level = Level::DEBUG), err(Debug, level = Level::ERROR))]
+async fn process_accessibility_challenge(
+    State(state): State<Arc<EmulatorState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    SiteKey(site_key): SiteKey,
+    hostname: Hostname,
+    Json(request): Json<AccessibilityRequest>,
+) -> Result<Json<PreAnalysisResponse>, ChallengeError> {
+    let key = state.key_by_site_key(&site_key)?;
+
+    Ok(Json(PreAnalysisResponse::Success {
+        response: key.respond(addr, hostname, request.action, request.form_digest)?,
+    }))
+}
+
+/// Verifies the challenge response like [`verification::site_verify`], with the keys in memory.
+#[instrument(skip(state), ret(Debug, level = Level::INFO), err(Debug, level = Level::ERROR))]
+async fn site_verify(
+    State(state): State<Arc<EmulatorState>>,
+    WithRejection(FormOrJson(verification), _): WithRejection<
+        FormOrJson<HashMap<String, String>>,
+        VerificationError,
+    >,
+) -> Result<Json<VerificationResponse>, VerificationError> {
+    let verification: Result<VerificationRequest, Vec<ErrorCodes>> = verification.try_into();
+    let verification = verification.map_err(VerificationResponse::failure)?;
+
+    let key = state
+        .key_by_secret(verification.secret())
+        .ok_or(VerificationResponse::failure(vec![
+            ErrorCodes::InvalidInputSecret,
+        ]))?;
+
+    verification::verify_with_api_key(
+        &key.api_key,
+        verification,
+        async |jti, expires_at, idempotency_key| {
+            Ok(state.consume_response_token(jti, expires_at, idempotency_key))
+        },
+    )
+    .await
+    .map(Json)
+}
@@ -1,6 +1,5 @@
 use axum::{
     Json,
-    extract::rejection::FormRejection,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
@@ -9,7 +8,10 @@ use thiserror::Error;
 
 use crate::db::{self, ConstraintKind};
 
-use super::verification::{ErrorCodes, VerificationResponse};
+use super::{
+    extractors::FormOrJsonRejection,
+    verification::{ErrorCodes, VerificationResponse},
+};
 
 /// Errors regarding challenge operations.
 #[derive(Debug, Error)]
@@ -26,6 +28,15 @@ pub enum ChallengeError {
     /// Failed proof of work challenge.
     #[error("Failed proof of work challenge")]
     FailedProofOfWork,
+    /// Proof of work challenge already redeemed.
+    #[error("Proof of work challenge already used")]
+    ReplayedProofOfWork,
+    /// Invalid puzzle instance.
+    #[error("Invalid puzzle")]
+    InvalidPuzzle(#[source] jsonwebtoken::errors::Error),
+    /// Puzzle already answered.
+    #[error("Puzzle already answered")]
+    ReplayedPuzzle,
     /// No matching challenge.
     #[error("No matching challenge")]
     NoMatchingChallenge,
@@ -55,6 +66,15 @@ impl IntoResponse for ChallengeError {
             ChallengeError::FailedProofOfWork => {
                 (StatusCode::BAD_REQUEST, self.to_string()).into_response()
             }
+            ChallengeError::ReplayedProofOfWork => {
+                (StatusCode::CONFLICT, self.to_string()).into_response()
+            }
+            ChallengeError::InvalidPuzzle(_) => {
+                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
+            ChallengeError::ReplayedPuzzle => {
+                (StatusCode::CONFLICT, self.to_string()).into_response()
+            }
             ChallengeError::NoMatchingChallenge => {
                 (StatusCode::NOT_FOUND, self.to_string()).into_response()
             }
@@ -204,7 +224,10 @@ pub enum VerificationError {
     UserError(#[from] VerificationResponse),
     /// Bad request.
     #[error(transparent)]
-    BadRequest(#[from] FormRejection),
+    BadRequest(#[from] FormOrJsonRejection),
+    /// Unknown site key.
+    #[error("Unknown site key")]
+    UnknownSiteKey,
     /// Unexpected error.
     #[error(transparent)]
     Unexpected(#[from] anyhow::Error),
@@ -223,6 +246,9 @@ impl IntoResponse for VerificationError {
             VerificationError::BadRequest(_) => {
                 Json(VerificationResponse::failure(vec![ErrorCodes::BadRequest])).into_response()
             }
+            VerificationError::UnknownSiteKey => {
+                (StatusCode::NOT_FOUND, self.to_string()).into_response()
+            }
         }
     }
 }
@@ -3,10 +3,17 @@ use std::sync::Arc;
 #[cfg(feature = "aws-lambda")]
 use axum::http::Request;
 use axum::{
-    extract::{FromRequestParts, OptionalFromRequestParts},
-    http::{StatusCode, request::Parts},
+    Form, Json,
+    extract::{
+        FromRequest, FromRequestParts, OptionalFromRequestParts,
+        rejection::{FormRejection, JsonRejection},
+    },
+    http::{StatusCode, header::CONTENT_TYPE, request::Parts},
+    response::{IntoResponse, Response},
 };
 use axum_extra::{TypedHeader, typed_header::TypedHeaderRejection};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
 
 use crate::{
     domain::hostname::Hostname,
@@ -162,3 +169,46 @@ where
             .ok_or(StatusCode::BAD_REQUEST)
     }
 }
+
+/// Extracts the payload from a JSON body when the content type is `application/json`, or from a form otherwise.
+#[derive(Debug, Clone)]
+pub struct FormOrJson<T>(pub T);
+
+/// Rejection of the [`FormOrJson`] extractor.
+#[derive(Debug, Error)]
+pub enum FormOrJsonRejection {
+    #[error(transparent)]
+    Form(#[from] FormRejection),
+    #[error(transparent)]
+    Json(#[from] JsonRejection),
+}
+
+impl IntoResponse for FormOrJsonRejection {
+    fn into_response(self) -> Response {
+        match self {
+            FormOrJsonRejection::Form(rejection) => rejection.into_response(),
+            FormOrJsonRejection::Json(rejection) => rejection.into_response(),
+        }
+    }
+}
+
+impl<T, S> FromRequest<S> for FormOrJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = FormOrJsonRejection;
+
+    async fn from_request(req: axum::extract::Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("application/json"));
+
+        match is_json {
+            true => Ok(FormOrJson(Json::from_request(req, state).await?.0)),
+            false => Ok(FormOrJson(Form::from_request(req, state).await?.0)),
+        }
+    }
+}
@@ -194,7 +194,7 @@ pub async fn block_bot_agent(
     }
 }
 
-/// Middleware to validate origin against allowed domains.
+/// Middleware to validate origin against allowed domains. Test keys accept any origin.
 #[instrument(skip_all, fields(%origin), err(Debug, level = Level::ERROR))]
 pub async fn validate_hostname(
     State(state): State<Arc<AppState>>,
@@ -208,11 +208,11 @@ pub async fn validate_hostname(
         .parse()
         .map_err(|_| ChallengeError::InvalidOrigin)?;
 
-    let is_allowed_domain = db::exists_allowed_domain_in_api_key(&state.pool, &site_key, &hostname)
+    let allowed_domains = db::fetch_allowed_domains(&state.pool, &site_key)
         .await?
         .ok_or(ChallengeError::InvalidKey)?;
 
-    if !is_allowed_domain {
+    if !allowed_domains.allows(&hostname) {
         return Err(ChallengeError::DomainNotAllowed);
     }
     request.extensions_mut().insert(hostname);
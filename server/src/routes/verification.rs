@@ -1,18 +1,44 @@
 use std::{collections::HashMap, fmt::Display, net::IpAddr, str::FromStr, sync::Arc};
 
 use anyhow::Context;
-use axum::{Form, Json, extract::State};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
 use axum_extra::extract::WithRejection;
-use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{errors::ErrorKind, jwk::JwkSet};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use time::OffsetDateTime;
 use tracing::{Level, instrument};
+use uuid::Uuid;
 
-use crate::{AppState, db, domain::hostname::Hostname, encodings::Base64, tokens::response};
+use crate::{
+    AppState,
+    db::{self, DbApiKey},
+    domain::{
+        action::Action, allowed_domain, api_key_mode::ApiKeyMode, form_digest::FormDigest,
+        hostname::Hostname,
+    },
+    encodings::{Base64, UrlSafe},
+    tokens::{response, signing::VerifyingKey},
+};
 
-use super::errors::VerificationError;
+use super::{errors::VerificationError, extractors::FormOrJson};
+
+pub mod hcaptcha;
+pub mod recaptcha;
+pub mod turnstile;
+
+/// Minimum score to pass the verification, unless configured otherwise for the api key.
+pub const DEFAULT_MIN_SCORE: f32 = 0.5;
+
+/// Fields that can carry the response token. Besides `response`, the name of the widget's form field is accepted
+/// so backends can forward the submitted form field as is.
+const RESPONSE_FIELDS: [&str; 3] = ["response", "g-recaptcha-response", "gotcha-response"];
 
 /// Verification request payload.
 #[derive(Debug)]
@@ -20,18 +46,35 @@ pub struct VerificationRequest {
     secret: Secret<Base64>,
     response: String,
     remoteip: Option<IpAddr>,
+    expected_action: Option<Action>,
+    expected_form_digest: Option<FormDigest>,
+    site_key: Option<Base64<UrlSafe>>,
+    idempotency_key: Option<Uuid>,
 }
 
 /// Verification response payload.
 #[derive(Debug, Serialize, Deserialize, Error)]
 pub struct VerificationResponse {
     pub success: bool,
+    /// Score of the response, so the backend can apply its own threshold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
     #[serde(with = "time::serde::iso8601")]
     pub challenge_ts: OffsetDateTime,
     #[serde(with = "crate::serde::none_as_empty_string")]
     pub hostname: Option<Hostname>,
+    /// Action the response token was bound to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
     #[serde(rename = "error-codes", skip_serializing_if = "Option::is_none")]
     pub error_codes: Option<Vec<ErrorCodes>>,
+    /// Warnings about the verification that don't change its outcome.
+    #[serde(
+        rename = "warning-codes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub warning_codes: Option<Vec<WarningCodes>>,
 }
 
 /// Error codes for verification response.
@@ -50,40 +93,177 @@ pub enum ErrorCodes {
     BadRequest,
     /// Timeout or duplicate.
     TimeoutOrDuplicate,
+    /// The action of the response doesn't match the expected action.
+    ActionMismatch,
+    /// The form data of the response doesn't match the submitted form data.
+    FormDataMismatch,
+    /// The site key doesn't belong to the secret.
+    SitekeySecretMismatch,
+}
+
+impl ErrorCodes {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCodes::MissingInputSecret => "missing-input-secret",
+            ErrorCodes::InvalidInputSecret => "invalid-input-secret",
+            ErrorCodes::MissingInputResponse => "missing-input-response",
+            ErrorCodes::InvalidInputResponse => "invalid-input-response",
+            ErrorCodes::BadRequest => "bad-request",
+            ErrorCodes::TimeoutOrDuplicate => "timeout-or-duplicate",
+            ErrorCodes::ActionMismatch => "action-mismatch",
+            ErrorCodes::FormDataMismatch => "form-data-mismatch",
+            ErrorCodes::SitekeySecretMismatch => "sitekey-secret-mismatch",
+        }
+    }
+}
+
+/// Warning codes for verification response.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningCodes {
+    /// The api key is a test key, whose verification always passes or always fails.
+    TestKey,
+}
+
+impl WarningCodes {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningCodes::TestKey => "test-key",
+        }
+    }
+}
+
+/// Response payload of a verification dialect, that mimics the verification of another vendor.
+pub trait DialectResponse: From<VerificationResponse> {
+    /// Failed verification that only carries the error codes.
+    fn failure(error_codes: Vec<ErrorCodes>) -> Self;
+}
+
+/// Error codes in the vocabulary of a dialect, without the repeated ones since several of ours can map to the
+/// same code of the vendor.
+fn dialect_error_codes<'a>(
+    error_codes: impl IntoIterator<Item = &'a ErrorCodes>,
+    error_code: fn(&ErrorCodes) -> &'static str,
+) -> Vec<&'static str> {
+    let mut codes: Vec<_> = error_codes.into_iter().map(error_code).collect();
+    codes.dedup();
+    codes
 }
 
-/// Verifies the challenge response.
+/// Verifies the challenge response. Each response token can only be verified once.
+/// It's successful if the score reaches the minimum score of the api key and, when an `expected_action`
+/// is given, the response was bound to that action. Likewise, when the submitted `form_data` or its
+/// `form_digest` is given, the response must have been bound to the same form data.
+///
+/// The payload can be sent as a form or as JSON. When the `sitekey` is given it must belong to the secret, and
+/// when an `idempotency_key` is given the verification of the same response token can be retried with it.
+///
+/// Test keys skip the checks and always pass or always fail, with the `test-key` warning code.
 #[instrument(skip(state), ret(Debug, level = Level::INFO), err(Debug, level = Level::ERROR))]
 pub async fn site_verify(
     State(state): State<Arc<AppState>>,
-    WithRejection(Form(verification), _): WithRejection<
-        Form<HashMap<String, String>>,
+    WithRejection(FormOrJson(verification), _): WithRejection<
+        FormOrJson<HashMap<String, String>>,
         VerificationError,
     >,
 ) -> Result<Json<VerificationResponse>, VerificationError> {
     let verification: Result<VerificationRequest, Vec<ErrorCodes>> = verification.try_into();
     let verification = verification.map_err(VerificationResponse::failure)?;
 
-    let api_key = db::fetch_api_key_by_secret(&state.pool, verification.secret.expose_secret())
-        .await
-        .context("failed to fetch encoding key bey api secret while verifying challenge")?
-        .ok_or(VerificationResponse::failure(vec![
-            ErrorCodes::InvalidInputSecret,
-        ]))?;
-
-    let claims = response::decode(&verification.response, &api_key.encoding_key)
-        .map_err(|err| match err.into_kind() {
-            ErrorKind::ExpiredSignature => ErrorCodes::TimeoutOrDuplicate,
-            _ => ErrorCodes::InvalidInputResponse,
-        })
-        .map_err(|err_code| VerificationResponse::failure(vec![err_code]))?;
+    verify(&state, verification).await.map(Json)
+}
+
+/// Verifies the payload of a dialect endpoint. Every failure is reported with error codes in the dialect's
+/// response, except unexpected errors.
+pub async fn dialect_verify<R: DialectResponse>(
+    state: &AppState,
+    verification: Option<HashMap<String, String>>,
+) -> Result<R, VerificationError> {
+    let Some(verification) = verification else {
+        return Ok(R::failure(vec![ErrorCodes::BadRequest]));
+    };
+    let verification: VerificationRequest = match verification.try_into() {
+        Ok(verification) => verification,
+        Err(error_codes) => return Ok(R::failure(error_codes)),
+    };
+
+    match verify(state, verification).await {
+        Ok(verification) => Ok(verification.into()),
+        Err(VerificationError::UserError(verification)) => {
+            Ok(R::failure(verification.error_codes.unwrap_or_default()))
+        }
+        Err(VerificationError::BadRequest(_)) => Ok(R::failure(vec![ErrorCodes::BadRequest])),
+        Err(err) => Err(err),
+    }
+}
+
+/// Verifies the challenge response, shared by the verification endpoints of every dialect.
+/// Failures that only have error codes to report are returned as [`VerificationError::UserError`].
+pub async fn verify(
+    state: &AppState,
+    verification: VerificationRequest,
+) -> Result<VerificationResponse, VerificationError> {
+    let api_key = db::fetch_api_key_by_secret(
+        &state.pool,
+        &state.master_key,
+        verification.secret.expose_secret(),
+    )
+    .await
+    .context("failed to fetch encoding key bey api secret while verifying challenge")?
+    .ok_or(VerificationResponse::failure(vec![
+        ErrorCodes::InvalidInputSecret,
+    ]))?;
+
+    verify_with_api_key(
+        &api_key,
+        verification,
+        async |jti, expires_at, idempotency_key| {
+            db::consume_response_token(&state.pool, jti, expires_at, idempotency_key)
+                .await
+                .context("failed to consume response token while verifying challenge")
+        },
+    )
+    .await
+}
+
+/// Verifies the challenge response with the api key of its secret. The response token is marked as consumed by
+/// `consume_response_token`, which returns `false` if it was already consumed, so it can't be verified twice.
+/// A token that doesn't match the expected hostname, action or form data is rejected without being consumed.
+pub(crate) async fn verify_with_api_key(
+    api_key: &DbApiKey,
+    verification: VerificationRequest,
+    consume_response_token: impl AsyncFnOnce(
+        &Uuid,
+        &OffsetDateTime,
+        Option<&Uuid>,
+    ) -> anyhow::Result<bool>,
+) -> Result<VerificationResponse, VerificationError> {
+    if verification
+        .site_key
+        .is_some_and(|site_key| site_key != api_key.site_key)
+    {
+        return Err(VerificationResponse::failure(vec![ErrorCodes::SitekeySecretMismatch]).into());
+    }
+
+    match api_key.mode {
+        ApiKeyMode::Live => {}
+        ApiKeyMode::TestPass => return Ok(VerificationResponse::test_key(true)),
+        ApiKeyMode::TestFail => return Err(VerificationResponse::test_key(false).into()),
+    }
 
-    let is_allowed_domain = api_key
-        .allowed_domains
-        .iter()
-        .any(|domain| domain == &claims.other.host);
+    let claims = response::decode_with_key_pair(
+        &verification.response,
+        api_key.decoding_keys(),
+        api_key.verifying_keys(),
+    )
+    .map_err(|err| match err.into_kind() {
+        ErrorKind::ExpiredSignature => ErrorCodes::TimeoutOrDuplicate,
+        _ => ErrorCodes::InvalidInputResponse,
+    })
+    .map_err(|err_code| VerificationResponse::failure(vec![err_code]))?;
 
-    if !is_allowed_domain {
+    // mismatches are reported before consuming the token, so they don't burn it
+    if !allowed_domain::is_allowed(&api_key.allowed_domains, &claims.other.host) {
         return Err(VerificationResponse::failure(vec![ErrorCodes::TimeoutOrDuplicate]).into());
     }
 
@@ -91,23 +271,99 @@ pub async fn site_verify(
         .remoteip
         .is_none_or(|solver| solver == claims.other.addr);
 
-    Ok(Json(VerificationResponse {
-        success: claims.other.score >= 0.5 && solver_check,
+    let action_check = verification
+        .expected_action
+        .is_none_or(|expected| claims.other.action.as_ref() == Some(&expected));
+    let form_digest_check = verification
+        .expected_form_digest
+        .is_none_or(|expected| claims.other.form_digest.as_ref() == Some(&expected));
+
+    let error_codes: Vec<_> = [
+        (!action_check).then_some(ErrorCodes::ActionMismatch),
+        (!form_digest_check).then_some(ErrorCodes::FormDataMismatch),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if error_codes.is_empty() {
+        let first_use = consume_response_token(
+            &claims.other.jti,
+            claims.exp(),
+            verification.idempotency_key.as_ref(),
+        )
+        .await?;
+        if !first_use {
+            return Err(VerificationResponse::failure(vec![ErrorCodes::TimeoutOrDuplicate]).into());
+        }
+    }
+
+    Ok(VerificationResponse {
+        success: claims.other.score >= api_key.min_score && solver_check && error_codes.is_empty(),
+        score: Some(claims.other.score),
         challenge_ts: *claims.iat(),
         hostname: Some(claims.other.host),
-        error_codes: None,
-    }))
+        action: claims.other.action,
+        error_codes: (!error_codes.is_empty()).then_some(error_codes),
+        warning_codes: None,
+    })
+}
+
+/// Gets the public keys that sign the response tokens of the site key given in the path, so the tokens can be
+/// verified offline. Keys that sign with HS256 have no public keys, besides the previous one of a change of
+/// signing algorithm during its grace period.
+#[instrument(skip(state), err(Debug, level = Level::ERROR))]
+pub async fn get_jwks(
+    State(state): State<Arc<AppState>>,
+    Path(site_key): Path<Base64<UrlSafe>>,
+) -> Result<impl IntoResponse, VerificationError> {
+    let api_key = db::fetch_api_key_by_site_key(&state.pool, &state.master_key, &site_key)
+        .await?
+        .ok_or(VerificationError::UnknownSiteKey)?;
+
+    let keys = api_key.verifying_keys().map(VerifyingKey::jwk).collect();
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(JwkSet { keys }),
+    ))
 }
 
 impl VerificationResponse {
     pub fn failure(errors: Vec<ErrorCodes>) -> Self {
         Self {
             success: false,
+            score: None,
             challenge_ts: OffsetDateTime::UNIX_EPOCH,
             hostname: None,
+            action: None,
             error_codes: Some(errors),
+            warning_codes: None,
         }
     }
+
+    /// Deterministic verification of a test key, regardless of the response token, warning that it's a test key.
+    pub fn test_key(success: bool) -> Self {
+        let verification = match success {
+            true => Self {
+                success: true,
+                score: Some(1.),
+                challenge_ts: OffsetDateTime::now_utc(),
+                hostname: None,
+                action: None,
+                error_codes: None,
+                warning_codes: None,
+            },
+            false => Self::failure(vec![ErrorCodes::InvalidInputResponse]),
+        };
+        Self { warning_codes: Some(vec![WarningCodes::TestKey]), ..verification }
+    }
+}
+
+impl VerificationRequest {
+    /// Secret of the api key the response is verified with.
+    pub fn secret(&self) -> &Base64 {
+        self.secret.expose_secret()
+    }
 }
 
 impl TryFrom<HashMap<String, String>> for VerificationRequest {
@@ -126,7 +382,8 @@ impl TryFrom<HashMap<String, String>> for VerificationRequest {
             }
             Some(Ok(secret)) => Some(secret),
         };
-        if !form.contains_key("response") {
+        let response = RESPONSE_FIELDS.iter().find_map(|field| form.remove(*field));
+        if response.is_none() {
             errors.push(ErrorCodes::MissingInputResponse);
         }
         let remoteip = match form.remove("remoteip").as_deref().map(IpAddr::from_str) {
@@ -137,16 +394,61 @@ impl TryFrom<HashMap<String, String>> for VerificationRequest {
             }
             Some(Ok(r)) => Some(r),
         };
+        let expected_action = match form.remove("expected_action").as_deref().map(Action::parse) {
+            None => None,
+            Some(Err(_)) => {
+                errors.push(ErrorCodes::BadRequest);
+                None
+            }
+            Some(Ok(action)) => Some(action),
+        };
+        let expected_form_digest = match (form.remove("form_data"), form.remove("form_digest")) {
+            (None, None) => None,
+            (Some(form_data), None) => Some(FormDigest::of_urlencoded(&form_data)),
+            (None, Some(form_digest)) => match FormDigest::parse(&form_digest) {
+                Ok(form_digest) => Some(form_digest),
+                Err(_) => {
+                    errors.push(ErrorCodes::BadRequest);
+                    None
+                }
+            },
+            (Some(_), Some(_)) => {
+                errors.push(ErrorCodes::BadRequest);
+                None
+            }
+        };
+        let site_key = match form.remove("sitekey").map(Base64::<UrlSafe>::try_from) {
+            None => None,
+            Some(Err(_)) => {
+                errors.push(ErrorCodes::BadRequest);
+                None
+            }
+            Some(Ok(site_key)) => Some(site_key),
+        };
+        let idempotency_key = match form
+            .remove("idempotency_key")
+            .as_deref()
+            .map(Uuid::parse_str)
+        {
+            None => None,
+            Some(Err(_)) => {
+                errors.push(ErrorCodes::BadRequest);
+                None
+            }
+            Some(Ok(idempotency_key)) => Some(idempotency_key),
+        };
         if !errors.is_empty() {
             return Err(errors);
         }
 
         Ok(VerificationRequest {
             secret: Secret::new(secret_b64.expect("validated before")),
-            response: form
-                .remove("response")
-                .expect("checked if it contains key before"),
+            response: response.expect("validated before"),
             remoteip,
+            expected_action,
+            expected_form_digest,
+            site_key,
+            idempotency_key,
         })
     }
 }
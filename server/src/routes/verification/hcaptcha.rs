@@ -0,0 +1,83 @@
+//! hCaptcha compatible verification, so existing server libraries work by only changing the verification url.
+//! The request is the same as [`site_verify`](super::site_verify), while the response has the fields and the
+//! error codes of hCaptcha's `siteverify`. The score is left out, since hCaptcha's score grows with the risk.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{Json, extract::State};
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::{Level, instrument};
+
+use crate::{
+    AppState,
+    domain::hostname::Hostname,
+    routes::{
+        errors::VerificationError,
+        extractors::{FormOrJson, FormOrJsonRejection},
+    },
+};
+
+use super::{
+    DialectResponse, ErrorCodes, VerificationResponse, dialect_error_codes, dialect_verify,
+};
+
+/// hCaptcha verification response payload.
+#[derive(Debug, Serialize)]
+pub struct HcaptchaVerificationResponse {
+    pub success: bool,
+    #[serde(
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub challenge_ts: Option<OffsetDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<Hostname>,
+    #[serde(rename = "error-codes", skip_serializing_if = "Vec::is_empty")]
+    pub error_codes: Vec<&'static str>,
+}
+
+/// hCaptcha's error code of each [`ErrorCodes`]. hCaptcha has no codes for the action and form data
+/// mismatches, which it reports as an invalid response.
+pub fn error_code(error_code: &ErrorCodes) -> &'static str {
+    match error_code {
+        ErrorCodes::TimeoutOrDuplicate => "invalid-or-already-seen-response",
+        ErrorCodes::ActionMismatch | ErrorCodes::FormDataMismatch => "invalid-input-response",
+        ErrorCodes::SitekeySecretMismatch => "sitekey-secret-mismatch",
+        other => other.as_str(),
+    }
+}
+
+impl DialectResponse for HcaptchaVerificationResponse {
+    fn failure(error_codes: Vec<ErrorCodes>) -> Self {
+        Self {
+            success: false,
+            challenge_ts: None,
+            hostname: None,
+            error_codes: dialect_error_codes(&error_codes, error_code),
+        }
+    }
+}
+
+impl From<VerificationResponse> for HcaptchaVerificationResponse {
+    fn from(verification: VerificationResponse) -> Self {
+        Self {
+            success: verification.success,
+            challenge_ts: Some(verification.challenge_ts),
+            hostname: verification.hostname,
+            error_codes: dialect_error_codes(verification.error_codes.iter().flatten(), error_code),
+        }
+    }
+}
+
+/// Verifies the challenge response at hCaptcha's `siteverify` path.
+#[instrument(skip(state), ret(Debug, level = Level::INFO), err(Debug, level = Level::ERROR))]
+pub async fn hcaptcha_site_verify(
+    State(state): State<Arc<AppState>>,
+    verification: Result<FormOrJson<HashMap<String, String>>, FormOrJsonRejection>,
+) -> Result<Json<HcaptchaVerificationResponse>, VerificationError> {
+    let verification = verification
+        .ok()
+        .map(|FormOrJson(verification)| verification);
+    dialect_verify(&state, verification).await.map(Json)
+}
@@ -0,0 +1,89 @@
+//! reCAPTCHA compatible verification, so existing server libraries work by only changing the base url.
+//! The request is the same as [`site_verify`](super::site_verify), while the response has the exact
+//! fields of reCAPTCHA's `siteverify`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Form, Json,
+    extract::{State, rejection::FormRejection},
+};
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::{Level, instrument};
+
+use crate::{
+    AppState,
+    domain::{action::Action, hostname::Hostname},
+    routes::errors::VerificationError,
+};
+
+use super::{
+    DialectResponse, ErrorCodes, VerificationResponse, dialect_error_codes, dialect_verify,
+};
+
+/// reCAPTCHA verification response payload.
+#[derive(Debug, Serialize)]
+pub struct RecaptchaVerificationResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+    #[serde(
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub challenge_ts: Option<OffsetDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<Hostname>,
+    #[serde(rename = "error-codes", skip_serializing_if = "Vec::is_empty")]
+    pub error_codes: Vec<&'static str>,
+}
+
+/// reCAPTCHA's error code of each [`ErrorCodes`]. reCAPTCHA has no codes for the mismatches, which it
+/// reports as an invalid response, or an invalid request for the site key.
+pub fn error_code(error_code: &ErrorCodes) -> &'static str {
+    match error_code {
+        ErrorCodes::ActionMismatch | ErrorCodes::FormDataMismatch => "invalid-input-response",
+        ErrorCodes::SitekeySecretMismatch => "bad-request",
+        other => other.as_str(),
+    }
+}
+
+impl DialectResponse for RecaptchaVerificationResponse {
+    /// Failed verification, which only carries the error codes like reCAPTCHA does.
+    fn failure(error_codes: Vec<ErrorCodes>) -> Self {
+        Self {
+            success: false,
+            score: None,
+            action: None,
+            challenge_ts: None,
+            hostname: None,
+            error_codes: dialect_error_codes(&error_codes, error_code),
+        }
+    }
+}
+
+impl From<VerificationResponse> for RecaptchaVerificationResponse {
+    fn from(verification: VerificationResponse) -> Self {
+        Self {
+            success: verification.success,
+            score: verification.score,
+            action: verification.action,
+            challenge_ts: Some(verification.challenge_ts),
+            hostname: verification.hostname,
+            error_codes: dialect_error_codes(verification.error_codes.iter().flatten(), error_code),
+        }
+    }
+}
+
+/// Verifies the challenge response at reCAPTCHA's `siteverify` path.
+#[instrument(skip(state), ret(Debug, level = Level::INFO), err(Debug, level = Level::ERROR))]
+pub async fn recaptcha_site_verify(
+    State(state): State<Arc<AppState>>,
+    verification: Result<Form<HashMap<String, String>>, FormRejection>,
+) -> Result<Json<RecaptchaVerificationResponse>, VerificationError> {
+    let verification = verification.ok().map(|Form(verification)| verification);
+    dialect_verify(&state, verification).await.map(Json)
+}
@@ -0,0 +1,88 @@
+//! Cloudflare Turnstile compatible verification, so existing server libraries work by only changing the
+//! verification url. The request is the same as [`site_verify`](super::site_verify), including the
+//! `idempotency_key` to retry a verification, while the response has the fields and the error codes of
+//! Turnstile's `siteverify`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{Json, extract::State};
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::{Level, instrument};
+
+use crate::{
+    AppState,
+    domain::{action::Action, hostname::Hostname},
+    routes::{
+        errors::VerificationError,
+        extractors::{FormOrJson, FormOrJsonRejection},
+    },
+};
+
+use super::{
+    DialectResponse, ErrorCodes, VerificationResponse, dialect_error_codes, dialect_verify,
+};
+
+/// Turnstile verification response payload.
+#[derive(Debug, Serialize)]
+pub struct TurnstileVerificationResponse {
+    pub success: bool,
+    #[serde(
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub challenge_ts: Option<OffsetDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<Hostname>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+    /// Always present, empty on success.
+    #[serde(rename = "error-codes")]
+    pub error_codes: Vec<&'static str>,
+}
+
+/// Turnstile's error code of each [`ErrorCodes`]. Turnstile has no codes for the mismatches, which it
+/// reports as an invalid response, or an invalid secret for the site key.
+pub fn error_code(error_code: &ErrorCodes) -> &'static str {
+    match error_code {
+        ErrorCodes::ActionMismatch | ErrorCodes::FormDataMismatch => "invalid-input-response",
+        ErrorCodes::SitekeySecretMismatch => "invalid-input-secret",
+        other => other.as_str(),
+    }
+}
+
+impl DialectResponse for TurnstileVerificationResponse {
+    fn failure(error_codes: Vec<ErrorCodes>) -> Self {
+        Self {
+            success: false,
+            challenge_ts: None,
+            hostname: None,
+            action: None,
+            error_codes: dialect_error_codes(&error_codes, error_code),
+        }
+    }
+}
+
+impl From<VerificationResponse> for TurnstileVerificationResponse {
+    fn from(verification: VerificationResponse) -> Self {
+        Self {
+            success: verification.success,
+            challenge_ts: Some(verification.challenge_ts),
+            hostname: verification.hostname,
+            action: verification.action,
+            error_codes: dialect_error_codes(verification.error_codes.iter().flatten(), error_code),
+        }
+    }
+}
+
+/// Verifies the challenge response at Turnstile's `siteverify` path.
+#[instrument(skip(state), ret(Debug, level = Level::INFO), err(Debug, level = Level::ERROR))]
+pub async fn turnstile_site_verify(
+    State(state): State<Arc<AppState>>,
+    verification: Result<FormOrJson<HashMap<String, String>>, FormOrJsonRejection>,
+) -> Result<Json<TurnstileVerificationResponse>, VerificationError> {
+    let verification = verification
+        .ok()
+        .map(|FormOrJson(verification)| verification);
+    dialect_verify(&state, verification).await.map(Json)
+}
@@ -1,17 +1,27 @@
-use std::{future::Future, net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
 use tokio::sync::{OnceCell, oneshot::Sender};
 use uuid::Uuid;
 
 use crate::{
-    HTTP_CLIENT, app, configuration,
-    db::{self, DbChallenge, DbUpdateApiKey},
-    domain::hostname::Hostname,
+    HTTP_CLIENT,
+    analysis::{interaction::Interaction, puzzle::PuzzleKind},
+    app, configuration,
+    crypto::MasterKey,
+    db::{self, DbChallenge},
+    domain::allowed_domain::AllowedDomain,
     encodings::{Base64, KEY_SIZE, Standard, UrlSafe},
     get_configuration,
+    ownership::{self, BoxFuture, OwnershipResolver},
 };
 
 const DEMO_CONSOLE_LABEL_PREFIX: &str = "console_for_integration_tests";
@@ -28,6 +38,9 @@ struct InnerContext {
     addr: SocketAddr,
     shutdown_signal: Sender<()>,
     pool: PgPool,
+    master_key: MasterKey,
+    api_secret: Base64,
+    ownership_resolver: Arc<StandInResolver>,
 }
 
 pub async fn with_test_context<F, Fut, R>(test: F) -> R
@@ -58,21 +71,35 @@ impl TestContext {
         let (shutdown_signal, shutdown_receiver) = tokio::sync::oneshot::channel();
 
         let pool = db::connect_database(db_conf);
+        let master_key = app_conf.master_key.clone();
         let test_id = Uuid::new_v4();
-        populate_demo(&pool, &test_id).await?;
+        let api_secret = populate_demo(&pool, &master_key, &test_id).await?;
 
         let app_pool = pool.clone();
+        let ownership_resolver = Arc::new(StandInResolver::default());
+        let app_resolver = Arc::clone(&ownership_resolver) as Arc<dyn OwnershipResolver>;
         let _join_handle = tokio::spawn(async move {
             axum::serve(
                 listener,
-                app(app_conf, app_pool).into_make_service_with_connect_info::<SocketAddr>(),
+                app(app_conf, app_pool, app_resolver)
+                    .into_make_service_with_connect_info::<SocketAddr>(),
             )
             .with_graceful_shutdown(async move { shutdown_receiver.await.unwrap() })
             .await
             .unwrap();
         });
 
-        Ok(Self { inner: Arc::new(InnerContext { test_id, addr, shutdown_signal, pool }) })
+        Ok(Self {
+            inner: Arc::new(InnerContext {
+                test_id,
+                addr,
+                shutdown_signal,
+                pool,
+                master_key,
+                api_secret,
+                ownership_resolver,
+            }),
+        })
     }
 
     pub async fn teardown(self) -> anyhow::Result<()> {
@@ -96,6 +123,28 @@ impl TestContext {
         &self.inner.pool
     }
 
+    pub fn master_key(&self) -> &MasterKey {
+        &self.inner.master_key
+    }
+
+    /// Stand-in resolver of the proofs of ownership of the domains, shared with the server.
+    pub fn ownership_resolver(&self) -> &StandInResolver {
+        &self.inner.ownership_resolver
+    }
+
+    /// Checks the ownership of the domains of the api key created on setup with the stand-in resolver,
+    /// returning the number of domains verified.
+    pub async fn check_api_key_domains(&self) -> usize {
+        ownership::check_api_key_domains(
+            &self.inner.pool,
+            self.ownership_resolver(),
+            &Default::default(),
+            &self.db_api_site_key().await,
+        )
+        .await
+        .expect("failed to check the ownership of the domains")
+    }
+
     pub async fn db_console(&self) -> Uuid {
         db::fetch_console_by_label(
             &self.inner.pool,
@@ -107,27 +156,32 @@ impl TestContext {
     }
 
     pub async fn db_api_site_key(&self) -> Base64<UrlSafe> {
-        db::fetch_api_keys(&self.inner.pool, &self.db_console().await)
-            .await
-            .unwrap()
-            .swap_remove(0)
-            .site_key
+        db::fetch_api_keys(
+            &self.inner.pool,
+            &self.inner.master_key,
+            &self.db_console().await,
+        )
+        .await
+        .unwrap()
+        .swap_remove(0)
+        .site_key
     }
 
+    /// Secret of the api key created on setup, which is only stored hashed.
     pub async fn db_api_secret(&self) -> Base64 {
-        db::fetch_api_keys(&self.inner.pool, &self.db_console().await)
-            .await
-            .unwrap()
-            .swap_remove(0)
-            .secret
+        self.inner.api_secret.clone()
     }
 
     pub async fn db_enconding_key(&self) -> Base64 {
-        db::fetch_api_key_by_site_key(&self.inner.pool, &self.db_api_site_key().await)
-            .await
-            .unwrap()
-            .expect("expected a encoding key to be created on setup")
-            .encoding_key
+        db::fetch_api_key_by_site_key(
+            &self.inner.pool,
+            &self.inner.master_key,
+            &self.db_api_site_key().await,
+        )
+        .await
+        .unwrap()
+        .expect("expected a encoding key to be created on setup")
+        .encoding_key
     }
 
     pub async fn db_challenges(&self) -> Vec<DbChallenge> {
@@ -135,7 +189,51 @@ impl TestContext {
     }
 }
 
-async fn populate_demo(pool: &PgPool, test_id: &Uuid) -> db::Result<()> {
+/// Local stand-in of the resolver of the proofs of ownership of the domains, which serves the proofs that the
+/// tests publish.
+#[derive(Debug, Default)]
+pub struct StandInResolver {
+    well_known: Mutex<HashMap<String, String>>,
+    txt: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl StandInResolver {
+    /// Serves the well known file of a host.
+    pub fn serve_well_known(&self, host: &str, content: &str) {
+        self.well_known
+            .lock()
+            .unwrap()
+            .insert(host.into(), content.into());
+    }
+
+    /// Publishes a TXT record of a name.
+    pub fn publish_txt(&self, name: &str, record: &str) {
+        self.txt
+            .lock()
+            .unwrap()
+            .entry(name.into())
+            .or_default()
+            .push(record.into());
+    }
+}
+
+impl OwnershipResolver for StandInResolver {
+    fn fetch_well_known<'a>(&'a self, host: &'a str) -> BoxFuture<'a, anyhow::Result<String>> {
+        let file = self.well_known.lock().unwrap().get(host).cloned();
+        Box::pin(async move { file.context("well known file not found") })
+    }
+
+    fn lookup_txt<'a>(&'a self, name: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<String>>> {
+        let records = self.txt.lock().unwrap().get(name).cloned();
+        Box::pin(async move { records.context("no TXT records found") })
+    }
+}
+
+async fn populate_demo(
+    pool: &PgPool,
+    master_key: &MasterKey,
+    test_id: &Uuid,
+) -> db::Result<Base64> {
     let mut txn = pool.begin().await?;
 
     let console_id = db::insert_console(
@@ -145,23 +243,15 @@ async fn populate_demo(pool: &PgPool, test_id: &Uuid) -> db::Result<()> {
     )
     .await?;
     let site_key = Base64::<UrlSafe>::random::<KEY_SIZE>();
+    let secret = Base64::<Standard>::random::<KEY_SIZE>();
     db::insert_api_key(
         &mut *txn,
+        master_key,
         &site_key,
         &console_id,
         &Base64::<Standard>::random::<KEY_SIZE>(),
-        &Base64::<Standard>::random::<KEY_SIZE>(),
-        &[],
-    )
-    .await?;
-    db::update_api_key(
-        &mut *txn,
-        &site_key,
-        &console_id,
-        DbUpdateApiKey {
-            label: None,
-            allowed_domains: Some(&[Hostname::parse("website-integration.test.com").unwrap()]),
-        },
+        &secret,
+        &[AllowedDomain::parse("website-integration.test.com").unwrap()],
     )
     .await?;
 
@@ -173,11 +263,12 @@ async fn populate_demo(pool: &PgPool, test_id: &Uuid) -> db::Result<()> {
         small_width: 360,
         small_height: 500,
         logo_url: None,
+        puzzle_kind: PuzzleKind::Selection.to_string(),
     };
     let _ = db::insert_challenge(&mut *txn, &challenge).await;
 
     txn.commit().await?;
-    Ok(())
+    Ok(secret)
 }
 
 async fn rollback_demo(pool: &PgPool, test_id: &Uuid) -> db::Result<()> {
@@ -192,6 +283,55 @@ async fn rollback_demo(pool: &PgPool, test_id: &Uuid) -> db::Result<()> {
     Ok(())
 }
 
+/// Deterministic noise in `[-1, 1]`.
+fn noise(seed: &mut u32) -> f64 {
+    *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+    (*seed >> 8) as f64 / (1 << 23) as f64 - 1.
+}
+
+/// Curved path with minimum jerk speed profile, hand tremor and irregular sampling.
+pub fn human_path(start: OffsetDateTime) -> Vec<(OffsetDateTime, i32, i32)> {
+    let mut seed = 7;
+    let mut ts = start;
+    (0..40)
+        .map(|i| {
+            let t = i as f64 / 39.;
+            let s = 10. * t.powi(3) - 15. * t.powi(4) + 6. * t.powi(5);
+            let (x, y) = (
+                (1. - s).powi(2) * 10. + 2. * (1. - s) * s * 200. + s.powi(2) * 300.,
+                (1. - s).powi(2) * 200. + 2. * (1. - s) * s * 250. + s.powi(2) * 40.,
+            );
+            ts += Duration::milliseconds(16 + (noise(&mut seed) * 6.).round() as i64);
+            (
+                ts,
+                (x + noise(&mut seed) * 1.5).round() as i32,
+                (y + noise(&mut seed) * 1.5).round() as i32,
+            )
+        })
+        .collect()
+}
+
+/// Mouse movement along the [`human_path`], followed by a click.
+pub fn human_interactions(start: OffsetDateTime) -> Vec<Interaction> {
+    let path = human_path(start);
+    let ts = |ts: OffsetDateTime| (ts.unix_timestamp_nanos() / 1_000_000) as i64;
+    let end = path.last().map_or(ts(start), |&(end, _, _)| ts(end));
+    let mut events: Vec<_> = path
+        .into_iter()
+        .map(|(at, x, y)| {
+            serde_json::json!({ "ts": ts(at), "event": { "kind": "mousemovement", "x": x, "y": y } })
+        })
+        .collect();
+    events.push(
+        serde_json::json!({ "ts": end + 120, "event": { "kind": "mouseclick", "mouse": "down" } }),
+    );
+    events.push(
+        serde_json::json!({ "ts": end + 210, "event": { "kind": "mouseclick", "mouse": "up" } }),
+    );
+
+    serde_json::from_value(serde_json::Value::Array(events)).expect("valid interactions")
+}
+
 pub async fn auth_jwt() -> &'static str {
     #[derive(Debug, Serialize)]
     struct TokenRequest {
@@ -6,8 +6,12 @@ use time::OffsetDateTime;
 use url::Url;
 
 pub mod auth;
+pub mod clearance;
+pub mod keys;
 pub mod pow_challenge;
+pub mod puzzle;
 pub mod response;
+pub mod signing;
 
 /// Claims with expiration and issued-at times.
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,222 @@
+//! Clearance tokens, issued to visitors that solved a challenge so they can skip it for a while.
+//! The clearance is scoped to the site key, the network of the visitor and optionally its user agent.
+
+use std::{net::IpAddr, time::Duration};
+
+use base64::prelude::*;
+use ipnetwork::IpNetwork;
+use jsonwebtoken::{Algorithm, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::encodings::{Base64, UrlSafe};
+
+use super::{
+    TimeClaims,
+    keys::{DecodingKeys, VersionedKey},
+};
+
+/// Algorithm used for clearance tokens.
+pub static JWT_CLEARANCE_ALGORITHM: Algorithm = Algorithm::HS256;
+
+/// Clearance configuration of an api key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClearancePolicy {
+    /// Lifetime of the clearance in seconds, 0 disables it.
+    pub lifetime_secs: u32,
+    /// Prefix length of the IPv4 network the clearance is bound to.
+    pub ipv4_prefix: u8,
+    /// Prefix length of the IPv6 network the clearance is bound to.
+    pub ipv6_prefix: u8,
+    /// Whether the clearance is bound to the user agent.
+    pub bind_user_agent: bool,
+}
+
+impl Default for ClearancePolicy {
+    fn default() -> Self {
+        Self {
+            lifetime_secs: 1800,
+            ipv4_prefix: 24,
+            ipv6_prefix: 64,
+            bind_user_agent: true,
+        }
+    }
+}
+
+impl ClearancePolicy {
+    /// Longest lifetime allowed for a clearance.
+    pub const MAX_LIFETIME_SECS: u32 = 86_400;
+
+    /// Validates the policy, describing the first invalid field.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lifetime_secs > Self::MAX_LIFETIME_SECS {
+            return Err(format!(
+                "lifetime_secs out of range [0:{}]",
+                Self::MAX_LIFETIME_SECS
+            ));
+        }
+        if self.ipv4_prefix > 32 {
+            return Err("ipv4_prefix out of range [0:32]".into());
+        }
+        if self.ipv6_prefix > 128 {
+            return Err("ipv6_prefix out of range [0:128]".into());
+        }
+        Ok(())
+    }
+
+    /// Whether clearances are issued.
+    pub fn enabled(&self) -> bool {
+        self.lifetime_secs > 0
+    }
+
+    /// Network of the address the clearance is bound to.
+    fn network(&self, addr: IpAddr) -> IpNetwork {
+        let prefix = match addr {
+            IpAddr::V4(_) => self.ipv4_prefix,
+            IpAddr::V6(_) => self.ipv6_prefix,
+        };
+        IpNetwork::new(addr, prefix)
+            .and_then(|network| IpNetwork::new(network.network(), prefix))
+            .expect("prefix was validated")
+    }
+}
+
+/// Claims contained in the clearance token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClearanceClaims {
+    pub site_key: Base64<UrlSafe>,
+    pub network: IpNetwork,
+    /// Hash of the user agent, when bound to it.
+    pub ua: Option<String>,
+    /// Score of the challenge that granted the clearance.
+    pub score: f32,
+}
+
+impl ClearanceClaims {
+    /// Creates the claims in the scope configured by the `policy`.
+    pub fn new(
+        policy: &ClearancePolicy,
+        site_key: Base64<UrlSafe>,
+        addr: IpAddr,
+        user_agent: Option<&str>,
+        score: f32,
+    ) -> Self {
+        Self {
+            site_key,
+            network: policy.network(addr),
+            ua: user_agent
+                .filter(|_| policy.bind_user_agent)
+                .map(user_agent_hash),
+            score,
+        }
+    }
+
+    /// Whether the clearance covers a request of the given site key, address and user agent.
+    pub fn permits(
+        &self,
+        site_key: &Base64<UrlSafe>,
+        addr: IpAddr,
+        user_agent: Option<&str>,
+    ) -> bool {
+        let user_agent_matches = match &self.ua {
+            Some(hash) => user_agent.is_some_and(|ua| user_agent_hash(ua) == *hash),
+            None => true,
+        };
+        self.site_key == *site_key && self.network.contains(addr) && user_agent_matches
+    }
+}
+
+fn user_agent_hash(user_agent: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(user_agent.as_bytes()))
+}
+
+/// Encodes clearance claims into a JWT that lasts for the lifetime of the policy.
+pub fn encode<'a>(
+    clearance_claims: ClearanceClaims,
+    policy: &ClearancePolicy,
+    enc_key: impl Into<VersionedKey<'a>>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let enc_key = enc_key.into();
+    jsonwebtoken::encode(
+        &enc_key.header(JWT_CLEARANCE_ALGORITHM),
+        &TimeClaims::with_timeout(
+            Duration::from_secs(policy.lifetime_secs.into()),
+            clearance_claims,
+        ),
+        &enc_key.encoding_key()?,
+    )
+}
+
+/// Decodes clearance claims from a JWT, with any of the decoding keys during a rotation.
+pub fn decode<'a>(
+    jwt: &str,
+    dec_keys: impl Into<DecodingKeys<'a>>,
+) -> Result<ClearanceClaims, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::new(JWT_CLEARANCE_ALGORITHM);
+    TimeClaims::<ClearanceClaims>::build_validation(&mut validation);
+
+    dec_keys
+        .into()
+        .decode::<TimeClaims<_>>(jwt, &validation)
+        .map(|tok| tok.claims.other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UA: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:133.0) Gecko/20100101 Firefox/133.0";
+
+    #[test]
+    fn clearance_scope() {
+        let site_key = Base64::<UrlSafe>::random::<32>();
+        let claims = ClearanceClaims::new(
+            &ClearancePolicy::default(),
+            site_key.clone(),
+            "192.0.2.10".parse().unwrap(),
+            Some(UA),
+            0.9,
+        );
+
+        assert_eq!(claims.network, "192.0.2.0/24".parse().unwrap());
+        assert!(claims.permits(&site_key, "192.0.2.200".parse().unwrap(), Some(UA)));
+        assert!(!claims.permits(&site_key, "192.0.3.10".parse().unwrap(), Some(UA)));
+        assert!(!claims.permits(&site_key, "192.0.2.10".parse().unwrap(), Some("curl/8.0")));
+        assert!(!claims.permits(&site_key, "192.0.2.10".parse().unwrap(), None));
+        assert!(!claims.permits(
+            &Base64::<UrlSafe>::random::<32>(),
+            "192.0.2.10".parse().unwrap(),
+            Some(UA)
+        ));
+    }
+
+    #[test]
+    fn clearance_scope_without_user_agent() {
+        let site_key = Base64::<UrlSafe>::random::<32>();
+        let policy =
+            ClearancePolicy { ipv6_prefix: 48, bind_user_agent: false, ..Default::default() };
+        let claims = ClearanceClaims::new(
+            &policy,
+            site_key.clone(),
+            "2001:db8:1:2::1".parse().unwrap(),
+            Some(UA),
+            0.9,
+        );
+
+        assert_eq!(claims.ua, None);
+        assert!(claims.permits(&site_key, "2001:db8:1:ff::1".parse().unwrap(), None));
+        assert!(!claims.permits(&site_key, "2001:db8:2::1".parse().unwrap(), None));
+    }
+
+    #[test]
+    fn validate_policy() {
+        assert!(ClearancePolicy::default().validate().is_ok());
+        for policy in [
+            ClearancePolicy { lifetime_secs: 86_401, ..Default::default() },
+            ClearancePolicy { ipv4_prefix: 33, ..Default::default() },
+            ClearancePolicy { ipv6_prefix: 129, ..Default::default() },
+        ] {
+            assert!(policy.validate().is_err(), "{policy:?}");
+        }
+    }
+}
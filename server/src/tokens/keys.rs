@@ -0,0 +1,185 @@
+//! Versioned encoding keys. Tokens carry the version of the key that encoded them as the `kid` of their
+//! header, so after a rotation the previous key keeps decoding the tokens in flight during a grace period.
+
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+    errors::{Error, ErrorKind},
+};
+use serde::de::DeserializeOwned;
+
+use crate::encodings::Base64;
+
+/// Grace period in seconds of the previous encoding key after a rotation, unless given otherwise. It outlasts
+/// the proof of work, puzzle and response tokens, and the clearances of the default policy.
+pub const DEFAULT_GRACE_PERIOD_SECS: u32 = 1800;
+
+/// Longest grace period in seconds of the previous encoding key, the longest lifetime of a clearance.
+pub const MAX_GRACE_PERIOD_SECS: u32 = 86_400;
+
+/// Encoding key encoded in standard base64, with its version when known.
+#[derive(Debug, Clone, Copy)]
+pub struct VersionedKey<'a> {
+    pub version: Option<i32>,
+    pub key: &'a str,
+}
+
+impl<'a> VersionedKey<'a> {
+    pub fn new(version: i32, key: &'a Base64) -> Self {
+        Self { version: Some(version), key: key.as_str() }
+    }
+
+    /// Key id of the tokens encoded with the key.
+    pub fn kid(&self) -> Option<String> {
+        self.version.map(|version| version.to_string())
+    }
+
+    /// Header of the tokens encoded with the key.
+    pub fn header(&self, algorithm: Algorithm) -> Header {
+        let mut header = Header::new(algorithm);
+        header.kid = self.kid();
+        header
+    }
+
+    pub fn encoding_key(&self) -> Result<EncodingKey, Error> {
+        EncodingKey::from_base64_secret(self.key)
+    }
+
+    /// Whether the key may have encoded a token with the given `kid`. Keys without a version and tokens
+    /// without a `kid` match any.
+    fn matches(&self, kid: Option<&str>) -> bool {
+        match (self.kid(), kid) {
+            (Some(version), Some(kid)) => version == kid,
+            _ => true,
+        }
+    }
+}
+
+impl<'a> From<&'a Base64> for VersionedKey<'a> {
+    fn from(key: &'a Base64) -> Self {
+        Self { version: None, key: key.as_str() }
+    }
+}
+
+impl<'a> From<&'a str> for VersionedKey<'a> {
+    fn from(key: &'a str) -> Self {
+        Self { version: None, key }
+    }
+}
+
+/// Keys that decode the tokens of an api key, the current encoding key and the previous one while the
+/// grace period of its rotation lasts.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodingKeys<'a> {
+    pub current: VersionedKey<'a>,
+    pub previous: Option<VersionedKey<'a>>,
+}
+
+impl DecodingKeys<'_> {
+    /// Decodes a JWT with the key whose version is the `kid` of the token. Tokens without a `kid`, issued
+    /// before the keys were versioned, are tried with every key.
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        jwt: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<T>, Error> {
+        let kid = jsonwebtoken::decode_header(jwt)?.kid;
+        let keys = [Some(self.current), self.previous]
+            .into_iter()
+            .flatten()
+            .filter(|key| key.matches(kid.as_deref()));
+
+        let mut result = Err(ErrorKind::InvalidSignature.into());
+        for key in keys {
+            result =
+                jsonwebtoken::decode(jwt, &DecodingKey::from_base64_secret(key.key)?, validation);
+            if !matches!(&result, Err(err) if matches!(err.kind(), ErrorKind::InvalidSignature)) {
+                break;
+            }
+        }
+        result
+    }
+}
+
+impl<'a, K: Into<VersionedKey<'a>>> From<K> for DecodingKeys<'a> {
+    fn from(current: K) -> Self {
+        Self { current: current.into(), previous: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{encodings::Standard, tokens::TimeClaims};
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Claims {
+        sub: String,
+    }
+
+    fn encode(key: VersionedKey) -> String {
+        jsonwebtoken::encode(
+            &key.header(Algorithm::HS256),
+            &TimeClaims::new(Claims { sub: "visitor".into() }),
+            &key.encoding_key().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn decode(jwt: &str, keys: DecodingKeys) -> Result<TimeClaims<Claims>, Error> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        TimeClaims::<Claims>::build_validation(&mut validation);
+        keys.decode(jwt, &validation).map(|tok| tok.claims)
+    }
+
+    #[test]
+    fn decode_during_grace_period() {
+        let (old_key, new_key) = (
+            Base64::<Standard>::random::<32>(),
+            Base64::<Standard>::random::<32>(),
+        );
+        let (old_key, new_key) = (
+            VersionedKey::new(1, &old_key),
+            VersionedKey::new(2, &new_key),
+        );
+        let keys = DecodingKeys { current: new_key, previous: Some(old_key) };
+
+        let jwt = encode(old_key);
+        assert_eq!(
+            jsonwebtoken::decode_header(&jwt).unwrap().kid.as_deref(),
+            Some("1")
+        );
+        assert!(decode(&jwt, keys).is_ok());
+        assert!(decode(&encode(new_key), keys).is_ok());
+
+        // after the grace period only the new key decodes
+        assert!(decode(&jwt, new_key.into()).is_err());
+    }
+
+    #[test]
+    fn decode_without_kid() {
+        let (old_key, new_key) = (
+            Base64::<Standard>::random::<32>(),
+            Base64::<Standard>::random::<32>(),
+        );
+        let keys = DecodingKeys {
+            current: VersionedKey::new(2, &new_key),
+            previous: Some(VersionedKey::new(1, &old_key)),
+        };
+
+        assert!(decode(&encode((&old_key).into()), keys).is_ok());
+        assert!(decode(&encode((&new_key).into()), keys).is_ok());
+        assert!(decode(&encode((&Base64::<Standard>::random::<32>()).into()), keys).is_err());
+    }
+
+    #[test]
+    fn decode_with_unknown_kid() {
+        let key = Base64::<Standard>::random::<32>();
+        let jwt = encode(VersionedKey::new(3, &key));
+
+        assert!(decode(&jwt, VersionedKey::new(2, &key).into()).is_err());
+        assert!(decode(&jwt, (&key).into()).is_ok());
+    }
+}
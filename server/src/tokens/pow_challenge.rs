@@ -1,48 +1,49 @@
 use std::time::Duration;
 
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{Algorithm, Validation};
 
-use crate::{analysis::proof_of_work::PowChallenge, encodings::Base64};
+use crate::analysis::proof_of_work::PowChallenge;
 
-use super::TimeClaims;
+use super::{
+    TimeClaims,
+    keys::{DecodingKeys, VersionedKey},
+};
 
 /// Algorithm used for proof of work tokens.
 pub static JWT_POW_ALGORITHM: Algorithm = Algorithm::HS256;
 
 /// Encodes a proof of work challenge into a JWT.
-pub fn encode(
+pub fn encode<'a>(
     pow_challenge: PowChallenge,
-    enc_key: &Base64,
+    enc_key: impl Into<VersionedKey<'a>>,
 ) -> Result<String, jsonwebtoken::errors::Error> {
-    jsonwebtoken::encode(
-        &Header::new(JWT_POW_ALGORITHM),
-        &TimeClaims::with_timeout(Duration::from_secs(300), pow_challenge),
-        &EncodingKey::from_base64_secret(enc_key.as_str())?,
-    )
+    encode_with_timeout(pow_challenge, enc_key, Duration::from_secs(300))
 }
 
 /// Encodes a proof of work challenge into a JWT with a custom timeout.
-pub fn encode_with_timeout(
+pub fn encode_with_timeout<'a>(
     pow_challenge: PowChallenge,
-    enc_key_b64: &str,
+    enc_key: impl Into<VersionedKey<'a>>,
     timeout: Duration,
 ) -> Result<String, jsonwebtoken::errors::Error> {
+    let enc_key = enc_key.into();
     jsonwebtoken::encode(
-        &Header::new(JWT_POW_ALGORITHM),
+        &enc_key.header(JWT_POW_ALGORITHM),
         &TimeClaims::with_timeout(timeout, pow_challenge),
-        &EncodingKey::from_base64_secret(enc_key_b64)?,
+        &enc_key.encoding_key()?,
     )
 }
 
-/// Decodes a proof of work challenge from a JWT.
-pub fn decode(jwt: &str, dec_key_b64: &str) -> Result<PowChallenge, jsonwebtoken::errors::Error> {
+/// Decodes a proof of work challenge from a JWT, with any of the decoding keys during a rotation.
+pub fn decode<'a>(
+    jwt: &str,
+    dec_keys: impl Into<DecodingKeys<'a>>,
+) -> Result<TimeClaims<PowChallenge>, jsonwebtoken::errors::Error> {
     let mut validation = Validation::new(JWT_POW_ALGORITHM);
     TimeClaims::<PowChallenge>::build_validation(&mut validation);
 
-    jsonwebtoken::decode::<_>(
-        jwt,
-        &DecodingKey::from_base64_secret(dec_key_b64)?,
-        &validation,
-    )
-    .map(|tok| tok.claims)
+    dec_keys
+        .into()
+        .decode::<TimeClaims<_>>(jwt, &validation)
+        .map(|tok| tok.claims)
 }
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use jsonwebtoken::{Algorithm, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::puzzle::Puzzle;
+
+use super::{
+    TimeClaims,
+    keys::{DecodingKeys, VersionedKey},
+};
+
+/// Algorithm used for puzzle tokens.
+pub static JWT_PUZZLE_ALGORITHM: Algorithm = Algorithm::HS256;
+
+/// Time a user has to solve the puzzle.
+pub const PUZZLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Claims of a puzzle token. Along with the instance it carries the layout, so the challenge can render it.
+#[derive(Debug, Serialize, Deserialize)]
+struct PuzzleClaims {
+    #[serde(flatten)]
+    puzzle: Puzzle,
+    layout: Vec<u16>,
+}
+
+/// Encodes a puzzle instance into a JWT signed with the encoding key, so it can't be tampered with.
+pub fn encode<'a>(
+    puzzle: Puzzle,
+    enc_key: impl Into<VersionedKey<'a>>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let layout = puzzle.layout();
+    let enc_key = enc_key.into();
+    jsonwebtoken::encode(
+        &enc_key.header(JWT_PUZZLE_ALGORITHM),
+        &TimeClaims::with_timeout(PUZZLE_TIMEOUT, PuzzleClaims { puzzle, layout }),
+        &enc_key.encoding_key()?,
+    )
+}
+
+/// Decodes a puzzle instance from a JWT, with any of the decoding keys during a rotation.
+pub fn decode<'a>(
+    jwt: &str,
+    dec_keys: impl Into<DecodingKeys<'a>>,
+) -> Result<TimeClaims<Puzzle>, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::new(JWT_PUZZLE_ALGORITHM);
+    TimeClaims::<PuzzleClaims>::build_validation(&mut validation);
+
+    let TimeClaims { exp, iat, other: PuzzleClaims { puzzle, .. } } = dec_keys
+        .into()
+        .decode::<TimeClaims<PuzzleClaims>>(jwt, &validation)?
+        .claims;
+
+    Ok(TimeClaims { exp, iat, other: puzzle })
+}
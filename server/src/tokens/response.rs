@@ -1,59 +1,184 @@
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{Algorithm, Header, Validation};
 use serde::{Deserialize, Serialize};
-use std::{net::IpAddr, time::Duration};
+use std::{net::IpAddr, ops::RangeInclusive, time::Duration};
+use uuid::Uuid;
 
-use crate::{domain::hostname::Hostname, encodings::Base64};
+use crate::domain::{action::Action, form_digest::FormDigest, hostname::Hostname};
 
-use super::TimeClaims;
+use super::{
+    TimeClaims,
+    keys::{DecodingKeys, VersionedKey},
+    signing::{SigningKeyPair, VerifyingKey},
+};
 
 /// Algorithm used for response tokens.
 pub static JWT_RESPONSE_ALGORITHM: Algorithm = Algorithm::HS256;
 
+/// Lifetime in seconds of response tokens, unless configured otherwise for the api key.
+pub const DEFAULT_TTL_SECS: u32 = 30;
+
+/// Lifetimes in seconds allowed for response tokens.
+pub const TTL_SECS_RANGE: RangeInclusive<u32> = 10..=900;
+
 /// Claims contained in the response token.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ResponseClaims {
+    /// Unique token id, so the token can only be verified once.
+    pub jti: Uuid,
     pub score: f32,
     pub addr: IpAddr,
     pub host: Hostname,
+    /// Action given by the widget when solving the challenge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+    /// Digest of the protected form fields given by the widget when solving the challenge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub form_digest: Option<FormDigest>,
+}
+
+impl ResponseClaims {
+    /// Creates new claims with a random token id.
+    pub fn new(score: f32, addr: IpAddr, host: Hostname) -> Self {
+        Self {
+            jti: Uuid::new_v4(),
+            score,
+            addr,
+            host,
+            action: None,
+            form_digest: None,
+        }
+    }
+
+    /// Binds the claims to an action.
+    pub fn with_action(mut self, action: Option<Action>) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Binds the claims to the digest of a form.
+    pub fn with_form_digest(mut self, form_digest: Option<FormDigest>) -> Self {
+        self.form_digest = form_digest;
+        self
+    }
 }
 
 /// Encodes response claims into a JWT.
-pub fn encode(
+pub fn encode<'a>(
     response_claims: ResponseClaims,
-    enc_key: &Base64,
+    enc_key: impl Into<VersionedKey<'a>>,
 ) -> Result<String, jsonwebtoken::errors::Error> {
-    jsonwebtoken::encode(
-        &Header::new(JWT_RESPONSE_ALGORITHM),
-        &TimeClaims::new(response_claims),
-        &EncodingKey::from_base64_secret(enc_key.as_str())?,
+    encode_with_timeout(
+        response_claims,
+        enc_key,
+        Duration::from_secs(DEFAULT_TTL_SECS.into()),
     )
 }
 
 /// Encodes response claims into a JWT with a custom timeout.
-pub fn encode_with_timeout(
+pub fn encode_with_timeout<'a>(
     response_claims: ResponseClaims,
-    enc_key: &Base64,
+    enc_key: impl Into<VersionedKey<'a>>,
     timeout: Duration,
 ) -> Result<String, jsonwebtoken::errors::Error> {
+    let enc_key = enc_key.into();
     jsonwebtoken::encode(
-        &Header::new(JWT_RESPONSE_ALGORITHM),
+        &enc_key.header(JWT_RESPONSE_ALGORITHM),
         &TimeClaims::with_timeout(timeout, response_claims),
-        &EncodingKey::from_base64_secret(enc_key.as_str())?,
+        &enc_key.encoding_key()?,
     )
 }
 
-/// Decodes response claims from a JWT.
-pub fn decode(
+/// Decodes response claims from a JWT, with any of the decoding keys during a rotation.
+pub fn decode<'a>(
     jwt: &str,
-    dec_key: &Base64,
+    dec_keys: impl Into<DecodingKeys<'a>>,
 ) -> Result<TimeClaims<ResponseClaims>, jsonwebtoken::errors::Error> {
     let mut validation = Validation::new(JWT_RESPONSE_ALGORITHM);
     TimeClaims::<ResponseClaims>::build_validation(&mut validation);
 
-    jsonwebtoken::decode::<TimeClaims<_>>(
-        jwt,
-        &DecodingKey::from_base64_secret(dec_key.as_str())?,
-        &validation,
+    dec_keys
+        .into()
+        .decode::<TimeClaims<_>>(jwt, &validation)
+        .map(|tok| tok.claims)
+}
+
+/// Encodes response claims into a JWT signed with an asymmetric keypair, with its `kid` in the header.
+pub fn encode_with_key_pair(
+    response_claims: ResponseClaims,
+    key_pair: &SigningKeyPair,
+    timeout: Duration,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let mut header = Header::new(key_pair.algorithm().jwt_algorithm());
+    header.kid = Some(key_pair.kid());
+    jsonwebtoken::encode(
+        &header,
+        &TimeClaims::with_timeout(timeout, response_claims),
+        &key_pair.encoding_key(),
     )
-    .map(|tok| tok.claims)
+}
+
+/// Decodes response claims from a JWT signed either with a keypair, when the `kid` of the header is one of the
+/// verifying keys', or with the decoding keys.
+pub fn decode_with_key_pair<'a, 'b>(
+    jwt: &str,
+    dec_keys: impl Into<DecodingKeys<'a>>,
+    verifying_keys: impl IntoIterator<Item = &'b VerifyingKey>,
+) -> Result<TimeClaims<ResponseClaims>, jsonwebtoken::errors::Error> {
+    let Some(kid) = jsonwebtoken::decode_header(jwt)?.kid else {
+        return decode(jwt, dec_keys);
+    };
+    let Some(verifying_key) = verifying_keys.into_iter().find(|key| key.kid() == kid) else {
+        return decode(jwt, dec_keys);
+    };
+
+    let mut validation = Validation::new(verifying_key.algorithm().jwt_algorithm());
+    TimeClaims::<ResponseClaims>::build_validation(&mut validation);
+
+    jsonwebtoken::decode::<TimeClaims<_>>(jwt, &verifying_key.decoding_key(), &validation)
+        .map(|tok| tok.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        encodings::{Base64, Standard},
+        tokens::signing::SigningAlgorithm,
+    };
+
+    use super::*;
+
+    fn claims() -> ResponseClaims {
+        ResponseClaims::new(
+            0.75,
+            [127, 0, 0, 1].into(),
+            "website-integration.test.com".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn decode_with_key_pair_or_encoding_key() {
+        let enc_key = Base64::<Standard>::random::<32>();
+        for algorithm in [SigningAlgorithm::ES256, SigningAlgorithm::EdDSA] {
+            let key_pair = SigningKeyPair::generate(algorithm).unwrap();
+            let timeout = Duration::from_secs(DEFAULT_TTL_SECS.into());
+
+            let jwt = encode_with_key_pair(claims(), &key_pair, timeout).unwrap();
+            let header = jsonwebtoken::decode_header(&jwt).unwrap();
+            assert_eq!(header.alg, algorithm.jwt_algorithm());
+            assert_eq!(header.kid, Some(key_pair.kid()));
+            assert!(decode_with_key_pair(&jwt, &enc_key, [key_pair.verifying_key()]).is_ok());
+            assert!(decode_with_key_pair(&jwt, &enc_key, []).is_err());
+            let other_key_pair = SigningKeyPair::generate(algorithm).unwrap();
+            assert!(
+                decode_with_key_pair(&jwt, &enc_key, [other_key_pair.verifying_key()]).is_err()
+            );
+            // the key of the token is found among the previous ones
+            let verifying_keys = [other_key_pair.verifying_key(), key_pair.verifying_key()];
+            assert!(decode_with_key_pair(&jwt, &enc_key, verifying_keys).is_ok());
+
+            // tokens signed with the encoding key are still accepted
+            let jwt = encode(claims(), &enc_key).unwrap();
+            assert!(decode_with_key_pair(&jwt, &enc_key, [key_pair.verifying_key()]).is_ok());
+        }
+    }
 }
@@ -0,0 +1,284 @@
+//! Algorithms that sign the response tokens of an api key. HS256 signs with the encoding key of the api key,
+//! while ES256 and EdDSA sign with a keypair whose public key is published in a JWKS, so the tokens can also
+//! be verified offline.
+
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+};
+
+use base64::prelude::*;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse,
+    },
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, Ed25519KeyPair, KeyPair},
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Algorithm that signs the response tokens of an api key.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SigningAlgorithm {
+    /// HMAC with SHA-256, signed with the encoding key.
+    #[default]
+    HS256,
+    /// ECDSA with the P-256 curve and SHA-256.
+    ES256,
+    /// EdDSA with the Ed25519 curve.
+    EdDSA,
+}
+
+impl SigningAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningAlgorithm::HS256 => "HS256",
+            SigningAlgorithm::ES256 => "ES256",
+            SigningAlgorithm::EdDSA => "EdDSA",
+        }
+    }
+
+    /// Algorithm of the JWT header.
+    pub fn jwt_algorithm(&self) -> Algorithm {
+        match self {
+            SigningAlgorithm::HS256 => Algorithm::HS256,
+            SigningAlgorithm::ES256 => Algorithm::ES256,
+            SigningAlgorithm::EdDSA => Algorithm::EdDSA,
+        }
+    }
+}
+
+impl FromStr for SigningAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HS256" => Ok(SigningAlgorithm::HS256),
+            "ES256" => Ok(SigningAlgorithm::ES256),
+            "EdDSA" => Ok(SigningAlgorithm::EdDSA),
+            other => Err(format!("{other} is not a supported signing algorithm")),
+        }
+    }
+}
+
+impl Display for SigningAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Keypair of an asymmetric [`SigningAlgorithm`].
+pub struct SigningKeyPair {
+    /// Private key as a PKCS#8 document.
+    private_key: Secret<Vec<u8>>,
+    verifying_key: VerifyingKey,
+}
+
+/// Public key of a [`SigningKeyPair`], which verifies the tokens it signed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyingKey {
+    algorithm: SigningAlgorithm,
+    /// Public key, the uncompressed point for ES256 or the raw key for EdDSA.
+    public_key: Vec<u8>,
+}
+
+impl SigningKeyPair {
+    /// Generates a random keypair for an asymmetric algorithm, `None` for HS256.
+    pub fn generate(algorithm: SigningAlgorithm) -> Option<Self> {
+        let rng = SystemRandom::new();
+        let (private_key, public_key) = match algorithm {
+            SigningAlgorithm::HS256 => return None,
+            SigningAlgorithm::ES256 => {
+                let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                    .expect("system random is available");
+                let key_pair = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    pkcs8.as_ref(),
+                    &rng,
+                )
+                .expect("generated pkcs8 is valid");
+                (
+                    pkcs8.as_ref().to_vec(),
+                    key_pair.public_key().as_ref().to_vec(),
+                )
+            }
+            SigningAlgorithm::EdDSA => {
+                let pkcs8 =
+                    Ed25519KeyPair::generate_pkcs8(&rng).expect("system random is available");
+                let key_pair =
+                    Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).expect("generated pkcs8 is valid");
+                (
+                    pkcs8.as_ref().to_vec(),
+                    key_pair.public_key().as_ref().to_vec(),
+                )
+            }
+        };
+        Some(Self {
+            private_key: Secret::new(private_key),
+            verifying_key: VerifyingKey { algorithm, public_key },
+        })
+    }
+
+    /// Creates the keypair from the keys encoded in standard base64, as stored in the database.
+    pub fn from_base64(
+        algorithm: SigningAlgorithm,
+        private_key: &str,
+        public_key: &str,
+    ) -> Result<Self, base64::DecodeError> {
+        Ok(Self {
+            private_key: Secret::new(BASE64_STANDARD.decode(private_key)?),
+            verifying_key: VerifyingKey::from_base64(algorithm, public_key)?,
+        })
+    }
+
+    pub fn algorithm(&self) -> SigningAlgorithm {
+        self.verifying_key.algorithm
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        &self.verifying_key
+    }
+
+    /// Private key encoded in standard base64.
+    pub fn private_key_base64(&self) -> Secret<String> {
+        Secret::new(BASE64_STANDARD.encode(self.private_key.expose_secret()))
+    }
+
+    /// Public key encoded in standard base64.
+    pub fn public_key_base64(&self) -> String {
+        self.verifying_key.public_key_base64()
+    }
+
+    pub fn kid(&self) -> String {
+        self.verifying_key.kid()
+    }
+
+    pub fn encoding_key(&self) -> EncodingKey {
+        match self.algorithm() {
+            SigningAlgorithm::ES256 => EncodingKey::from_ec_der(self.private_key.expose_secret()),
+            _ => EncodingKey::from_ed_der(self.private_key.expose_secret()),
+        }
+    }
+
+    pub fn decoding_key(&self) -> DecodingKey {
+        self.verifying_key.decoding_key()
+    }
+
+    pub fn jwk(&self) -> Jwk {
+        self.verifying_key.jwk()
+    }
+}
+
+impl VerifyingKey {
+    /// Creates the public key from its encoding in standard base64, as stored in the database.
+    pub fn from_base64(
+        algorithm: SigningAlgorithm,
+        public_key: &str,
+    ) -> Result<Self, base64::DecodeError> {
+        Ok(Self { algorithm, public_key: BASE64_STANDARD.decode(public_key)? })
+    }
+
+    pub fn algorithm(&self) -> SigningAlgorithm {
+        self.algorithm
+    }
+
+    /// Public key encoded in standard base64.
+    pub fn public_key_base64(&self) -> String {
+        BASE64_STANDARD.encode(&self.public_key)
+    }
+
+    /// Key id, the hash of the public key encoded in URL-safe base64 without padding.
+    pub fn kid(&self) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(&self.public_key))
+    }
+
+    pub fn decoding_key(&self) -> DecodingKey {
+        match self.algorithm {
+            SigningAlgorithm::ES256 => DecodingKey::from_ec_der(&self.public_key),
+            _ => DecodingKey::from_ed_der(&self.public_key),
+        }
+    }
+
+    /// Public key as a JWK.
+    pub fn jwk(&self) -> Jwk {
+        let (key_algorithm, algorithm) = match self.algorithm {
+            SigningAlgorithm::ES256 => (
+                KeyAlgorithm::ES256,
+                AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                    key_type: EllipticCurveKeyType::EC,
+                    curve: EllipticCurve::P256,
+                    // skips the tag of the uncompressed point
+                    x: BASE64_URL_SAFE_NO_PAD.encode(&self.public_key[1..33]),
+                    y: BASE64_URL_SAFE_NO_PAD.encode(&self.public_key[33..]),
+                }),
+            ),
+            _ => (
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: BASE64_URL_SAFE_NO_PAD.encode(&self.public_key),
+                }),
+            ),
+        };
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(self.kid()),
+                ..Default::default()
+            },
+            algorithm,
+        }
+    }
+}
+
+impl Debug for SigningKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKeyPair")
+            .field("algorithm", &self.algorithm())
+            .field("kid", &self.kid())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_key_pairs() {
+        assert!(SigningKeyPair::generate(SigningAlgorithm::HS256).is_none());
+        for algorithm in [SigningAlgorithm::ES256, SigningAlgorithm::EdDSA] {
+            let key_pair = SigningKeyPair::generate(algorithm).expect("asymmetric algorithm");
+            let stored = SigningKeyPair::from_base64(
+                algorithm,
+                key_pair.private_key_base64().expose_secret(),
+                &key_pair.public_key_base64(),
+            )
+            .unwrap();
+
+            assert_eq!(stored.kid(), key_pair.kid());
+            assert_eq!(stored.jwk(), key_pair.jwk());
+        }
+    }
+
+    #[test]
+    fn signing_algorithm_from_str() {
+        for algorithm in [
+            SigningAlgorithm::HS256,
+            SigningAlgorithm::ES256,
+            SigningAlgorithm::EdDSA,
+        ] {
+            assert_eq!(algorithm.as_str().parse(), Ok(algorithm));
+        }
+        assert!("RS256".parse::<SigningAlgorithm>().is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use gotcha_server::{
     HTTP_CLIENT,
+    analysis::puzzle::PuzzleKind,
     routes::admin::{AddChallenge, DeleteChallenge},
     test_helpers,
 };
@@ -15,7 +16,12 @@ async fn add_challenge_successful(server: TestContext) -> anyhow::Result<()> {
     let response = HTTP_CLIENT
         .post(format!("http://localhost:{port}/api/admin/challenge"))
         .bearer_auth(test_helpers::auth_jwt().await)
-        .json(&AddChallenge { url: url.clone(), width: 50, height: 50 })
+        .json(&AddChallenge {
+            url: url.clone(),
+            width: 50,
+            height: 50,
+            puzzle_kind: PuzzleKind::default(),
+        })
         .send()
         .await?;
     assert_eq!(response.status(), StatusCode::OK);
@@ -37,6 +43,7 @@ async fn add_challenge_bad_url(server: TestContext) -> anyhow::Result<()> {
             url: "bad_url::gotcha-integration.test.com/index.html".into(),
             width: 50,
             height: 50,
+            puzzle_kind: PuzzleKind::default(),
         })
         .send()
         .await?;
@@ -75,6 +82,7 @@ async fn add_challenge_zero_dimensions(server: TestContext) -> anyhow::Result<()
             url: "https://gotcha-integration.test.com/index.html".into(),
             width: 50,
             height: 0,
+            puzzle_kind: PuzzleKind::default(),
         })
         .send()
         .await?;
@@ -93,7 +101,12 @@ async fn add_challenge_already_exists(server: TestContext) -> anyhow::Result<()>
     let response = HTTP_CLIENT
         .post(format!("http://localhost:{port}/api/admin/challenge"))
         .bearer_auth(test_helpers::auth_jwt().await)
-        .json(&AddChallenge { url: url.clone(), width: 50, height: 50 })
+        .json(&AddChallenge {
+            url: url.clone(),
+            width: 50,
+            height: 50,
+            puzzle_kind: PuzzleKind::default(),
+        })
         .send()
         .await?;
     assert_eq!(response.status(), StatusCode::OK);
@@ -101,7 +114,12 @@ async fn add_challenge_already_exists(server: TestContext) -> anyhow::Result<()>
     let response = HTTP_CLIENT
         .post(format!("http://localhost:{port}/api/admin/challenge"))
         .header("Authorization", format!("Bearer {auth_key}"))
-        .json(&AddChallenge { url: url.clone(), width: 50, height: 50 })
+        .json(&AddChallenge {
+            url: url.clone(),
+            width: 50,
+            height: 50,
+            puzzle_kind: PuzzleKind::default(),
+        })
         .send()
         .await?;
     assert_eq!(response.status(), StatusCode::OK);
@@ -119,7 +137,12 @@ async fn remove_challenge_successful(server: TestContext) -> anyhow::Result<()>
     let response = HTTP_CLIENT
         .post(format!("http://localhost:{port}/api/admin/challenge"))
         .bearer_auth(test_helpers::auth_jwt().await)
-        .json(&AddChallenge { url: url.clone(), width: 50, height: 50 })
+        .json(&AddChallenge {
+            url: url.clone(),
+            width: 50,
+            height: 50,
+            puzzle_kind: PuzzleKind::default(),
+        })
         .send()
         .await?;
     assert_eq!(response.status(), StatusCode::OK);
@@ -163,6 +186,7 @@ async fn challenge_endpoint_missing_auth_key(server: TestContext) -> anyhow::Res
             url: format!("https://gotcha-integration.test.com/index.html?nonce={nonce}"),
             width: 50,
             height: 50,
+            puzzle_kind: PuzzleKind::default(),
         })
         .send()
         .await?;
@@ -183,6 +207,7 @@ async fn challenge_endpoint_wrong_auth_key(server: TestContext) -> anyhow::Resul
             url: format!("https://gotcha-integration.test.com/index.html?nonce={nonce}"),
             width: 50,
             height: 50,
+            puzzle_kind: PuzzleKind::default(),
         })
         .send()
         .await?;
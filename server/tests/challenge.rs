@@ -1,19 +1,31 @@
 use gotcha_server::{
     HTTP_CLIENT,
-    encodings::{Base64, UrlSafe},
+    analysis::{
+        interaction::Interaction, policy::PreAnalysisPolicy, proof_of_work::PowAlgorithm,
+        scorer::SignalKind,
+    },
+    configuration::DomainVerificationConfig,
+    db::{self, DbUpdateApiKey},
+    encodings::{Base64, KEY_SIZE, Standard, UrlSafe},
+    ownership,
     routes::challenge::{
         AccessibilityRequest, ChallengeResponse, ChallengeResults, GetChallenge, PowResponse,
-        PreAnalysisRequest, PreAnalysisResponse, ProofOfWork,
+        PreAnalysisRequest, PreAnalysisResponse, ProofOfWork, PuzzleSolution, TEST_RESPONSE_TOKEN,
     },
+    test_helpers::{self, TestContext},
     tokens::{
-        TimeClaims, pow_challenge,
-        response::{JWT_RESPONSE_ALGORITHM, ResponseClaims},
+        TimeClaims,
+        clearance::ClearancePolicy,
+        pow_challenge, puzzle,
+        response::{self, JWT_RESPONSE_ALGORITHM, ResponseClaims},
+        signing::{SigningAlgorithm, SigningKeyPair},
     },
 };
 use gotcha_server_macros::integration_test;
-use jsonwebtoken::{DecodingKey, Validation};
+use jsonwebtoken::{DecodingKey, Validation, jwk::JwkSet};
 use reqwest::StatusCode;
 use url::Url;
+use uuid::Uuid;
 
 async fn get_pow_helper(port: u16, site_key: &Base64<UrlSafe>) -> anyhow::Result<PowResponse> {
     Ok(HTTP_CLIENT
@@ -27,185 +39,1178 @@ async fn get_pow_helper(port: u16, site_key: &Base64<UrlSafe>) -> anyhow::Result
         .await?)
 }
 
+async fn get_challenge_helper(
+    port: u16,
+    site_key: &Base64<UrlSafe>,
+) -> anyhow::Result<GetChallenge> {
+    Ok(HTTP_CLIENT
+        .get(format!("http://localhost:{port}/api/challenge"))
+        .header("X-Site-Key", site_key.as_str())
+        .send()
+        .await?
+        .json::<GetChallenge>()
+        .await?)
+}
+
+#[integration_test]
+async fn get_challenge(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+
+    let response = HTTP_CLIENT
+        .get(format!("http://localhost:{port}/api/challenge"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let _challenge: GetChallenge = response.json().await?;
+
+    Ok(())
+}
+
+#[integration_test]
+async fn get_challenge_with_puzzle(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let challenge = get_challenge_helper(port, &site_key).await?;
+    let puzzle_token = challenge.puzzle.expect("challenge must have a puzzle");
+    let puzzle = puzzle::decode(&puzzle_token, &enc_key)?;
+    assert_eq!(puzzle.other.challenge, challenge.url);
+
+    // the layout is signed along with the instance, so the challenge can render it
+    let claims = jsonwebtoken::decode::<serde_json::Value>(
+        &puzzle_token,
+        &DecodingKey::from_base64_secret(enc_key.as_str())?,
+        &Validation::new(puzzle::JWT_PUZZLE_ALGORITHM),
+    )?
+    .claims;
+    assert_eq!(claims["layout"], serde_json::json!(puzzle.other.layout()));
+
+    Ok(())
+}
+
+#[ignore = "TODO: create one db per test to isolate"]
+#[integration_test]
+async fn get_challenge_fails(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+
+    let response = HTTP_CLIENT
+        .get(format!("http://localhost:{port}/api/challenge"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn get_all_challenges(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+
+    let response = HTTP_CLIENT
+        .get(format!("http://localhost:{port}/api/challenge/all"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let _challenges: Vec<GetChallenge> = response.json().await?;
+
+    Ok(())
+}
+
+/// Mouse movement along a human path, followed by a click.
+fn human_interactions() -> Vec<Interaction> {
+    test_helpers::human_interactions(time::OffsetDateTime::now_utc())
+}
+
+#[integration_test]
+async fn process_successful_challenge(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let challenge = get_challenge_helper(port, &site_key).await?;
+    let puzzle_token = challenge.puzzle.expect("challenge must have a puzzle");
+    let puzzle = puzzle::decode(&puzzle_token, &enc_key)
+        .expect("server returned invalid puzzle")
+        .other;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+            challenge: challenge.url,
+            solution: Some(PuzzleSolution { puzzle: puzzle_token, answer: puzzle.solve() }),
+            interactions: human_interactions(),
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ChallengeResponse { token, .. } = response.json().await?;
+    eprintln!("{token}");
+    let token_data = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
+        &token,
+        &DecodingKey::from_base64_secret(enc_key.as_str())?,
+        &Validation::new(JWT_RESPONSE_ALGORITHM),
+    )?;
+    assert_eq!(token_data.header.alg, JWT_RESPONSE_ALGORITHM);
+    assert!(token_data.claims.other.score >= 0.5);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_with_action(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let challenge = get_challenge_helper(port, &site_key).await?;
+    let puzzle_token = challenge.puzzle.expect("challenge must have a puzzle");
+    let puzzle = puzzle::decode(&puzzle_token, &enc_key)
+        .expect("server returned invalid puzzle")
+        .other;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+            challenge: challenge.url,
+            solution: Some(PuzzleSolution { puzzle: puzzle_token, answer: puzzle.solve() }),
+            interactions: human_interactions(),
+            action: Some("newsletter/subscribe".parse()?),
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ChallengeResponse { token, .. } = response.json().await?;
+    let claims = response::decode(&token, &enc_key)?;
+    assert_eq!(claims.other.action, Some("newsletter/subscribe".parse()?));
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&serde_json::json!({
+            "success": true,
+            "challenge": "https://gotcha-integration.test.com/im-not-a-robot/index.html",
+            "action": "log in",
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_solved_challenge_without_interactions(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let challenge = get_challenge_helper(port, &site_key).await?;
+    let puzzle_token = challenge.puzzle.expect("challenge must have a puzzle");
+    let puzzle = puzzle::decode(&puzzle_token, &enc_key)
+        .expect("server returned invalid puzzle")
+        .other;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+            challenge: challenge.url,
+            solution: Some(PuzzleSolution { puzzle: puzzle_token, answer: puzzle.solve() }),
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ChallengeResponse { token, .. } = response.json().await?;
+    let token_data = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
+        &token,
+        &DecodingKey::from_base64_secret(enc_key.as_str())?,
+        &Validation::new(JWT_RESPONSE_ALGORITHM),
+    )?;
+    assert!(token_data.claims.other.score < 0.5);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_without_solution_is_advisory(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    for success in [true, false] {
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/challenge/process"))
+            .header("Origin", "http://website-integration.test.com")
+            .header("X-Site-Key", site_key.as_str())
+            .json(&ChallengeResults {
+                success,
+                challenge: Url::parse(
+                    "https://gotcha-integration.test.com/im-not-a-robot/index.html",
+                )?,
+                solution: None,
+                interactions: human_interactions(),
+                action: None,
+                form_digest: None,
+            })
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let ChallengeResponse { token, clearance } = response.json().await?;
+        let claims = response::decode(&token, &enc_key)?;
+        assert_eq!(claims.other.score >= 0.5, success);
+        // only a validated answer grants a clearance
+        assert!(clearance.is_none());
+    }
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_with_wrong_answer(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let challenge = get_challenge_helper(port, &site_key).await?;
+    let puzzle_token = challenge.puzzle.expect("challenge must have a puzzle");
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+            challenge: challenge.url,
+            solution: Some(PuzzleSolution { puzzle: puzzle_token, answer: vec![] }),
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ChallengeResponse { token, .. } = response.json().await?;
+    let token_data = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
+        &token,
+        &DecodingKey::from_base64_secret(enc_key.as_str())?,
+        &Validation::new(JWT_RESPONSE_ALGORITHM),
+    )?;
+    assert!(token_data.claims.other.score == 0.);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_with_invalid_puzzle(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
+            solution: Some(PuzzleSolution { puzzle: "".into(), answer: vec![0, 1, 2] }),
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_with_replayed_puzzle(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let challenge = get_challenge_helper(port, &site_key).await?;
+    let puzzle_token = challenge.puzzle.expect("challenge must have a puzzle");
+    let puzzle = puzzle::decode(&puzzle_token, &enc_key)
+        .expect("server returned invalid puzzle")
+        .other;
+    let results = ChallengeResults {
+        success: true,
+        challenge: challenge.url,
+        solution: Some(PuzzleSolution { puzzle: puzzle_token, answer: puzzle.solve() }),
+        interactions: human_interactions(),
+        action: None,
+        form_digest: None,
+    };
+
+    let process = || {
+        HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/challenge/process"))
+            .header("Origin", "http://website-integration.test.com")
+            .header("X-Site-Key", site_key.as_str())
+            .json(&results)
+            .send()
+    };
+    assert_eq!(process().await?.status(), StatusCode::OK);
+    assert_eq!(process().await?.status(), StatusCode::CONFLICT);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_failed_challenge(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: false,
+            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
+            solution: None,
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ChallengeResponse { token, .. } = response.json().await?;
+    eprintln!("{token}");
+    let token_data = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
+        &token,
+        &DecodingKey::from_base64_secret(enc_key.as_str())?,
+        &Validation::new(JWT_RESPONSE_ALGORITHM),
+    )?;
+    assert_eq!(token_data.header.alg, JWT_RESPONSE_ALGORITHM);
+    assert!(token_data.claims.other.score == 0.);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_with_invalid_secret(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", "bXktd3Jvbmctc2VjcmV0")
+        .json(&serde_json::json!({
+            "success": false,
+            // "site_key": "bXktd3Jvbmctc2VjcmV0", // `my-wrong-secret` in base64
+            "hostname": "website-integration.test.com",
+            "challenge": "https://gotcha-integration.test.com/im-not-a-robot/index.html",
+            "interactions": [],
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_wrong_hostname(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://wrong-website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+
+            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
+            solution: None,
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_wildcard_hostname(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        DbUpdateApiKey {
+            allowed_domains: Some(&["*.website-integration.test.com".parse()?]),
+            ..Default::default()
+        },
+    )
+    .await?;
+    // the ownership of a wildcard is proven on its base
+    for domain in db::fetch_api_key_domains(server.pool(), &site_key).await? {
+        server.ownership_resolver().publish_txt(
+            "_gotcha-verification.website-integration.test.com",
+            &domain.token.to_string(),
+        );
+    }
+    assert_eq!(server.check_api_key_domains().await, 1);
+
+    for (origin, status) in [
+        (
+            "http://preview.website-integration.test.com",
+            StatusCode::OK,
+        ),
+        (
+            "http://pr-1.preview.website-integration.test.com",
+            StatusCode::OK,
+        ),
+        ("http://website-integration.test.com", StatusCode::FORBIDDEN),
+        (
+            "http://badwebsite-integration.test.com",
+            StatusCode::FORBIDDEN,
+        ),
+    ] {
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/challenge/process"))
+            .header("Origin", origin)
+            .header("X-Site-Key", site_key.as_str())
+            .json(&ChallengeResults {
+                success: false,
+                challenge: Url::parse(
+                    "https://gotcha-integration.test.com/im-not-a-robot/index.html",
+                )?,
+                solution: None,
+                interactions: vec![],
+                action: None,
+                form_digest: None,
+            })
+            .send()
+            .await?;
+        assert_eq!(response.status(), status, "{origin}");
+    }
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_test_key(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        DbUpdateApiKey { mode: "test-pass".parse().ok(), ..Default::default() },
+    )
+    .await?;
+
+    // any origin is accepted and the puzzle and the proof of work aren't checked
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://any-website.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: false,
+            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
+            solution: None,
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response: ChallengeResponse = response.json().await?;
+    assert_eq!(response, ChallengeResponse::test_key());
+    assert_eq!(response.token, TEST_RESPONSE_TOKEN);
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-pre-analysis"
+        ))
+        .header("Origin", "http://localhost")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&PreAnalysisRequest {
+            interactions: vec![],
+            proof_of_work: ProofOfWork { challenge: "invalid".into(), solution: 0 },
+            clearance: None,
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response: PreAnalysisResponse = response.json().await?;
+    assert_eq!(
+        response,
+        PreAnalysisResponse::Success { response: ChallengeResponse::test_key() }
+    );
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-accessibility"
+        ))
+        .header("Origin", "http://any-website.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&AccessibilityRequest {
+            proof_of_work: ProofOfWork { challenge: "invalid".into(), solution: 0 },
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response: PreAnalysisResponse = response.json().await?;
+    assert_eq!(
+        response,
+        PreAnalysisResponse::Success { response: ChallengeResponse::test_key() }
+    );
+
+    Ok(())
+}
+
+async fn process_failed_challenge_helper(
+    port: u16,
+    site_key: &Base64<UrlSafe>,
+    origin: &str,
+) -> anyhow::Result<StatusCode> {
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", origin)
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: false,
+            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
+            solution: None,
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    Ok(response.status())
+}
+
+#[integration_test]
+async fn process_challenge_domain_ownership(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let origin = "https://example.com";
+
+    let domain =
+        db::insert_allowed_domain(server.pool(), &site_key, &"example.com".parse()?).await?;
+    assert_eq!(domain.verified_at, None);
+    assert_eq!(
+        process_failed_challenge_helper(port, &site_key, origin).await?,
+        StatusCode::FORBIDDEN
+    );
+
+    // another token doesn't prove the ownership
+    let resolver = server.ownership_resolver();
+    resolver.serve_well_known("example.com", &Uuid::new_v4().to_string());
+    resolver.publish_txt("_gotcha-verification.example.com", "some other record");
+    assert_eq!(server.check_api_key_domains().await, 0);
+    assert_eq!(
+        process_failed_challenge_helper(port, &site_key, origin).await?,
+        StatusCode::FORBIDDEN
+    );
+
+    resolver.publish_txt(
+        "_gotcha-verification.example.com",
+        &format!(" {} ", domain.token),
+    );
+    assert_eq!(server.check_api_key_domains().await, 1);
+    assert_eq!(
+        process_failed_challenge_helper(port, &site_key, origin).await?,
+        StatusCode::OK
+    );
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_domain_ownership_lost(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let origin = "http://website-integration.test.com";
+
+    let domains = db::fetch_api_key_domains(server.pool(), &site_key).await?;
+    let no_grace_period = DomainVerificationConfig { grace_period_secs: 0, ..Default::default() };
+
+    // the domain keeps being verified during the grace period even if the proof is gone
+    ownership::check_domains(
+        server.pool(),
+        server.ownership_resolver(),
+        &Default::default(),
+        &domains,
+    )
+    .await?;
+    assert_eq!(
+        process_failed_challenge_helper(port, &site_key, origin).await?,
+        StatusCode::OK
+    );
+
+    ownership::check_domains(
+        server.pool(),
+        server.ownership_resolver(),
+        &no_grace_period,
+        &domains,
+    )
+    .await?;
+    assert_eq!(
+        process_failed_challenge_helper(port, &site_key, origin).await?,
+        StatusCode::FORBIDDEN
+    );
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_challenge_exempt_domain_kept(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let origin = "http://website-integration.test.com";
+
+    // like a domain allowed before the verification existed
+    sqlx::query("update api_key_domain set exempt = true where site_key = $1")
+        .bind(site_key.as_str())
+        .execute(server.pool())
+        .await?;
+
+    let domains = db::fetch_api_key_domains(server.pool(), &site_key).await?;
+    let verified_count = ownership::check_domains(
+        server.pool(),
+        server.ownership_resolver(),
+        &DomainVerificationConfig { grace_period_secs: 0, ..Default::default() },
+        &domains,
+    )
+    .await?;
+    assert_eq!(verified_count, 0);
+    assert_eq!(
+        process_failed_challenge_helper(port, &site_key, origin).await?,
+        StatusCode::OK
+    );
+
+    // and it's never due to be checked
+    let due = db::claim_api_key_domains_to_check(server.pool(), 0, 0, i64::MAX).await?;
+    assert!(due.iter().all(|domain| domain.site_key != site_key));
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_pre_analysis_succeeds_but_with_failure(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
+    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
+        .expect("server returned invalid PoW");
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-pre-analysis"
+        ))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&PreAnalysisRequest {
+            interactions: vec![],
+            proof_of_work: ProofOfWork {
+                challenge: pow_res.token,
+                solution: pow_challenge.other.solve(),
+            },
+            clearance: None,
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response: PreAnalysisResponse = response.json().await?;
+    assert_eq!(response, PreAnalysisResponse::Failure);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_pre_analysis_after_encoding_key_rotation(
+    server: TestContext,
+) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
+    let pow_challenge =
+        pow_challenge::decode(&pow_res.token, &enc_key).expect("server returned invalid PoW");
+    let header = jsonwebtoken::decode_header(&pow_res.token)?;
+    assert_eq!(header.kid.as_deref(), Some("1"));
+
+    db::rotate_encoding_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        &Base64::<Standard>::random::<KEY_SIZE>(),
+        &(time::OffsetDateTime::now_utc() + time::Duration::minutes(5)),
+    )
+    .await?;
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-pre-analysis"
+        ))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&PreAnalysisRequest {
+            interactions: vec![],
+            proof_of_work: ProofOfWork {
+                challenge: pow_res.token,
+                solution: pow_challenge.other.solve(),
+            },
+            clearance: None,
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // new proofs of work are issued with the rotated key
+    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
+    let header = jsonwebtoken::decode_header(&pow_res.token)?;
+    assert_eq!(header.kid.as_deref(), Some("2"));
+    assert!(pow_challenge::decode(&pow_res.token, &enc_key).is_err());
+
+    Ok(())
+}
+
+async fn solve_challenge_helper(
+    port: u16,
+    site_key: &Base64<UrlSafe>,
+    enc_key: &Base64,
+) -> anyhow::Result<ChallengeResponse> {
+    let challenge = get_challenge_helper(port, site_key).await?;
+    let puzzle_token = challenge.puzzle.expect("challenge must have a puzzle");
+    let puzzle = puzzle::decode(&puzzle_token, enc_key)
+        .expect("server returned invalid puzzle")
+        .other;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+            challenge: challenge.url,
+            solution: Some(PuzzleSolution { puzzle: puzzle_token, answer: puzzle.solve() }),
+            interactions: human_interactions(),
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(response.json().await?)
+}
+
+async fn cleared_pre_analysis_helper(
+    port: u16,
+    site_key: &Base64<UrlSafe>,
+    enc_key: &Base64,
+    clearance: String,
+    user_agent: &str,
+) -> anyhow::Result<PreAnalysisResponse> {
+    let pow_res: PowResponse = get_pow_helper(port, site_key).await?;
+    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
+        .expect("server returned invalid PoW");
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-pre-analysis"
+        ))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .header("User-Agent", user_agent)
+        .json(&PreAnalysisRequest {
+            interactions: vec![],
+            proof_of_work: ProofOfWork {
+                challenge: pow_res.token,
+                solution: pow_challenge.other.solve(),
+            },
+            clearance: Some(clearance),
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(response.json().await?)
+}
+
+#[integration_test]
+async fn process_pre_analysis_with_clearance(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let response = solve_challenge_helper(port, &site_key, &enc_key).await?;
+    let clearance = response
+        .clearance
+        .expect("solved challenge must grant a clearance");
+
+    // without interactions the pre analysis fails, unless cleared
+    let response = cleared_pre_analysis_helper(
+        port,
+        &site_key,
+        &enc_key,
+        clearance.clone(),
+        "gotcha-server/0.1.0",
+    )
+    .await?;
+    let PreAnalysisResponse::Success { response: ChallengeResponse { token, clearance: None } } =
+        response
+    else {
+        panic!("expected pre analysis to be cleared: {response:?}");
+    };
+    let token_data = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
+        &token,
+        &DecodingKey::from_base64_secret(enc_key.as_str())?,
+        &Validation::new(JWT_RESPONSE_ALGORITHM),
+    )?;
+    assert!(token_data.claims.other.score >= 0.5);
+
+    // clearance is bound to the user agent
+    let response =
+        cleared_pre_analysis_helper(port, &site_key, &enc_key, clearance, "gotcha-server/0.2.0")
+            .await?;
+    assert_eq!(response, PreAnalysisResponse::Failure);
+
+    Ok(())
+}
+
 #[integration_test]
-async fn get_challenge(server: TestContext) -> anyhow::Result<()> {
+async fn process_challenge_without_clearance(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
 
-    let response = HTTP_CLIENT
-        .get(format!("http://localhost:{port}/api/challenge"))
-        .send()
-        .await?;
-    assert_eq!(response.status(), StatusCode::OK);
-    let _challenge: GetChallenge = response.json().await?;
+    let policy = ClearancePolicy { lifetime_secs: 0, ..Default::default() };
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        DbUpdateApiKey { clearance_policy: Some(&policy), ..Default::default() },
+    )
+    .await?;
+
+    let response = solve_challenge_helper(port, &site_key, &enc_key).await?;
+    assert_eq!(response.clearance, None);
 
     Ok(())
 }
 
-#[ignore = "TODO: create one db per test to isolate"]
 #[integration_test]
-async fn get_challenge_fails(server: TestContext) -> anyhow::Result<()> {
+async fn process_challenge_below_min_score_without_clearance(
+    server: TestContext,
+) -> anyhow::Result<()> {
     let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
 
-    let response = HTTP_CLIENT
-        .get(format!("http://localhost:{port}/api/challenge"))
-        .send()
-        .await?;
-    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    update_pre_analysis_policy(
+        &server,
+        PreAnalysisPolicy { min_score: 1., ..Default::default() },
+    )
+    .await?;
+
+    let response = solve_challenge_helper(port, &site_key, &enc_key).await?;
+    assert_eq!(response.clearance, None);
 
     Ok(())
 }
 
 #[integration_test]
-async fn get_all_challenges(server: TestContext) -> anyhow::Result<()> {
+async fn process_challenge_with_configured_response_ttl(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
 
-    let response = HTTP_CLIENT
-        .get(format!("http://localhost:{port}/api/challenge/all"))
-        .send()
-        .await?;
-    assert_eq!(response.status(), StatusCode::OK);
-    let _challenges: Vec<GetChallenge> = response.json().await?;
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        DbUpdateApiKey { response_ttl_secs: Some(120), ..Default::default() },
+    )
+    .await?;
+
+    let ChallengeResponse { token, .. } = solve_challenge_helper(port, &site_key, &enc_key).await?;
+    let claims = response::decode(&token, &enc_key)?;
+    assert_eq!(*claims.exp() - *claims.iat(), time::Duration::seconds(120));
+
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    let PreAnalysisResponse::Success { response: ChallengeResponse { token, .. } } = response
+    else {
+        panic!("expected pre analysis to succeed: {response:?}");
+    };
+    let claims = response::decode(&token, &enc_key)?;
+    assert_eq!(*claims.exp() - *claims.iat(), time::Duration::seconds(120));
 
     Ok(())
 }
 
-// This test overtime gets more meaningless and untestable
 #[integration_test]
-async fn process_successful_challenge(server: TestContext) -> anyhow::Result<()> {
+async fn process_challenge_with_asymmetric_signing(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
     let site_key = server.db_api_site_key().await;
     let enc_key = server.db_enconding_key().await;
 
-    let response = HTTP_CLIENT
-        .post(format!("http://localhost:{port}/api/challenge/process"))
-        .header("Origin", "http://website-integration.test.com")
-        .header("X-Site-Key", site_key.as_str())
-        .json(&ChallengeResults {
-            success: true,
-            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
-            interactions: vec![],
-        })
+    let key_pair = SigningKeyPair::generate(SigningAlgorithm::ES256).expect("asymmetric algorithm");
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        DbUpdateApiKey {
+            signing_algorithm: Some(SigningAlgorithm::ES256),
+            signing_key: Some(&key_pair),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let ChallengeResponse { token, .. } = solve_challenge_helper(port, &site_key, &enc_key).await?;
+    assert!(response::decode(&token, &enc_key).is_err());
+
+    // verifies the token offline with the published public key
+    let jwks: JwkSet = HTTP_CLIENT
+        .get(format!("http://localhost:{port}/api/jwks/{site_key}"))
         .send()
+        .await?
+        .json()
         .await?;
-    assert_eq!(response.status(), StatusCode::OK);
-
-    let ChallengeResponse { token } = response.json().await?;
-    eprintln!("{token}");
-    let token_data = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
+    let header = jsonwebtoken::decode_header(&token)?;
+    let kid = header.kid.expect("token must have a kid");
+    assert_eq!(kid, key_pair.kid());
+    let jwk = jwks
+        .find(&kid)
+        .expect("jwks must have the key of the token");
+
+    let mut validation = Validation::new(jsonwebtoken::Algorithm::ES256);
+    TimeClaims::<ResponseClaims>::build_validation(&mut validation);
+    let claims = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
         &token,
-        &DecodingKey::from_base64_secret(enc_key.as_str())?,
-        &Validation::new(JWT_RESPONSE_ALGORITHM),
+        &DecodingKey::from_jwk(jwk)?,
+        &validation,
     )?;
-    assert_eq!(token_data.header.alg, JWT_RESPONSE_ALGORITHM);
-    // assert!(token_data.claims.custom.score >= 0.5);
+    assert_eq!(
+        claims.claims.other.host,
+        "website-integration.test.com".parse()?
+    );
 
     Ok(())
 }
 
-#[integration_test]
-async fn process_failed_challenge(server: TestContext) -> anyhow::Result<()> {
-    let port = server.port();
-    let site_key = server.db_api_site_key().await;
-    let enc_key = server.db_enconding_key().await;
+async fn pre_analysis_helper(
+    port: u16,
+    site_key: &Base64<UrlSafe>,
+    enc_key: &Base64,
+    accept_language: Option<&str>,
+) -> anyhow::Result<PreAnalysisResponse> {
+    let pow_res: PowResponse = get_pow_helper(port, site_key).await?;
+    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
+        .expect("server returned invalid PoW");
 
-    let response = HTTP_CLIENT
-        .post(format!("http://localhost:{port}/api/challenge/process"))
+    let mut request = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-pre-analysis"
+        ))
         .header("Origin", "http://website-integration.test.com")
-        .header("X-Site-Key", site_key.as_str())
-        .json(&ChallengeResults {
-            success: false,
-            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
-            interactions: vec![],
+        .header("X-Site-Key", site_key.as_str());
+    if let Some(accept_language) = accept_language {
+        request = request.header("Accept-Language", accept_language);
+    }
+    let response = request
+        .json(&PreAnalysisRequest {
+            interactions: human_interactions(),
+            proof_of_work: ProofOfWork {
+                challenge: pow_res.token,
+                solution: pow_challenge.other.solve(),
+            },
+            clearance: None,
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
     assert_eq!(response.status(), StatusCode::OK);
 
-    let ChallengeResponse { token } = response.json().await?;
-    eprintln!("{token}");
+    Ok(response.json().await?)
+}
+
+async fn update_pre_analysis_policy(
+    server: &TestContext,
+    policy: PreAnalysisPolicy,
+) -> anyhow::Result<()> {
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &server.db_api_site_key().await,
+        &server.db_console().await,
+        DbUpdateApiKey { pre_analysis_policy: Some(&policy), ..Default::default() },
+    )
+    .await?;
+    Ok(())
+}
+
+#[integration_test]
+async fn process_pre_analysis_success(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    let PreAnalysisResponse::Success { response: ChallengeResponse { token, .. } } = response
+    else {
+        panic!("expected pre analysis to succeed: {response:?}");
+    };
     let token_data = jsonwebtoken::decode::<TimeClaims<ResponseClaims>>(
         &token,
         &DecodingKey::from_base64_secret(enc_key.as_str())?,
         &Validation::new(JWT_RESPONSE_ALGORITHM),
     )?;
-    assert_eq!(token_data.header.alg, JWT_RESPONSE_ALGORITHM);
-    assert!(token_data.claims.other.score == 0.);
+    assert!(token_data.claims.other.score >= PreAnalysisPolicy::default().min_score);
 
     Ok(())
 }
 
 #[integration_test]
-async fn process_challenge_with_invalid_secret(server: TestContext) -> anyhow::Result<()> {
+async fn process_pre_analysis_fails_below_min_score(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
 
-    let response = HTTP_CLIENT
-        .post(format!("http://localhost:{port}/api/challenge/process"))
-        .header("Origin", "http://website-integration.test.com")
-        .header("X-Site-Key", "bXktd3Jvbmctc2VjcmV0")
-        .json(&serde_json::json!({
-            "success": false,
-            // "site_key": "bXktd3Jvbmctc2VjcmV0", // `my-wrong-secret` in base64
-            "hostname": "website-integration.test.com",
-            "challenge": "https://gotcha-integration.test.com/im-not-a-robot/index.html",
-            "interactions": [],
-        }))
-        .send()
-        .await?;
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    update_pre_analysis_policy(
+        &server,
+        PreAnalysisPolicy { min_score: 1., ..Default::default() },
+    )
+    .await?;
+
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    assert_eq!(response, PreAnalysisResponse::Failure);
 
     Ok(())
 }
 
 #[integration_test]
-async fn process_challenge_wrong_hostname(server: TestContext) -> anyhow::Result<()> {
+async fn process_pre_analysis_with_required_signals(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
     let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
 
-    let response = HTTP_CLIENT
-        .post(format!("http://localhost:{port}/api/challenge/process"))
-        .header("Origin", "http://wrong-website-integration.test.com")
-        .header("X-Site-Key", site_key.as_str())
-        .json(&ChallengeResults {
-            success: true,
+    update_pre_analysis_policy(
+        &server,
+        PreAnalysisPolicy { required_signals: vec![SignalKind::Headers], ..Default::default() },
+    )
+    .await?;
 
-            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
-            interactions: vec![],
-        })
-        .send()
-        .await?;
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = pre_analysis_helper(port, &site_key, &enc_key, None).await?;
+    assert_eq!(response, PreAnalysisResponse::Failure);
+
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    assert!(matches!(response, PreAnalysisResponse::Success { .. }));
 
     Ok(())
 }
 
 #[integration_test]
-async fn process_pre_analysis_succeeds_but_with_failure(server: TestContext) -> anyhow::Result<()> {
+async fn process_pre_analysis_within_max_pass_rate(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
     let site_key = server.db_api_site_key().await;
     let enc_key = server.db_enconding_key().await;
 
-    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
-    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
-        .expect("server returned invalid PoW");
+    update_pre_analysis_policy(
+        &server,
+        PreAnalysisPolicy { max_pass_rate: 0.5, ..Default::default() },
+    )
+    .await?;
 
-    let response = HTTP_CLIENT
-        .post(format!(
-            "http://localhost:{port}/api/challenge/process-pre-analysis"
-        ))
-        .header("Origin", "http://website-integration.test.com")
-        .header("X-Site-Key", site_key.as_str())
-        .json(&PreAnalysisRequest {
-            interactions: vec![],
-            proof_of_work: ProofOfWork {
-                challenge: pow_res.token,
-                solution: pow_challenge.solve(),
-            },
-        })
-        .send()
-        .await?;
-    assert_eq!(response.status(), StatusCode::OK);
+    // the first attempt of the window passes, then every other one
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    assert!(matches!(response, PreAnalysisResponse::Success { .. }));
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    assert_eq!(response, PreAnalysisResponse::Failure);
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    assert!(matches!(response, PreAnalysisResponse::Success { .. }));
 
-    let response: PreAnalysisResponse = response.json().await?;
+    Ok(())
+}
+
+#[integration_test]
+async fn process_pre_analysis_rejected_attempts_dont_grow_pass_rate(
+    server: TestContext,
+) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    update_pre_analysis_policy(
+        &server,
+        PreAnalysisPolicy {
+            required_signals: vec![SignalKind::Headers],
+            max_pass_rate: 0.5,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    for _ in 0..2 {
+        let response = pre_analysis_helper(port, &site_key, &enc_key, None).await?;
+        assert_eq!(response, PreAnalysisResponse::Failure);
+    }
+
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
+    assert!(matches!(response, PreAnalysisResponse::Success { .. }));
+    let response = pre_analysis_helper(port, &site_key, &enc_key, Some("en-US")).await?;
     assert_eq!(response, PreAnalysisResponse::Failure);
 
     Ok(())
@@ -227,6 +1232,9 @@ async fn process_pre_analysis_fails_on_invalid_proof_of_work(
         .json(&PreAnalysisRequest {
             interactions: vec![],
             proof_of_work: ProofOfWork { challenge: "".into(), solution: 0 },
+            clearance: None,
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
@@ -253,6 +1261,9 @@ async fn process_pre_analysis_fails_on_proof_of_work_failed(
         .json(&PreAnalysisRequest {
             interactions: vec![],
             proof_of_work: ProofOfWork { challenge: pow.token, solution: 0 },
+            clearance: None,
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
@@ -281,8 +1292,11 @@ async fn process_pre_analysis_with_wrong_hostname(server: TestContext) -> anyhow
             interactions: vec![],
             proof_of_work: ProofOfWork {
                 challenge: pow_res.token,
-                solution: pow_challenge.solve(),
+                solution: pow_challenge.other.solve(),
             },
+            clearance: None,
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
@@ -310,8 +1324,58 @@ async fn process_accessibility_success(server: TestContext) -> anyhow::Result<()
         .json(&AccessibilityRequest {
             proof_of_work: ProofOfWork {
                 challenge: pow_res.token,
-                solution: pow_challenge.solve(),
+                solution: pow_challenge.other.solve(),
+            },
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response: PreAnalysisResponse = response.json().await?;
+    assert!(matches!(response, PreAnalysisResponse::Success { .. }));
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_accessibility_success_with_argon2id(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        DbUpdateApiKey {
+            pow_difficulty: Some(2),
+            pow_algorithm: Some(PowAlgorithm::Argon2id),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
+    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
+        .expect("server returned invalid PoW");
+    assert_eq!(pow_challenge.other.algorithm, PowAlgorithm::Argon2id);
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-accessibility"
+        ))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&AccessibilityRequest {
+            proof_of_work: ProofOfWork {
+                challenge: pow_res.token,
+                solution: pow_challenge.other.solve(),
             },
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
@@ -338,6 +1402,8 @@ async fn process_accessibility_fails_on_invalid_proof_of_work(
         .header("X-Site-Key", site_key.as_str())
         .json(&AccessibilityRequest {
             proof_of_work: ProofOfWork { challenge: "".into(), solution: 0 },
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
@@ -363,6 +1429,8 @@ async fn process_accessibility_fails_on_proof_of_work_failed(
         .header("X-Site-Key", site_key.as_str())
         .json(&AccessibilityRequest {
             proof_of_work: ProofOfWork { challenge: pow.token, solution: 0 },
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
@@ -390,8 +1458,10 @@ async fn process_accessibility_with_wrong_hostname(server: TestContext) -> anyho
         .json(&AccessibilityRequest {
             proof_of_work: ProofOfWork {
                 challenge: pow_res.token,
-                solution: pow_challenge.solve(),
+                solution: pow_challenge.other.solve(),
             },
+            action: None,
+            form_digest: None,
         })
         .send()
         .await?;
@@ -420,6 +1490,31 @@ async fn get_proof_of_work_challenge(server: TestContext) -> anyhow::Result<()>
     Ok(())
 }
 
+#[integration_test]
+async fn get_proof_of_work_challenge_with_configured_difficulty(
+    server: TestContext,
+) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    db::update_api_key(
+        server.pool(),
+        server.master_key(),
+        &site_key,
+        &server.db_console().await,
+        DbUpdateApiKey { pow_difficulty: Some(4), ..Default::default() },
+    )
+    .await?;
+
+    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
+    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
+        .expect("server returned invalid PoW");
+    assert_eq!(pow_challenge.other.difficulty, 4);
+
+    Ok(())
+}
+
 #[integration_test]
 async fn get_proof_of_work_challenge_no_site_key(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
@@ -434,3 +1529,87 @@ async fn get_proof_of_work_challenge_no_site_key(server: TestContext) -> anyhow:
 
     Ok(())
 }
+
+#[integration_test]
+async fn process_accessibility_fails_on_replayed_proof_of_work(
+    server: TestContext,
+) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
+    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
+        .expect("server returned invalid PoW");
+    let solution = pow_challenge.other.solve();
+
+    for expected_status in [StatusCode::OK, StatusCode::CONFLICT] {
+        let response = HTTP_CLIENT
+            .post(format!(
+                "http://localhost:{port}/api/challenge/process-accessibility"
+            ))
+            .header("Origin", "http://website-integration.test.com")
+            .header("X-Site-Key", site_key.as_str())
+            .json(&AccessibilityRequest {
+                proof_of_work: ProofOfWork { challenge: pow_res.token.clone(), solution },
+                action: None,
+                form_digest: None,
+            })
+            .send()
+            .await?;
+        assert_eq!(response.status(), expected_status);
+    }
+
+    Ok(())
+}
+
+#[integration_test]
+async fn process_pre_analysis_fails_on_proof_of_work_redeemed_elsewhere(
+    server: TestContext,
+) -> anyhow::Result<()> {
+    let port = server.port();
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let pow_res: PowResponse = get_pow_helper(port, &site_key).await?;
+    let pow_challenge = pow_challenge::decode(&pow_res.token, enc_key.as_str())
+        .expect("server returned invalid PoW");
+    let proof_of_work = || ProofOfWork {
+        challenge: pow_res.token.clone(),
+        solution: pow_challenge.other.solve(),
+    };
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-accessibility"
+        ))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&AccessibilityRequest {
+            proof_of_work: proof_of_work(),
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-pre-analysis"
+        ))
+        .header("Origin", "http://website-integration.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&PreAnalysisRequest {
+            interactions: vec![],
+            proof_of_work: proof_of_work(),
+            clearance: None,
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    Ok(())
+}
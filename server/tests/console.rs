@@ -1,13 +1,17 @@
 use gotcha_server::{
     HTTP_CLIENT,
+    analysis::proof_of_work::PowAlgorithm,
     db::{self, DbChallengeCustomization, RowsAffected},
     encodings::{Base64, KEY_SIZE, UrlSafe},
     routes::console::{
-        ApiKeyChallengePoolResponse, ApiKeyResponse, ChallengeApiKeyPoolRequest,
-        ChallengePreferences, ConsoleResponse, CreateConsoleRequest, UpdateApiKeyRequest,
-        UpdateConsoleRequest,
+        AllowedDomainRequest, AllowedDomainResponse, ApiKeyAllowedDomainsResponse,
+        ApiKeyChallengePoolResponse, ApiKeyResponse, ApiKeySecretResponse,
+        ChallengeApiKeyPoolRequest, ChallengePreferences, ConsoleResponse,
+        CreateApiKeySecretRequest, CreateConsoleRequest, DomainStatus, RotateEncodingKeyRequest,
+        RotateEncodingKeyResponse, UpdateApiKeyRequest, UpdateConsoleRequest,
     },
     test_helpers,
+    tokens::{clearance::ClearancePolicy, signing::SigningAlgorithm},
 };
 use gotcha_server_macros::integration_test;
 use rand::distr::{Alphanumeric, SampleString};
@@ -193,7 +197,7 @@ async fn gen_api_key(server: TestContext) -> anyhow::Result<()> {
     let ApiKeyResponse { site_key, .. } = response.json().await?;
     assert_eq!(site_key.as_str().len(), KEY_SIZE * 4 / 3);
 
-    let db_res = db::fetch_api_keys(pool, &console_id).await?;
+    let db_res = db::fetch_api_keys(pool, server.master_key(), &console_id).await?;
     assert!(db_res.iter().any(|k| k.site_key == site_key));
 
     Ok(())
@@ -249,7 +253,7 @@ async fn update_api_key(server: TestContext) -> anyhow::Result<()> {
             "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
         ))
         .bearer_auth(test_helpers::auth_jwt().await)
-        .json(&UpdateApiKeyRequest { label: Some("updated".into()), allowed_domains: None })
+        .json(&UpdateApiKeyRequest { label: Some("updated".into()), ..Default::default() })
         .send()
         .await?;
     assert_eq!(response.status(), StatusCode::OK);
@@ -257,6 +261,397 @@ async fn update_api_key(server: TestContext) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[integration_test]
+async fn update_api_key_pow_difficulty(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let response = HTTP_CLIENT
+        .patch(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&UpdateApiKeyRequest { pow_difficulty: Some(5), ..Default::default() })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let api_key = db::fetch_api_key_by_site_key(pool, server.master_key(), &site_key)
+        .await?
+        .expect("api key should exist");
+    assert_eq!(api_key.pow_difficulty, 5);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_pow_difficulty_out_of_range(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    // sha256-hex counts 4 bits per level of difficulty, up to 24 bits
+    for pow_difficulty in [0, 7, 33] {
+        let response = HTTP_CLIENT
+            .patch(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&UpdateApiKeyRequest {
+                pow_difficulty: Some(pow_difficulty),
+                ..Default::default()
+            })
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_pow_algorithm(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let update = async |request: UpdateApiKeyRequest| {
+        HTTP_CLIENT
+            .patch(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&request)
+            .send()
+            .await
+            .map(|response| response.status())
+    };
+
+    // the difficulty of sha256-hex would be too easy with sha256
+    let response = update(UpdateApiKeyRequest {
+        pow_algorithm: Some(PowAlgorithm::Sha256),
+        ..Default::default()
+    })
+    .await?;
+    assert_eq!(response, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // an argon2id hash is far more expensive than a sha256 one
+    let response = update(UpdateApiKeyRequest {
+        pow_algorithm: Some(PowAlgorithm::Argon2id),
+        pow_difficulty: Some(16),
+        ..Default::default()
+    })
+    .await?;
+    assert_eq!(response, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = update(UpdateApiKeyRequest {
+        pow_algorithm: Some(PowAlgorithm::Argon2id),
+        pow_difficulty: Some(2),
+        ..Default::default()
+    })
+    .await?;
+    assert_eq!(response, StatusCode::OK);
+
+    let response = update(UpdateApiKeyRequest {
+        pow_algorithm: Some(PowAlgorithm::Sha256),
+        pow_difficulty: Some(16),
+        ..Default::default()
+    })
+    .await?;
+    assert_eq!(response, StatusCode::OK);
+
+    let api_key = db::fetch_api_key_by_site_key(pool, server.master_key(), &site_key)
+        .await?
+        .expect("api key should exist");
+    assert_eq!(api_key.pow_algorithm, PowAlgorithm::Sha256);
+    assert_eq!(api_key.pow_difficulty, 16);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_min_score(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let response = HTTP_CLIENT
+        .patch(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&UpdateApiKeyRequest { min_score: Some(0.9), ..Default::default() })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let api_key = db::fetch_api_key_by_site_key(pool, server.master_key(), &site_key)
+        .await?
+        .expect("api key should exist");
+    assert_eq!(api_key.min_score, 0.9);
+
+    for min_score in [-0.1, 1.1] {
+        let response = HTTP_CLIENT
+            .patch(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&UpdateApiKeyRequest { min_score: Some(min_score), ..Default::default() })
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_response_ttl(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let response = HTTP_CLIENT
+        .patch(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&UpdateApiKeyRequest { response_ttl_secs: Some(300), ..Default::default() })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let api_key = db::fetch_api_key_by_site_key(pool, server.master_key(), &site_key)
+        .await?
+        .expect("api key should exist");
+    assert_eq!(api_key.response_ttl_secs, 300);
+
+    for response_ttl_secs in [0, 901] {
+        let response = HTTP_CLIENT
+            .patch(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&UpdateApiKeyRequest {
+                response_ttl_secs: Some(response_ttl_secs),
+                ..Default::default()
+            })
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_signing_algorithm(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    // whether the update replaces a keypair, whose public key is kept for the tokens in flight
+    let mut kid = None;
+    for (signing_algorithm, replaced) in [
+        (SigningAlgorithm::ES256, false),
+        (SigningAlgorithm::ES256, false),
+        (SigningAlgorithm::EdDSA, true),
+        (SigningAlgorithm::HS256, true),
+    ] {
+        let response = HTTP_CLIENT
+            .patch(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&UpdateApiKeyRequest {
+                signing_algorithm: Some(signing_algorithm),
+                ..Default::default()
+            })
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let api_key = db::fetch_api_key_by_site_key(pool, server.master_key(), &site_key)
+            .await?
+            .expect("api key should exist");
+        assert_eq!(api_key.signing_algorithm(), signing_algorithm);
+        assert_eq!(
+            api_key.signing_key.is_some(),
+            signing_algorithm != SigningAlgorithm::HS256
+        );
+
+        let current_kid = api_key.signing_key.as_ref().map(|key_pair| key_pair.kid());
+        let previous_kid = api_key.previous_verifying_key.as_ref().map(|key| key.kid());
+        match replaced {
+            true => {
+                assert_ne!(current_kid, kid);
+                assert_eq!(previous_kid, kid);
+            }
+            false => {
+                assert!(kid.is_none() || current_kid == kid);
+                assert_eq!(previous_kid, None);
+            }
+        }
+        kid = current_kid;
+    }
+
+    Ok(())
+}
+
+#[integration_test]
+async fn rotate_encoding_key(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+    let enc_key = server.db_enconding_key().await;
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/rotate-encoding-key"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&RotateEncodingKeyRequest { grace_period_secs: Some(600) })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let rotation: RotateEncodingKeyResponse = response.json().await?;
+    assert_eq!(rotation.encoding_key_version, 2);
+
+    let api_key = db::fetch_api_key_by_site_key(pool, server.master_key(), &site_key)
+        .await?
+        .expect("api key should exist");
+    assert_eq!(api_key.encoding_key_version, 2);
+    assert_ne!(api_key.encoding_key, enc_key);
+    assert_eq!(api_key.previous_encoding_key, Some(enc_key));
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/rotate-encoding-key"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&RotateEncodingKeyRequest { grace_period_secs: Some(86_401) })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn api_key_secrets(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+    let secret = server.db_api_secret().await;
+
+    let response = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/secrets"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&CreateApiKeySecretRequest { label: Some("billing".into()) })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: ApiKeySecretResponse = response.json().await?;
+    assert_eq!(created.label.as_deref(), Some("billing"));
+    let created_secret = created.secret.expect("secret is returned on creation");
+    assert_ne!(created_secret, secret);
+    assert!(created_secret.as_str().ends_with(&created.secret_hint));
+
+    let secrets: Vec<ApiKeySecretResponse> = HTTP_CLIENT
+        .get(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/secrets"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(secrets.len(), 2);
+    assert!(secrets.iter().all(|s| s.revoked_at.is_none()));
+    // and never shown again
+    assert!(secrets.iter().all(|s| s.secret.is_none()));
+
+    let auth_jwt = test_helpers::auth_jwt().await;
+    let revoke = || {
+        HTTP_CLIENT
+            .delete(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/secrets/{}",
+                created.id
+            ))
+            .bearer_auth(auth_jwt)
+            .send()
+    };
+    assert_eq!(revoke().await?.status(), StatusCode::OK);
+    // already revoked
+    assert_eq!(revoke().await?.status(), StatusCode::NOT_FOUND);
+
+    assert!(
+        db::fetch_api_key_by_secret(pool, server.master_key(), &created_secret)
+            .await?
+            .is_none()
+    );
+    assert!(
+        db::fetch_api_key_by_secret(pool, server.master_key(), &secret)
+            .await?
+            .is_some()
+    );
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_clearance_policy(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let clearance_policy =
+        ClearancePolicy { lifetime_secs: 600, ipv4_prefix: 32, ..Default::default() };
+    let response = HTTP_CLIENT
+        .patch(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&UpdateApiKeyRequest {
+            clearance_policy: Some(clearance_policy.clone()),
+            ..Default::default()
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let api_key = db::fetch_api_key_by_site_key(pool, server.master_key(), &site_key)
+        .await?
+        .expect("api key should exist");
+    assert_eq!(api_key.clearance_policy, clearance_policy);
+
+    let response = HTTP_CLIENT
+        .patch(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&UpdateApiKeyRequest {
+            clearance_policy: Some(ClearancePolicy { lifetime_secs: 86_401, ..Default::default() }),
+            ..Default::default()
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    Ok(())
+}
+
 #[integration_test]
 async fn revoke_api_key(server: TestContext) -> anyhow::Result<()> {
     let port = server.port();
@@ -282,7 +677,7 @@ async fn revoke_api_key(server: TestContext) -> anyhow::Result<()> {
         .await?;
     assert_eq!(response.status(), StatusCode::OK);
 
-    let db_keys = db::fetch_api_keys(pool, &console_id).await?;
+    let db_keys = db::fetch_api_keys(pool, server.master_key(), &console_id).await?;
     assert!(db_keys.iter().all(|k| k.site_key != site_key));
 
     Ok(())
@@ -301,7 +696,7 @@ async fn update_forbidden_api_key(server: TestContext) -> anyhow::Result<()> {
             "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
         ))
         .bearer_auth(test_helpers::auth_jwt().await)
-        .json(&UpdateApiKeyRequest { label: Some("updated".into()), allowed_domains: None })
+        .json(&UpdateApiKeyRequest { label: Some("updated".into()), ..Default::default() })
         .send()
         .await?;
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
@@ -597,16 +992,255 @@ async fn remove_challenge_not_in_pool(server: TestContext) -> anyhow::Result<()>
     Ok(())
 }
 
-#[ignore = "TODO"]
+async fn get_allowed_domains_helper(
+    port: u16,
+    console_id: &Uuid,
+    site_key: &Base64<UrlSafe>,
+) -> anyhow::Result<Vec<String>> {
+    let ApiKeyAllowedDomainsResponse { domains } = HTTP_CLIENT
+        .get(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/allowed-domains"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .send()
+        .await?
+        .json()
+        .await?;
+    Ok(domains.iter().map(|d| d.domain.to_string()).collect())
+}
+
+#[integration_test]
+async fn add_allowed_domain(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    for domain in [
+        "bücher.example.com",
+        "*.Example.com",
+        "xn--bcher-kva.example.com",
+    ] {
+        let response = HTTP_CLIENT
+            .post(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/allowed-domains"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&AllowedDomainRequest { domain: domain.into() })
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let domains = get_allowed_domains_helper(port, &console_id, &site_key).await?;
+    assert_eq!(
+        domains,
+        [
+            "website-integration.test.com",
+            "xn--bcher-kva.example.com",
+            "*.example.com"
+        ]
+    );
+
+    Ok(())
+}
+
+#[integration_test]
+async fn verify_allowed_domain(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let AllowedDomainResponse { status, token, well_known_url, txt_record_name, .. } = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/allowed-domains"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&AllowedDomainRequest { domain: "*.example.com".into() })
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(status, DomainStatus::Pending);
+    assert_eq!(
+        well_known_url,
+        "https://example.com/.well-known/gotcha-verification.txt"
+    );
+    assert_eq!(txt_record_name, "_gotcha-verification.example.com");
+
+    server
+        .ownership_resolver()
+        .serve_well_known("example.com", &token.to_string());
+    let ApiKeyAllowedDomainsResponse { domains } = HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/allowed-domains/verify"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .send()
+        .await?
+        .json()
+        .await?;
+    let domain = domains
+        .iter()
+        .find(|d| d.domain.to_string() == "*.example.com")
+        .expect("domain should be listed");
+    assert_eq!(domain.status, DomainStatus::Verified);
+    assert!(domain.verified_at.is_some());
+
+    Ok(())
+}
+
 #[integration_test]
-async fn add_origin(_server: TestContext) -> anyhow::Result<()> {
-    todo!()
+async fn add_invalid_allowed_domain(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    for domain in [
+        "com",
+        "*.co.uk",
+        "github.io",
+        "127.0.0.1",
+        "www.*.example.com",
+        "exa mple.com",
+    ] {
+        let response = HTTP_CLIENT
+            .post(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/allowed-domains"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&AllowedDomainRequest { domain: domain.into() })
+            .send()
+            .await?;
+        assert_eq!(
+            response.status(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{domain}"
+        );
+        assert!(response.text().await?.contains(domain));
+    }
+
+    let domains = get_allowed_domains_helper(port, &console_id, &site_key).await?;
+    assert_eq!(domains, ["website-integration.test.com"]);
+
+    Ok(())
 }
 
-#[ignore = "TODO"]
 #[integration_test]
-async fn remove_origin(_server: TestContext) -> anyhow::Result<()> {
-    todo!()
+async fn remove_allowed_domain(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let remove_allowed_domain = async |domain: &str| {
+        HTTP_CLIENT
+            .delete(format!(
+                "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}/allowed-domains"
+            ))
+            .bearer_auth(test_helpers::auth_jwt().await)
+            .json(&AllowedDomainRequest { domain: domain.into() })
+            .send()
+            .await
+    };
+
+    let response = remove_allowed_domain("Website-Integration.test.com.").await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = remove_allowed_domain("website-integration.test.com").await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let domains = get_allowed_domains_helper(port, &console_id, &site_key).await?;
+    assert!(domains.is_empty());
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_invalid_allowed_domains(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    let response = HTTP_CLIENT
+        .patch(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&UpdateApiKeyRequest {
+            allowed_domains: Some(vec![
+                "example.com".into(),
+                "*.com".into(),
+                "10.0.0.1".into(),
+            ]),
+            ..Default::default()
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error = response.text().await?;
+    assert!(error.contains("`*.com`"));
+    assert!(error.contains("`10.0.0.1`"));
+    assert!(!error.contains("`example.com`"));
+
+    let domains = get_allowed_domains_helper(port, &console_id, &site_key).await?;
+    assert_eq!(domains, ["website-integration.test.com"]);
+
+    Ok(())
+}
+
+#[integration_test]
+async fn update_api_key_keeps_allowed_domains(server: TestContext) -> anyhow::Result<()> {
+    let port = server.port();
+    let pool = server.pool();
+    let console_id = server.db_console().await;
+    let site_key = server.db_api_site_key().await;
+
+    // like the domains allowed for local development when the api key was created
+    db::update_api_key(
+        pool,
+        server.master_key(),
+        &site_key,
+        &console_id,
+        db::DbUpdateApiKey {
+            allowed_domains: Some(&[
+                "website-integration.test.com".parse()?,
+                "localhost".parse()?,
+            ]),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let response = HTTP_CLIENT
+        .patch(format!(
+            "http://localhost:{port}/api/console/{console_id}/api-key/{site_key}"
+        ))
+        .bearer_auth(test_helpers::auth_jwt().await)
+        .json(&UpdateApiKeyRequest {
+            allowed_domains: Some(vec![
+                "localhost".into(),
+                "Website-Integration.test.com".into(),
+                "example.com".into(),
+            ]),
+            ..Default::default()
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let domains = db::fetch_api_key_domains(pool, &site_key).await?;
+    let status = domains
+        .iter()
+        .map(|d| (d.domain.to_string(), d.verified_at.is_some(), d.exempt))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        status,
+        [
+            ("website-integration.test.com".into(), true, false),
+            ("localhost".into(), true, true),
+            ("example.com".into(), false, false),
+        ]
+    );
+
+    Ok(())
 }
 
 async fn create_api_key_on_another_console(port: u16) -> anyhow::Result<(Uuid, Base64<UrlSafe>)> {
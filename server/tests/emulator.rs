@@ -0,0 +1,256 @@
+use std::net::SocketAddr;
+
+use gotcha_server::{
+    HTTP_CLIENT,
+    configuration::{self, EmulatorConfig, EmulatorKeyConfig},
+    encodings::{Base64, KEY_SIZE, Standard, UrlSafe},
+    routes::{
+        challenge::{
+            ChallengeResponse, ChallengeResults, PowResponse, PreAnalysisRequest,
+            PreAnalysisResponse, ProofOfWork,
+        },
+        verification::{ErrorCodes, VerificationResponse, WarningCodes},
+    },
+};
+use reqwest::StatusCode;
+use url::Url;
+
+/// Serves the emulator with the given keys, like `SITE_KEY:SECRET[:MODE]`, on a random port.
+async fn spawn_emulator(keys: &[String]) -> anyhow::Result<u16> {
+    let keys = keys
+        .iter()
+        .map(|key| key.parse::<EmulatorKeyConfig>())
+        .collect::<Result<_, _>>()
+        .map_err(anyhow::Error::msg)?;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            gotcha_server::emulator(EmulatorConfig { keys, ..Default::default() })
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+    Ok(port)
+}
+
+fn random_key() -> (Base64<UrlSafe>, Base64) {
+    (
+        Base64::<UrlSafe>::random::<KEY_SIZE>(),
+        Base64::<Standard>::random::<KEY_SIZE>(),
+    )
+}
+
+async fn pre_analysis_helper(
+    port: u16,
+    site_key: &Base64<UrlSafe>,
+    origin: &str,
+) -> anyhow::Result<reqwest::Response> {
+    let pow: PowResponse = HTTP_CLIENT
+        .get(format!(
+            "http://localhost:{port}/api/challenge/proof-of-work"
+        ))
+        .header("X-Site-Key", site_key.as_str())
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    Ok(HTTP_CLIENT
+        .post(format!(
+            "http://localhost:{port}/api/challenge/process-pre-analysis"
+        ))
+        .header("Origin", origin)
+        .header("X-Site-Key", site_key.as_str())
+        .json(&PreAnalysisRequest {
+            interactions: vec![],
+            // the emulator doesn't check the proof of work
+            proof_of_work: ProofOfWork { challenge: pow.token, solution: 0 },
+            clearance: None,
+            action: Some("login".parse()?),
+            form_digest: None,
+        })
+        .send()
+        .await?)
+}
+
+#[tokio::test]
+async fn solved_challenge() -> anyhow::Result<()> {
+    let (site_key, secret) = random_key();
+    let port = spawn_emulator(&[format!("{site_key}:{}", secret.as_str())]).await?;
+
+    let response = pre_analysis_helper(port, &site_key, "http://localhost:3000").await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let PreAnalysisResponse::Success { response: ChallengeResponse { token, .. } } =
+        response.json().await?
+    else {
+        panic!("the emulator must pass the pre analysis");
+    };
+
+    let form = [
+        ("secret", secret.as_str()),
+        ("response", &token),
+        ("expected_action", "login"),
+    ];
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/siteverify"))
+        .form(&form)
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let verification: VerificationResponse = response.json().await?;
+    assert!(verification.success);
+    assert_eq!(verification.score, Some(0.9));
+    assert_eq!(verification.hostname, Some("localhost".parse()?));
+    assert_eq!(verification.action, Some("login".parse()?));
+    assert_eq!(verification.error_codes, None);
+    assert_eq!(verification.warning_codes, None);
+
+    // response tokens can only be verified once
+    let verification: VerificationResponse = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/siteverify"))
+        .form(&form)
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(!verification.success);
+    assert_eq!(
+        verification.error_codes,
+        Some(vec![ErrorCodes::TimeoutOrDuplicate])
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn domain_not_allowed() -> anyhow::Result<()> {
+    let (site_key, secret) = random_key();
+    let port = spawn_emulator(&[format!("{site_key}:{}", secret.as_str())]).await?;
+
+    let response = pre_analysis_helper(port, &site_key, "http://example.com").await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let (unknown_site_key, _) = random_key();
+    let response = pre_analysis_helper(port, &unknown_site_key, "http://localhost").await;
+    assert!(
+        response.is_err(),
+        "the proof of work needs a known site key"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_secret() -> anyhow::Result<()> {
+    let (site_key, secret) = random_key();
+    let port = spawn_emulator(&[format!("{site_key}:{}", secret.as_str())]).await?;
+
+    let (_, unknown_secret) = random_key();
+    let verification: VerificationResponse = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/siteverify"))
+        .form(&[("secret", unknown_secret.as_str()), ("response", "token")])
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(!verification.success);
+    assert_eq!(
+        verification.error_codes,
+        Some(vec![ErrorCodes::InvalidInputSecret])
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_key() -> anyhow::Result<()> {
+    let (site_key, secret) = random_key();
+    let port = spawn_emulator(&[format!("{site_key}:{}:test-fail", secret.as_str())]).await?;
+
+    let response = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/challenge/process"))
+        .header("Origin", "http://any-website.test.com")
+        .header("X-Site-Key", site_key.as_str())
+        .json(&ChallengeResults {
+            success: true,
+            challenge: Url::parse("https://gotcha-integration.test.com/im-not-a-robot/index.html")?,
+            solution: None,
+            interactions: vec![],
+            action: None,
+            form_digest: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response: ChallengeResponse = response.json().await?;
+    assert_eq!(response, ChallengeResponse::test_key());
+
+    let verification: VerificationResponse = HTTP_CLIENT
+        .post(format!("http://localhost:{port}/api/siteverify"))
+        .form(&[("secret", secret.as_str()), ("response", &response.token)])
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(!verification.success);
+    assert_eq!(
+        verification.error_codes,
+        Some(vec![ErrorCodes::InvalidInputResponse])
+    );
+    assert_eq!(
+        verification.warning_codes,
+        Some(vec![WarningCodes::TestKey])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn yaml_configuration() -> anyhow::Result<()> {
+    let (site_key, secret) = random_key();
+    let path = std::env::temp_dir().join(format!("gotcha-emulator-{}.yaml", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        format!(
+            "port: 5001
+keys:
+  - site_key: {site_key}
+    secret: {secret}
+  - site_key: {site_key}
+    secret: {secret}
+    mode: test-pass
+    allowed_domains: [\"*.example.com\"]
+    score: 0.2
+",
+            secret = secret.as_str()
+        ),
+    )?;
+    let config = configuration::get_emulator_configuration(Some(&path));
+    std::fs::remove_file(&path)?;
+    let config = config?;
+
+    assert_eq!(config.port, 5001);
+    let [live, test] = &config.keys[..] else {
+        panic!("expected two keys: {:?}", config.keys);
+    };
+    assert_eq!(live.site_key, site_key);
+    assert_eq!(live.secret, secret);
+    assert_eq!(live.mode.to_string(), "live");
+    assert_eq!(live.score, 0.9);
+    assert_eq!(
+        live.allowed_domains
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        ["localhost", "127.0.0.1"]
+    );
+    assert_eq!(test.mode.to_string(), "test-pass");
+    assert_eq!(test.score, 0.2);
+    assert_eq!(test.allowed_domains[0].to_string(), "*.example.com");
+
+    Ok(())
+}
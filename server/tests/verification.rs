@@ -1,13 +1,25 @@
 mod verify_site {
-    use std::net::IpAddr;
+    use std::{collections::HashMap, net::IpAddr, time::Duration};
 
     use gotcha_server::{
         HTTP_CLIENT,
-        routes::verification::{ErrorCodes, VerificationResponse},
-        tokens::response::{self, ResponseClaims},
+        db::{self, DbUpdateApiKey},
+        encodings::{Base64, KEY_SIZE, Standard, UrlSafe},
+        routes::{
+            challenge::TEST_RESPONSE_TOKEN,
+            verification::{ErrorCodes, VerificationResponse, WarningCodes},
+        },
+        tokens::{
+            keys::VersionedKey,
+            response::{self, ResponseClaims},
+            signing::{SigningAlgorithm, SigningKeyPair},
+        },
     };
     use gotcha_server_macros::integration_test;
     use reqwest::StatusCode;
+    use secrecy::ExposeSecret;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
 
     #[integration_test]
     async fn sucessful_challenge(server: TestContext) -> anyhow::Result<()> {
@@ -16,11 +28,11 @@ mod verify_site {
         let enc_key = server.db_enconding_key().await;
 
         let token = response::encode(
-            ResponseClaims {
-                score: 0.75,
-                addr: [127, 0, 0, 1].into(),
-                host: "website-integration.test.com".parse()?,
-            },
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
             &enc_key,
         )?;
 
@@ -33,6 +45,46 @@ mod verify_site {
 
         let verification: VerificationResponse = response.json().await?;
         assert!(verification.success);
+        assert_eq!(verification.score, Some(0.75));
+        assert_eq!(verification.error_codes, None);
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn score_below_api_key_min_score(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        db::update_api_key(
+            server.pool(),
+            server.master_key(),
+            &server.db_api_site_key().await,
+            &server.db_console().await,
+            DbUpdateApiKey { min_score: Some(0.8), ..Default::default() },
+        )
+        .await?;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &enc_key,
+        )?;
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let verification: VerificationResponse = response.json().await?;
+        assert!(!verification.success);
+        assert_eq!(verification.score, Some(0.75));
         assert_eq!(verification.error_codes, None);
 
         Ok(())
@@ -46,7 +98,7 @@ mod verify_site {
         let addr = [127, 0, 0, 1].into();
 
         let token = response::encode(
-            ResponseClaims { score: 0.75, addr, host: "website-integration.test.com".parse()? },
+            ResponseClaims::new(0.75, addr, "website-integration.test.com".parse()?),
             &enc_key,
         )?;
 
@@ -75,11 +127,11 @@ mod verify_site {
         let enc_key = server.db_enconding_key().await;
 
         let token = response::encode(
-            ResponseClaims {
-                score: 0.75,
-                addr: [127, 0, 0, 1].into(),
-                host: "website-integration.test.com".parse()?,
-            },
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
             &enc_key,
         )?;
 
@@ -109,11 +161,11 @@ mod verify_site {
         let enc_key = server.db_enconding_key().await;
 
         let token = response::encode(
-            ResponseClaims {
-                score: 0.3,
-                addr: [127, 0, 0, 1].into(),
-                host: "website-integration.test.com".parse()?,
-            },
+            ResponseClaims::new(
+                0.3,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
             &enc_key,
         )?;
 
@@ -131,17 +183,214 @@ mod verify_site {
         Ok(())
     }
 
+    #[integration_test]
+    async fn matching_action(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            )
+            .with_action(Some("login".parse()?)),
+            &enc_key,
+        )?;
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[
+                ("secret", secret.as_str()),
+                ("response", &token),
+                ("expected_action", "login"),
+            ])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
+        assert_eq!(verification.action, Some("login".parse()?));
+        assert_eq!(verification.error_codes, None);
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn mismatching_action(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        for action in [Some("newsletter".parse()?), None] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )
+                .with_action(action.clone()),
+                &enc_key,
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    ("expected_action", "login"),
+                ])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert!(!verification.success);
+            assert_eq!(verification.action, action);
+            assert_eq!(
+                verification.error_codes,
+                Some(vec![ErrorCodes::ActionMismatch])
+            );
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn mismatching_action_keeps_token(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            )
+            .with_action(Some("login".parse()?)),
+            &enc_key,
+        )?;
+
+        for (expected_action, success) in [("newsletter", false), ("login", true)] {
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    ("expected_action", expected_action),
+                ])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert_eq!(verification.success, success, "{expected_action}");
+            assert_eq!(
+                verification.error_codes,
+                (!success).then(|| vec![ErrorCodes::ActionMismatch])
+            );
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn matching_form_data(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        // digest of `amount=10&email=user%40example.com`
+        let form_digest = "Y22obralVFus5MP3rmHVkmKsD_IpJfoDPW53Dzd2tfE";
+        for (field, value) in [
+            ("form_data", "email=user%40example.com&amount=10"),
+            ("form_digest", form_digest),
+        ] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )
+                .with_form_digest(Some(form_digest.parse()?)),
+                &enc_key,
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    (field, value),
+                ])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert!(verification.success, "{field}");
+            assert_eq!(verification.error_codes, None);
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn mismatching_form_data(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        for form_digest in [
+            Some("Y22obralVFus5MP3rmHVkmKsD_IpJfoDPW53Dzd2tfE".parse()?),
+            None,
+        ] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )
+                .with_form_digest(form_digest),
+                &enc_key,
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    ("form_data", "email=user%40example.com&amount=1000"),
+                ])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert!(!verification.success);
+            assert_eq!(
+                verification.error_codes,
+                Some(vec![ErrorCodes::FormDataMismatch])
+            );
+        }
+
+        Ok(())
+    }
+
     #[integration_test]
     async fn missing_secret(server: TestContext) -> anyhow::Result<()> {
         let port = server.port();
         let enc_key = server.db_enconding_key().await;
 
         let token = response::encode(
-            ResponseClaims {
-                score: 1.,
-                addr: [127, 0, 0, 1].into(),
-                host: "website-integration.test.com".parse()?,
-            },
+            ResponseClaims::new(
+                1.,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
             &enc_key,
         )?;
 
@@ -225,11 +474,11 @@ mod verify_site {
         let enc_key = server.db_enconding_key().await;
 
         let token = response::encode(
-            ResponseClaims {
-                score: 1.,
-                addr: [127, 0, 0, 1].into(),
-                host: "website-integration.test.com".parse()?,
-            },
+            ResponseClaims::new(
+                1.,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
             &enc_key,
         )?;
 
@@ -266,11 +515,11 @@ mod verify_site {
         let enc_key = server.db_enconding_key().await;
 
         let token = response::encode(
-            ResponseClaims {
-                score: 0.75,
-                addr: [127, 0, 0, 1].into(),
-                host: "website-integration.test.com".parse()?,
-            },
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
             &enc_key,
         )?;
 
@@ -322,83 +571,728 @@ mod verify_site {
     }
 
     #[integration_test]
-    async fn duplicate(_server: TestContext) -> anyhow::Result<()> {
-        // TODO
-        Ok(())
-    }
+    async fn duplicate(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
 
-    mod response_token {
-        use std::time::Duration;
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &enc_key,
+        )?;
 
-        use gotcha_server::tokens::TimeClaims;
-        use jsonwebtoken::{EncodingKey, Header};
-        use response::JWT_RESPONSE_ALGORITHM;
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
 
-        use super::*;
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
 
-        #[integration_test]
-        async fn expired_signature(server: TestContext) -> anyhow::Result<()> {
-            let port = server.port();
-            let secret = server.db_api_secret().await;
-            let enc_key = server.db_enconding_key().await;
+        let verification: VerificationResponse = response.json().await?;
+        assert!(!verification.success);
+        assert!(
+            verification
+                .error_codes
+                .expect("must have error codes")
+                .contains(&ErrorCodes::TimeoutOrDuplicate)
+        );
 
-            let token = response::encode_with_timeout(
-                ResponseClaims {
-                    score: 1.,
-                    addr: [127, 0, 0, 1].into(),
-                    host: "website-integration.test.com".parse()?,
-                },
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn widget_response_field(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        for field in ["g-recaptcha-response", "gotcha-response"] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                ),
                 &enc_key,
-                Duration::from_secs(0),
             )?;
-            // expired by 1 second
-            tokio::time::sleep(Duration::from_secs(1)).await;
 
             let response = HTTP_CLIENT
                 .post(format!("http://localhost:{port}/api/siteverify"))
-                .form(&[("secret", secret.as_str()), ("response", &token)])
+                .form(&[("secret", secret.as_str()), (field, &token)])
                 .send()
                 .await?;
             assert_eq!(response.status(), StatusCode::OK);
 
             let verification: VerificationResponse = response.json().await?;
-            assert!(!verification.success);
-            assert!(
-                verification
-                    .error_codes
-                    .expect("must have error codes")
-                    .contains(&ErrorCodes::TimeoutOrDuplicate)
-            );
-
-            Ok(())
+            assert!(verification.success, "{field}");
         }
 
-        #[integration_test]
-        async fn immature_signature(_server: TestContext) -> anyhow::Result<()> {
-            // TODO
-            Ok(())
-        }
+        Ok(())
+    }
 
-        #[integration_test]
-        async fn invalid_token(server: TestContext) -> anyhow::Result<()> {
-            let port = server.port();
-            let secret = server.db_api_secret().await;
+    #[integration_test]
+    async fn json_body(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &enc_key,
+        )?;
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .json(&HashMap::from([
+                ("secret", secret.as_str()),
+                ("response", &token),
+            ]))
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn sitekey_secret_mismatch(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let site_key = server.db_api_site_key().await;
+        let enc_key = server.db_enconding_key().await;
+
+        for (sitekey, success) in [
+            (site_key.as_str(), true),
+            (
+                "4BdwFU84HLqceCQbE90-U5mw7f0erayega3nFOYvp1T5qXd8IqnTHJfsh675Vb2q",
+                false,
+            ),
+        ] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                ),
+                &enc_key,
+            )?;
 
-            let token = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9..eyJleHAiOjE3MzAzMDIyNDYsInN1Y2Nlc3MiOnRydWV9.9VBstXEca0JEPksQbMOEXdL_MxBvjiDgLbp0JnfsXMw";
-            //                                                ^ extra dot
             let response = HTTP_CLIENT
                 .post(format!("http://localhost:{port}/api/siteverify"))
-                .form(&[("secret", secret.as_str()), ("response", token)])
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    ("sitekey", sitekey),
+                ])
                 .send()
                 .await?;
             assert_eq!(response.status(), StatusCode::OK);
 
             let verification: VerificationResponse = response.json().await?;
-            assert!(!verification.success);
-            assert!(
-                verification
-                    .error_codes
-                    .expect("must have error codes")
+            assert_eq!(verification.success, success);
+            if !success {
+                assert_eq!(
+                    verification.error_codes,
+                    Some(vec![ErrorCodes::SitekeySecretMismatch])
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn retry_with_idempotency_key(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &enc_key,
+        )?;
+        let idempotency_key = Uuid::new_v4().to_string();
+
+        for _ in 0..2 {
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    ("idempotency_key", &idempotency_key),
+                ])
+                .send()
+                .await?;
+            let verification: VerificationResponse = response.json().await?;
+            assert!(verification.success);
+        }
+
+        let other_idempotency_key = Uuid::new_v4().to_string();
+        for idempotency_key in [Some(other_idempotency_key.as_str()), None] {
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[
+                    ("secret", Some(secret.as_str())),
+                    ("response", Some(&token)),
+                    ("idempotency_key", idempotency_key),
+                ])
+                .send()
+                .await?;
+            let verification: VerificationResponse = response.json().await?;
+            assert!(!verification.success);
+            assert_eq!(
+                verification.error_codes,
+                Some(vec![ErrorCodes::TimeoutOrDuplicate])
+            );
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn asymmetric_signing(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+
+        let key_pair =
+            SigningKeyPair::generate(SigningAlgorithm::EdDSA).expect("asymmetric algorithm");
+        db::update_api_key(
+            server.pool(),
+            server.master_key(),
+            &server.db_api_site_key().await,
+            &server.db_console().await,
+            DbUpdateApiKey {
+                signing_algorithm: Some(SigningAlgorithm::EdDSA),
+                signing_key: Some(&key_pair),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let other_key_pair =
+            SigningKeyPair::generate(SigningAlgorithm::EdDSA).expect("asymmetric algorithm");
+        for (key_pair, success) in [(&key_pair, true), (&other_key_pair, false)] {
+            let token = response::encode_with_key_pair(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                ),
+                key_pair,
+                Duration::from_secs(30),
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[("secret", secret.as_str()), ("response", &token)])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert_eq!(verification.success, success);
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn asymmetric_signing_with_previous_key(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let site_key = server.db_api_site_key().await;
+        let console_id = server.db_console().await;
+
+        let previous_key_pair =
+            SigningKeyPair::generate(SigningAlgorithm::EdDSA).expect("asymmetric algorithm");
+        let key_pair =
+            SigningKeyPair::generate(SigningAlgorithm::ES256).expect("asymmetric algorithm");
+        for (key_pair, previous_expires_at) in [
+            (&previous_key_pair, None),
+            (
+                &key_pair,
+                Some(OffsetDateTime::now_utc() + Duration::from_secs(60)),
+            ),
+        ] {
+            db::update_api_key(
+                server.pool(),
+                server.master_key(),
+                &site_key,
+                &console_id,
+                DbUpdateApiKey {
+                    signing_algorithm: Some(key_pair.algorithm()),
+                    signing_key: Some(key_pair),
+                    previous_verifying_key_expires_at: previous_expires_at.as_ref(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        }
+
+        // tokens in flight signed by the replaced keypair are still verified
+        let token = response::encode_with_key_pair(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &previous_key_pair,
+            Duration::from_secs(30),
+        )?;
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
+
+        let jwks: jsonwebtoken::jwk::JwkSet = HTTP_CLIENT
+            .get(format!("http://localhost:{port}/api/jwks/{site_key}"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        for key_pair in [&key_pair, &previous_key_pair] {
+            assert!(jwks.find(&key_pair.kid()).is_some());
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn wildcard_domain(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let site_key = server.db_api_site_key().await;
+        db::update_api_key(
+            server.pool(),
+            server.master_key(),
+            &site_key,
+            &server.db_console().await,
+            DbUpdateApiKey {
+                allowed_domains: Some(&["*.test.com".parse()?]),
+                ..Default::default()
+            },
+        )
+        .await?;
+        // the ownership of a wildcard is proven on its base
+        for domain in db::fetch_api_key_domains(server.pool(), &site_key).await? {
+            server
+                .ownership_resolver()
+                .serve_well_known("test.com", &domain.token.to_string());
+        }
+        assert_eq!(server.check_api_key_domains().await, 1);
+
+        for (host, success) in [
+            ("website-integration.test.com", true),
+            ("preview.website-integration.test.com", true),
+            ("test.com", false),
+            ("website-integration.badtest.com", false),
+        ] {
+            let token = response::encode(
+                ResponseClaims::new(0.75, [127, 0, 0, 1].into(), host.parse()?),
+                &enc_key,
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[("secret", secret.as_str()), ("response", &token)])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert_eq!(verification.success, success, "{host}");
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn unverified_domain(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        db::insert_allowed_domain(
+            server.pool(),
+            &server.db_api_site_key().await,
+            &"example.com".parse()?,
+        )
+        .await?;
+
+        let token = response::encode(
+            ResponseClaims::new(0.75, [127, 0, 0, 1].into(), "example.com".parse()?),
+            &enc_key,
+        )?;
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let verification: VerificationResponse = response.json().await?;
+        assert!(!verification.success);
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn test_keys(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+
+        for (mode, success, error_codes) in [
+            ("test-pass", true, None),
+            (
+                "test-fail",
+                false,
+                Some(vec![ErrorCodes::InvalidInputResponse]),
+            ),
+        ] {
+            db::update_api_key(
+                server.pool(),
+                server.master_key(),
+                &server.db_api_site_key().await,
+                &server.db_console().await,
+                DbUpdateApiKey { mode: mode.parse().ok(), ..Default::default() },
+            )
+            .await?;
+
+            // the same response can be verified again, since it's never checked
+            for _ in 0..2 {
+                let response = HTTP_CLIENT
+                    .post(format!("http://localhost:{port}/api/siteverify"))
+                    .form(&[
+                        ("secret", secret.as_str()),
+                        ("response", TEST_RESPONSE_TOKEN),
+                    ])
+                    .send()
+                    .await?;
+                assert_eq!(response.status(), StatusCode::OK);
+
+                let verification: VerificationResponse = response.json().await?;
+                assert_eq!(verification.success, success, "{mode}");
+                assert_eq!(verification.error_codes, error_codes, "{mode}");
+                assert_eq!(
+                    verification.warning_codes,
+                    Some(vec![WarningCodes::TestKey]),
+                    "{mode}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn rotated_encoding_key(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let site_key = server.db_api_site_key().await;
+        let console_id = server.db_console().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let claims = || -> anyhow::Result<ResponseClaims> {
+            Ok(ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ))
+        };
+        let token = response::encode(claims()?, VersionedKey::new(1, &enc_key))?;
+        let retired_token = response::encode(claims()?, VersionedKey::new(1, &enc_key))?;
+
+        // tokens of the previous key are accepted during the grace period
+        let version = db::rotate_encoding_key(
+            server.pool(),
+            server.master_key(),
+            &site_key,
+            &console_id,
+            &Base64::<Standard>::random::<KEY_SIZE>(),
+            &(OffsetDateTime::now_utc() + Duration::from_secs(60)),
+        )
+        .await?;
+        assert_eq!(version, Some(2));
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
+
+        // and rejected once the key is retired
+        db::rotate_encoding_key(
+            server.pool(),
+            server.master_key(),
+            &site_key,
+            &console_id,
+            &Base64::<Standard>::random::<KEY_SIZE>(),
+            &OffsetDateTime::now_utc(),
+        )
+        .await?;
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &retired_token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let verification: VerificationResponse = response.json().await?;
+        assert!(!verification.success);
+        assert_eq!(
+            verification.error_codes,
+            Some(vec![ErrorCodes::InvalidInputResponse])
+        );
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn revoked_secret(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let site_key = server.db_api_site_key().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let other_secret = Base64::<Standard>::random::<KEY_SIZE>();
+        let other_secret_id = db::insert_api_key_secret(
+            server.pool(),
+            server.master_key(),
+            &site_key,
+            Some("other backend"),
+            &other_secret,
+        )
+        .await?
+        .id;
+        let token = || -> anyhow::Result<String> {
+            Ok(response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                ),
+                &enc_key,
+            )?)
+        };
+
+        // every active secret verifies the responses
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", other_secret.as_str()), ("response", &token()?)])
+            .send()
+            .await?;
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
+
+        let secrets = db::fetch_api_key_secrets(server.pool(), &site_key).await?;
+        let used = secrets.iter().find(|s| s.id == other_secret_id).unwrap();
+        assert!(used.last_used_at.is_some());
+
+        // until it's revoked, without affecting the other secrets
+        db::revoke_api_key_secret(server.pool(), &site_key, &other_secret_id).await?;
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", other_secret.as_str()), ("response", &token()?)])
+            .send()
+            .await?;
+        let verification: VerificationResponse = response.json().await?;
+        assert!(!verification.success);
+        assert_eq!(
+            verification.error_codes,
+            Some(vec![ErrorCodes::InvalidInputSecret])
+        );
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token()?)])
+            .send()
+            .await?;
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn plaintext_credentials(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let site_key = server.db_api_site_key().await;
+        let enc_key = Base64::<Standard>::random::<KEY_SIZE>();
+        let secret = Base64::<Standard>::random::<KEY_SIZE>();
+
+        let key_pair =
+            SigningKeyPair::generate(SigningAlgorithm::EdDSA).expect("asymmetric algorithm");
+
+        // credentials stored before they were protected at rest
+        sqlx::query(
+            "update api_key set encoding_key = $1, signing_algorithm = 'EdDSA', signing_key = $2,
+            verifying_key = $3 where site_key = $4",
+        )
+        .bind(enc_key.as_str())
+        .bind(key_pair.private_key_base64().expose_secret())
+        .bind(key_pair.public_key_base64())
+        .bind(site_key.as_str())
+        .execute(server.pool())
+        .await?;
+        sqlx::query(
+            "insert into api_key_secret (site_key, secret, secret_hint) values ($1, $2, '')",
+        )
+        .bind(site_key.as_str())
+        .bind(secret.as_str())
+        .execute(server.pool())
+        .await?;
+
+        let protected =
+            db::protect_plaintext_credentials(server.pool(), server.master_key()).await?;
+        assert!(protected >= 2);
+        assert_eq!(server.db_enconding_key().await, enc_key);
+        let api_key = db::fetch_api_key_by_site_key(server.pool(), server.master_key(), &site_key)
+            .await?
+            .expect("api key should exist");
+        assert_eq!(
+            api_key.signing_key.map(|signing_key| signing_key.kid()),
+            Some(key_pair.kid())
+        );
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &enc_key,
+        )?;
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/api/siteverify"))
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        let verification: VerificationResponse = response.json().await?;
+        assert!(verification.success);
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn jwks_of_unknown_site_key(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let site_key = Base64::<UrlSafe>::random::<KEY_SIZE>();
+
+        let response = HTTP_CLIENT
+            .get(format!("http://localhost:{port}/api/jwks/{site_key}"))
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    mod response_token {
+        use std::time::Duration;
+
+        use gotcha_server::tokens::TimeClaims;
+        use jsonwebtoken::{EncodingKey, Header};
+        use response::JWT_RESPONSE_ALGORITHM;
+
+        use super::*;
+
+        #[integration_test]
+        async fn expired_signature(server: TestContext) -> anyhow::Result<()> {
+            let port = server.port();
+            let secret = server.db_api_secret().await;
+            let enc_key = server.db_enconding_key().await;
+
+            let token = response::encode_with_timeout(
+                ResponseClaims::new(
+                    1.,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                ),
+                &enc_key,
+                Duration::from_secs(0),
+            )?;
+            // expired by 1 second
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[("secret", secret.as_str()), ("response", &token)])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert!(!verification.success);
+            assert!(
+                verification
+                    .error_codes
+                    .expect("must have error codes")
+                    .contains(&ErrorCodes::TimeoutOrDuplicate)
+            );
+
+            Ok(())
+        }
+
+        #[integration_test]
+        async fn immature_signature(_server: TestContext) -> anyhow::Result<()> {
+            // TODO
+            Ok(())
+        }
+
+        #[integration_test]
+        async fn invalid_token(server: TestContext) -> anyhow::Result<()> {
+            let port = server.port();
+            let secret = server.db_api_secret().await;
+
+            let token = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9..eyJleHAiOjE3MzAzMDIyNDYsInN1Y2Nlc3MiOnRydWV9.9VBstXEca0JEPksQbMOEXdL_MxBvjiDgLbp0JnfsXMw";
+            //                                                ^ extra dot
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/api/siteverify"))
+                .form(&[("secret", secret.as_str()), ("response", token)])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: VerificationResponse = response.json().await?;
+            assert!(!verification.success);
+            assert!(
+                verification
+                    .error_codes
+                    .expect("must have error codes")
                     .contains(&ErrorCodes::InvalidInputResponse)
             );
 
@@ -412,11 +1306,11 @@ mod verify_site {
 
             let token = jsonwebtoken::encode(
                 &Header::new(JWT_RESPONSE_ALGORITHM),
-                &TimeClaims::new(ResponseClaims {
-                    score: 1.,
-                    addr: [127, 0, 0, 1].into(),
-                    host: "website-integration.test.com".parse()?,
-                }),
+                &TimeClaims::new(ResponseClaims::new(
+                    1.,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )),
                 &EncodingKey::from_base64_secret(
                     "bXktd3Jvbmctc2VjcmV0", /* `my-wrong-secret` in base64 */
                 )?,
@@ -449,11 +1343,11 @@ mod verify_site {
 
             let token = jsonwebtoken::encode(
                 &Header::new(jsonwebtoken::Algorithm::HS512), // wrong algorithm
-                &TimeClaims::new(ResponseClaims {
-                    score: 1.,
-                    addr: [127, 0, 0, 1].into(),
-                    host: "website-integration.test.com".parse()?,
-                }),
+                &TimeClaims::new(ResponseClaims::new(
+                    1.,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )),
                 &EncodingKey::from_base64_secret(enc_key.as_str())?,
             )?;
 
@@ -503,3 +1397,380 @@ mod verify_site {
         }
     }
 }
+
+mod recaptcha_site_verify {
+    use gotcha_server::{
+        HTTP_CLIENT,
+        tokens::response::{self, ResponseClaims},
+    };
+    use gotcha_server_macros::integration_test;
+    use reqwest::{StatusCode, header::USER_AGENT};
+    use serde_json::{Value, json};
+    use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+    #[integration_test]
+    async fn sucessful_challenge(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            )
+            .with_action(Some("login".parse()?)),
+            &enc_key,
+        )?;
+
+        // server libraries use their own HTTP client
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/recaptcha/api/siteverify"))
+            .header(USER_AGENT, "python-requests/2.32.3")
+            .form(&[("secret", secret.as_str()), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut verification: Value = response.json().await?;
+        let challenge_ts = verification["challenge_ts"]
+            .as_str()
+            .expect("challenge_ts must be a string");
+        OffsetDateTime::parse(challenge_ts, &Rfc3339)?;
+        verification["challenge_ts"].take();
+        assert_eq!(
+            verification,
+            json!({
+                "success": true,
+                "score": 0.75,
+                "action": "login",
+                "challenge_ts": null,
+                "hostname": "website-integration.test.com",
+            })
+        );
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn invalid_secret(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &enc_key,
+        )?;
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/recaptcha/api/siteverify"))
+            .form(&[("secret", "bm90LXRoZS1zZWNyZXQ="), ("response", &token)])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let verification: Value = response.json().await?;
+        assert_eq!(
+            verification,
+            json!({ "success": false, "error-codes": ["invalid-input-secret"] })
+        );
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn missing_input(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/recaptcha/api/siteverify"))
+            .form::<[(&str, &str)]>(&[])
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let verification: Value = response.json().await?;
+        assert_eq!(
+            verification,
+            json!({
+                "success": false,
+                "error-codes": ["missing-input-secret", "missing-input-response"],
+            })
+        );
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn mismatches_as_recaptcha_codes(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        for (field, value, error_code) in [
+            ("expected_action", "signup", "invalid-input-response"),
+            ("form_data", "user=alice", "invalid-input-response"),
+            (
+                "sitekey",
+                "4BdwFU84HLqceCQbE90-U5mw7f0erayega3nFOYvp1T5qXd8IqnTHJfsh675Vb2q",
+                "bad-request",
+            ),
+        ] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )
+                .with_action(Some("login".parse()?)),
+                &enc_key,
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/recaptcha/api/siteverify"))
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    (field, value),
+                ])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: Value = response.json().await?;
+            assert_eq!(verification["success"], false);
+            assert_eq!(verification["error-codes"], json!([error_code]), "{field}");
+        }
+
+        Ok(())
+    }
+}
+
+mod hcaptcha_site_verify {
+    use gotcha_server::{
+        HTTP_CLIENT,
+        tokens::response::{self, ResponseClaims},
+    };
+    use gotcha_server_macros::integration_test;
+    use reqwest::StatusCode;
+    use serde_json::{Value, json};
+
+    #[integration_test]
+    async fn sucessful_challenge(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let site_key = server.db_api_site_key().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            ),
+            &enc_key,
+        )?;
+
+        let form = [
+            ("secret", secret.as_str()),
+            ("response", &token),
+            ("sitekey", site_key.as_str()),
+        ];
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/hcaptcha/siteverify"))
+            .form(&form)
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut verification: Value = response.json().await?;
+        assert!(verification["challenge_ts"].take().is_string());
+        assert_eq!(
+            verification,
+            json!({
+                "success": true,
+                "challenge_ts": null,
+                "hostname": "website-integration.test.com",
+            })
+        );
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/hcaptcha/siteverify"))
+            .form(&form)
+            .send()
+            .await?;
+        let verification: Value = response.json().await?;
+        assert_eq!(
+            verification,
+            json!({ "success": false, "error-codes": ["invalid-or-already-seen-response"] })
+        );
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn mismatches_as_hcaptcha_codes(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        for (field, value, error_code) in [
+            ("expected_action", "signup", "invalid-input-response"),
+            ("form_data", "user=alice", "invalid-input-response"),
+            (
+                "sitekey",
+                "4BdwFU84HLqceCQbE90-U5mw7f0erayega3nFOYvp1T5qXd8IqnTHJfsh675Vb2q",
+                "sitekey-secret-mismatch",
+            ),
+        ] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )
+                .with_action(Some("login".parse()?)),
+                &enc_key,
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/hcaptcha/siteverify"))
+                .form(&[
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    (field, value),
+                ])
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: Value = response.json().await?;
+            assert_eq!(verification["success"], false);
+            assert_eq!(verification["error-codes"], json!([error_code]), "{field}");
+        }
+
+        Ok(())
+    }
+}
+
+mod turnstile_site_verify {
+    use std::collections::HashMap;
+
+    use gotcha_server::{
+        HTTP_CLIENT,
+        tokens::response::{self, ResponseClaims},
+    };
+    use gotcha_server_macros::integration_test;
+    use reqwest::StatusCode;
+    use serde_json::{Value, json};
+    use uuid::Uuid;
+
+    #[integration_test]
+    async fn retried_challenge(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        let token = response::encode(
+            ResponseClaims::new(
+                0.75,
+                [127, 0, 0, 1].into(),
+                "website-integration.test.com".parse()?,
+            )
+            .with_action(Some("login".parse()?)),
+            &enc_key,
+        )?;
+        let idempotency_key = Uuid::new_v4().to_string();
+
+        for _ in 0..2 {
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/turnstile/v0/siteverify"))
+                .json(&HashMap::from([
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    ("idempotency_key", &idempotency_key),
+                ]))
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let mut verification: Value = response.json().await?;
+            assert!(verification["challenge_ts"].take().is_string());
+            assert_eq!(
+                verification,
+                json!({
+                    "success": true,
+                    "challenge_ts": null,
+                    "hostname": "website-integration.test.com",
+                    "action": "login",
+                    "error-codes": [],
+                })
+            );
+        }
+
+        let response = HTTP_CLIENT
+            .post(format!("http://localhost:{port}/turnstile/v0/siteverify"))
+            .json(&HashMap::from([
+                ("secret", secret.as_str()),
+                ("response", &token),
+            ]))
+            .send()
+            .await?;
+        let verification: Value = response.json().await?;
+        assert_eq!(
+            verification,
+            json!({ "success": false, "error-codes": ["timeout-or-duplicate"] })
+        );
+
+        Ok(())
+    }
+
+    #[integration_test]
+    async fn mismatches_as_turnstile_codes(server: TestContext) -> anyhow::Result<()> {
+        let port = server.port();
+        let secret = server.db_api_secret().await;
+        let enc_key = server.db_enconding_key().await;
+
+        for (field, value, error_code) in [
+            ("expected_action", "signup", "invalid-input-response"),
+            ("form_data", "user=alice", "invalid-input-response"),
+            (
+                "sitekey",
+                "4BdwFU84HLqceCQbE90-U5mw7f0erayega3nFOYvp1T5qXd8IqnTHJfsh675Vb2q",
+                "invalid-input-secret",
+            ),
+        ] {
+            let token = response::encode(
+                ResponseClaims::new(
+                    0.75,
+                    [127, 0, 0, 1].into(),
+                    "website-integration.test.com".parse()?,
+                )
+                .with_action(Some("login".parse()?)),
+                &enc_key,
+            )?;
+
+            let response = HTTP_CLIENT
+                .post(format!("http://localhost:{port}/turnstile/v0/siteverify"))
+                .json(&HashMap::from([
+                    ("secret", secret.as_str()),
+                    ("response", &token),
+                    (field, value),
+                ]))
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let verification: Value = response.json().await?;
+            assert_eq!(verification["success"], false);
+            assert_eq!(verification["error-codes"], json!([error_code]), "{field}");
+        }
+
+        Ok(())
+    }
+}
@@ -6,7 +6,10 @@ import "./styles.css";
 loadCss()
   .then(() => {
     // Expose the API globally
-    (window as any).gotcha = new GotchaCaptcha();
+    const gotcha = new GotchaCaptcha();
+    (window as any).gotcha = gotcha;
+    // drop-in replacement for pages written against reCAPTCHA's API
+    (window as any).grecaptcha ??= gotcha;
     const { onload } = getJsParams();
     onload?.();
   })
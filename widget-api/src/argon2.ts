@@ -0,0 +1,430 @@
+/**
+ * Argon2id ([RFC 9106](https://www.rfc-editor.org/rfc/rfc9106)) with a single lane, as the server hashes the
+ * proof of work, and BLAKE2b ([RFC 7693](https://www.rfc-editor.org/rfc/rfc7693)) which it is built on.
+ * 64-bit words are held as pairs of 32-bit words, the low one first.
+ */
+
+export type Argon2Params = {
+  memoryKib: number;
+  iterations: number;
+  hashLength: number;
+};
+
+const ARGON2_VERSION = 0x13;
+const ARGON2ID = 2;
+const SYNC_POINTS = 4;
+/** Words of 32 bits in a block of 1 KiB. */
+const BLOCK_WORDS = 256;
+/** Pseudo random values in a block of addresses. */
+const ADDRESSES_IN_BLOCK = 128;
+
+export function argon2id(
+  password: Uint8Array,
+  salt: Uint8Array,
+  { memoryKib, iterations, hashLength }: Argon2Params,
+): Uint8Array {
+  const lanes = 1;
+  const h0 = blake2b(
+    concat(
+      le32(lanes),
+      le32(hashLength),
+      le32(memoryKib),
+      le32(iterations),
+      le32(ARGON2_VERSION),
+      le32(ARGON2ID),
+      le32(password.length),
+      password,
+      le32(salt.length),
+      salt,
+      le32(0),
+      le32(0),
+    ),
+    64,
+  );
+
+  const segmentLength = Math.floor(memoryKib / (SYNC_POINTS * lanes));
+  const laneLength = segmentLength * SYNC_POINTS;
+  const memory = new Uint32Array(laneLength * BLOCK_WORDS);
+  for (let i = 0; i < 2; i++) {
+    memory.set(
+      bytesToWords(hashLong(concat(h0, le32(i), le32(0)), 1024)),
+      i * BLOCK_WORDS,
+    );
+  }
+
+  for (let pass = 0; pass < iterations; pass++) {
+    for (let slice = 0; slice < SYNC_POINTS; slice++) {
+      fillSegment(memory, pass, slice, segmentLength, iterations);
+    }
+  }
+
+  return hashLong(wordsToBytes(block(memory, laneLength - 1)), hashLength);
+}
+
+function fillSegment(
+  memory: Uint32Array,
+  pass: number,
+  slice: number,
+  segmentLength: number,
+  iterations: number,
+) {
+  const laneLength = segmentLength * SYNC_POINTS;
+  // Argon2id takes the reference blocks independently of the data in the first half of the first pass
+  const dataIndependent = pass === 0 && slice < SYNC_POINTS / 2;
+  const zero = new Uint32Array(BLOCK_WORDS);
+  const input = new Uint32Array(BLOCK_WORDS);
+  const addresses = new Uint32Array(BLOCK_WORDS);
+  input[0] = pass;
+  input[4] = slice;
+  input[6] = laneLength;
+  input[8] = iterations;
+  input[10] = ARGON2ID;
+  const nextAddresses = () => {
+    input[12] = input[12]! + 1;
+    fillBlock(zero, input, addresses, false);
+    fillBlock(zero, addresses, addresses, false);
+  };
+
+  let startingIndex = 0;
+  if (pass === 0 && slice === 0) {
+    // the first two blocks are already filled
+    startingIndex = 2;
+    if (dataIndependent) nextAddresses();
+  }
+
+  let currOffset = slice * segmentLength + startingIndex;
+  let prevOffset =
+    currOffset % laneLength === 0 ? currOffset + laneLength - 1 : currOffset - 1;
+  for (let i = startingIndex; i < segmentLength; i++, currOffset++, prevOffset++) {
+    if (currOffset % laneLength === 1) prevOffset = currOffset - 1;
+
+    let pseudoRand: number;
+    if (dataIndependent) {
+      if (i % ADDRESSES_IN_BLOCK === 0) nextAddresses();
+      pseudoRand = addresses[(i % ADDRESSES_IN_BLOCK) * 2]!;
+    } else {
+      pseudoRand = memory[prevOffset * BLOCK_WORDS]!;
+    }
+
+    // with a single lane, the reference block is always taken from it
+    const refOffset = referenceIndex(pass, slice, i, pseudoRand, segmentLength);
+    fillBlock(
+      block(memory, prevOffset),
+      block(memory, refOffset),
+      block(memory, currOffset),
+      pass > 0,
+    );
+  }
+}
+
+/** Index of the reference block within the lane, mapped from the pseudo random value. */
+function referenceIndex(
+  pass: number,
+  slice: number,
+  index: number,
+  pseudoRand: number,
+  segmentLength: number,
+): number {
+  const laneLength = segmentLength * SYNC_POINTS;
+  let areaSize: number;
+  let startPosition: number;
+  if (pass === 0) {
+    areaSize = slice * segmentLength + index - 1;
+    startPosition = 0;
+  } else {
+    areaSize = laneLength - segmentLength + index - 1;
+    startPosition = slice === SYNC_POINTS - 1 ? 0 : (slice + 1) * segmentLength;
+  }
+
+  mul32(pseudoRand, pseudoRand);
+  mul32(areaSize, mulHi);
+  const relativePosition = areaSize - 1 - mulHi;
+  return (startPosition + relativePosition) % laneLength;
+}
+
+const R = new Uint32Array(BLOCK_WORDS);
+const T = new Uint32Array(BLOCK_WORDS);
+
+/** Compression function `G` of Argon2, which fills the `next` block out of the `prev` and `ref` ones. */
+function fillBlock(
+  prev: Uint32Array,
+  ref: Uint32Array,
+  next: Uint32Array,
+  withXor: boolean,
+) {
+  for (let i = 0; i < BLOCK_WORDS; i++) {
+    R[i] = prev[i]! ^ ref[i]!;
+    T[i] = withXor ? R[i]! ^ next[i]! : R[i]!;
+  }
+  // rows of 16 consecutive words of 64 bits, then columns of pairs of words
+  for (let i = 0; i < 8; i++) {
+    permute(R, 16 * i, 2);
+  }
+  for (let i = 0; i < 8; i++) {
+    permute(R, 2 * i, 16);
+  }
+  for (let i = 0; i < BLOCK_WORDS; i++) {
+    next[i] = T[i]! ^ R[i]!;
+  }
+}
+
+/** Permutation `P` over 16 words of 64 bits, in pairs of words `stride` words apart from `start`. */
+function permute(v: Uint32Array, start: number, stride: number) {
+  const p0 = start;
+  const p2 = p0 + stride;
+  const p4 = p2 + stride;
+  const p6 = p4 + stride;
+  const p8 = p6 + stride;
+  const p10 = p8 + stride;
+  const p12 = p10 + stride;
+  const p14 = p12 + stride;
+  roundG(v, p0, p4, p8, p12);
+  roundG(v, p0 + 1, p4 + 1, p8 + 1, p12 + 1);
+  roundG(v, p2, p6, p10, p14);
+  roundG(v, p2 + 1, p6 + 1, p10 + 1, p14 + 1);
+  roundG(v, p0, p4 + 1, p10, p14 + 1);
+  roundG(v, p0 + 1, p6, p10 + 1, p12);
+  roundG(v, p2, p6 + 1, p8, p12 + 1);
+  roundG(v, p2 + 1, p4, p8 + 1, p14);
+}
+
+/** Mixing function of BLAKE2b with the multiplications of BlaMka and no message, with its rotations unrolled. */
+function roundG(v: Uint32Array, a: number, b: number, c: number, d: number) {
+  a *= 2;
+  b *= 2;
+  c *= 2;
+  d *= 2;
+  let lo: number;
+  let hi: number;
+
+  blaMka(v, a, b);
+  lo = v[d]! ^ v[a]!;
+  hi = v[d + 1]! ^ v[a + 1]!;
+  v[d] = hi;
+  v[d + 1] = lo;
+
+  blaMka(v, c, d);
+  lo = v[b]! ^ v[c]!;
+  hi = v[b + 1]! ^ v[c + 1]!;
+  v[b] = (lo >>> 24) | (hi << 8);
+  v[b + 1] = (hi >>> 24) | (lo << 8);
+
+  blaMka(v, a, b);
+  lo = v[d]! ^ v[a]!;
+  hi = v[d + 1]! ^ v[a + 1]!;
+  v[d] = (lo >>> 16) | (hi << 16);
+  v[d + 1] = (hi >>> 16) | (lo << 16);
+
+  blaMka(v, c, d);
+  lo = v[b]! ^ v[c]!;
+  hi = v[b + 1]! ^ v[c + 1]!;
+  v[b] = (hi >>> 31) | (lo << 1);
+  v[b + 1] = (lo >>> 31) | (hi << 1);
+}
+
+/** `v[a] = v[a] + v[b] + 2 * lo(v[a]) * lo(v[b])`, with `a` and `b` offsets of 32-bit words. */
+function blaMka(v: Uint32Array, a: number, b: number) {
+  const al = v[a]!;
+  const bl = v[b]!;
+  // 64-bit product of the low words, in halves of 16 bits, then doubled
+  const t0 = (al & 0xffff) * (bl & 0xffff);
+  const t1 = (al >>> 16) * (bl & 0xffff) + (t0 >>> 16);
+  const t2 = (al & 0xffff) * (bl >>> 16) + (t1 & 0xffff);
+  const ml = Math.imul(al, bl) >>> 0;
+  const mh = (al >>> 16) * (bl >>> 16) + (t1 >>> 16) + (t2 >>> 16);
+  const pl = (ml << 1) >>> 0;
+  const ph = ((mh << 1) | (ml >>> 31)) >>> 0;
+
+  let lo = al + bl;
+  let hi = v[a + 1]! + v[b + 1]! + (lo > 0xffffffff ? 1 : 0);
+  lo = (lo >>> 0) + pl;
+  hi += ph + (lo > 0xffffffff ? 1 : 0);
+  v[a] = lo;
+  v[a + 1] = hi;
+}
+
+let mulHi = 0;
+
+/** High word of the 64-bit product of two 32-bit words, into `mulHi`. */
+function mul32(a: number, b: number) {
+  const t0 = (a & 0xffff) * (b & 0xffff);
+  const t1 = (a >>> 16) * (b & 0xffff) + (t0 >>> 16);
+  const t2 = (a & 0xffff) * (b >>> 16) + (t1 & 0xffff);
+  mulHi = ((a >>> 16) * (b >>> 16) + (t1 >>> 16) + (t2 >>> 16)) >>> 0;
+}
+
+/** `v[x] = rotr(v[x] ^ v[y], n)` */
+function xorRotr(v: Uint32Array, x: number, y: number, n: number) {
+  const lo = v[2 * x]! ^ v[2 * y]!;
+  const hi = v[2 * x + 1]! ^ v[2 * y + 1]!;
+  if (n === 32) {
+    v[2 * x] = hi;
+    v[2 * x + 1] = lo;
+  } else if (n < 32) {
+    v[2 * x] = (lo >>> n) | (hi << (32 - n));
+    v[2 * x + 1] = (hi >>> n) | (lo << (32 - n));
+  } else {
+    v[2 * x] = (hi >>> (n - 32)) | (lo << (64 - n));
+    v[2 * x + 1] = (lo >>> (n - 32)) | (hi << (64 - n));
+  }
+}
+
+/** `v[a] = v[a] + v[b]` */
+function add64(v: Uint32Array, a: number, b: number) {
+  const lo = v[2 * a]! + v[2 * b]!;
+  v[2 * a] = lo;
+  v[2 * a + 1] = v[2 * a + 1]! + v[2 * b + 1]! + (lo > 0xffffffff ? 1 : 0);
+}
+
+/** `v[a] = v[a] + m[i]` */
+function add64Msg(v: Uint32Array, a: number, m: Uint32Array, i: number) {
+  const lo = v[2 * a]! + m[2 * i]!;
+  v[2 * a] = lo;
+  v[2 * a + 1] = v[2 * a + 1]! + m[2 * i + 1]! + (lo > 0xffffffff ? 1 : 0);
+}
+
+/** Variable length hash function `H'` of Argon2. */
+function hashLong(input: Uint8Array, length: number): Uint8Array {
+  const data = concat(le32(length), input);
+  if (length <= 64) return blake2b(data, length);
+
+  const out = new Uint8Array(length);
+  let v = blake2b(data, 64);
+  out.set(v.subarray(0, 32), 0);
+  let pos = 32;
+  while (length - pos > 64) {
+    v = blake2b(v, 64);
+    out.set(v.subarray(0, 32), pos);
+    pos += 32;
+  }
+  out.set(blake2b(v, length - pos), pos);
+  return out;
+}
+
+// prettier-ignore
+const BLAKE2B_IV = new Uint32Array([
+  0xf3bcc908, 0x6a09e667, 0x84caa73b, 0xbb67ae85,
+  0xfe94f82b, 0x3c6ef372, 0x5f1d36f1, 0xa54ff53a,
+  0xade682d1, 0x510e527f, 0x2b3e6c1f, 0x9b05688c,
+  0xfb41bd6b, 0x1f83d9ab, 0x137e2179, 0x5be0cd19,
+]);
+
+// prettier-ignore
+const SIGMA = [
+  [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+  [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+  [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+  [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+  [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+  [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+  [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+  [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+  [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+  [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/** Unkeyed BLAKE2b of `outLength` bytes. */
+export function blake2b(data: Uint8Array, outLength: number): Uint8Array {
+  const h = BLAKE2B_IV.slice();
+  h[0] = h[0]! ^ 0x01010000 ^ outLength;
+
+  const blocks = Math.max(1, Math.ceil(data.length / 128));
+  for (let i = 0; i < blocks; i++) {
+    const chunk = new Uint8Array(128);
+    chunk.set(data.subarray(i * 128, (i + 1) * 128));
+    const last = i === blocks - 1;
+    compress(h, bytesToWords(chunk), last ? data.length : (i + 1) * 128, last);
+  }
+
+  return wordsToBytes(h).slice(0, outLength);
+}
+
+const V = new Uint32Array(32);
+
+function compress(h: Uint32Array, m: Uint32Array, counter: number, last: boolean) {
+  V.set(h, 0);
+  V.set(BLAKE2B_IV, 16);
+  V[24] = V[24]! ^ (counter >>> 0);
+  V[25] = V[25]! ^ Math.floor(counter / 0x100000000);
+  if (last) {
+    V[28] = ~V[28]!;
+    V[29] = ~V[29]!;
+  }
+
+  for (let round = 0; round < 12; round++) {
+    const s = SIGMA[round % 10]!;
+    mixG(V, m, 0, 4, 8, 12, s[0]!, s[1]!);
+    mixG(V, m, 1, 5, 9, 13, s[2]!, s[3]!);
+    mixG(V, m, 2, 6, 10, 14, s[4]!, s[5]!);
+    mixG(V, m, 3, 7, 11, 15, s[6]!, s[7]!);
+    mixG(V, m, 0, 5, 10, 15, s[8]!, s[9]!);
+    mixG(V, m, 1, 6, 11, 12, s[10]!, s[11]!);
+    mixG(V, m, 2, 7, 8, 13, s[12]!, s[13]!);
+    mixG(V, m, 3, 4, 9, 14, s[14]!, s[15]!);
+  }
+
+  for (let i = 0; i < 16; i++) {
+    h[i] = h[i]! ^ V[i]! ^ V[i + 16]!;
+  }
+}
+
+/** Mixing function `G` of BLAKE2b. */
+function mixG(
+  v: Uint32Array,
+  m: Uint32Array,
+  a: number,
+  b: number,
+  c: number,
+  d: number,
+  x: number,
+  y: number,
+) {
+  add64(v, a, b);
+  add64Msg(v, a, m, x);
+  xorRotr(v, d, a, 32);
+  add64(v, c, d);
+  xorRotr(v, b, c, 24);
+  add64(v, a, b);
+  add64Msg(v, a, m, y);
+  xorRotr(v, d, a, 16);
+  add64(v, c, d);
+  xorRotr(v, b, c, 63);
+}
+
+function block(memory: Uint32Array, index: number): Uint32Array {
+  return memory.subarray(index * BLOCK_WORDS, (index + 1) * BLOCK_WORDS);
+}
+
+function le32(n: number): Uint8Array {
+  const bytes = new Uint8Array(4);
+  new DataView(bytes.buffer).setUint32(0, n, true);
+  return bytes;
+}
+
+function concat(...parts: Uint8Array[]): Uint8Array {
+  const out = new Uint8Array(parts.reduce((len, part) => len + part.length, 0));
+  let pos = 0;
+  for (const part of parts) {
+    out.set(part, pos);
+    pos += part.length;
+  }
+  return out;
+}
+
+function bytesToWords(bytes: Uint8Array): Uint32Array {
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  const words = new Uint32Array(bytes.length / 4);
+  for (let i = 0; i < words.length; i++) {
+    words[i] = view.getUint32(i * 4, true);
+  }
+  return words;
+}
+
+function wordsToBytes(words: Uint32Array): Uint8Array {
+  const bytes = new Uint8Array(words.length * 4);
+  const view = new DataView(bytes.buffer);
+  for (let i = 0; i < words.length; i++) {
+    view.setUint32(i * 4, words[i]!, true);
+  }
+  return bytes;
+}
@@ -8,7 +8,7 @@ import {
   Switch,
 } from "solid-js";
 import { defaultRenderParams } from "../gotcha-captcha";
-import { fetchChallenge, processChallenge } from "../server";
+import { fetchChallenge, processChallenge, TokenBinding } from "../server";
 import CloseSvg from "./icons/close";
 import RefreshSvg from "./icons/refresh";
 import Logo from "./logo";
@@ -18,6 +18,7 @@ import { Challenge } from "./types";
 type ChallengeFrameProps = {
   open: boolean;
   params: SearchParams;
+  binding: () => Promise<TokenBinding>;
   onComplete: (response: string) => void;
   onFail: () => void;
   onError: () => void;
@@ -38,6 +39,9 @@ export default function ChallengeFrame(props: ChallengeFrameProps) {
 
   const isSmallWindow = createMediaQuery("(max-width: 767px)");
 
+  // answer to the puzzle, posted by the challenge before its result
+  let answer: number[] | null = null;
+
   const handleMessage = async (event: MessageEvent) => {
     const challenge = challengeRes();
     if (
@@ -50,6 +54,9 @@ export default function ChallengeFrame(props: ChallengeFrameProps) {
 
     const message = event.data;
     switch (message.type) {
+      case "puzzle-answer":
+        answer = message.answer;
+        break;
       case "response-callback":
         if (!message.success) {
           props.onFail();
@@ -60,7 +67,11 @@ export default function ChallengeFrame(props: ChallengeFrameProps) {
           props.params.k,
           message.success,
           challenge.url,
+          challenge.puzzle && answer
+            ? { puzzle: challenge.puzzle, answer }
+            : null,
           message.interactions,
+          await props.binding(),
         );
         if (response) {
           props.onComplete(response);
@@ -74,9 +85,14 @@ export default function ChallengeFrame(props: ChallengeFrameProps) {
     }
   };
 
+  const refetchChallenge = async () => {
+    answer = null;
+    await challengeActions.refetch();
+  };
+
   const onClose = async () => {
     props.onClose();
-    await challengeActions.refetch();
+    await refetchChallenge();
   };
 
   createEffect(() => {
@@ -125,7 +141,7 @@ export default function ChallengeFrame(props: ChallengeFrameProps) {
               type="button"
               class="text-gray-400 hover:text-purple-700 dark:hover:text-purple-400"
               onClick={async () => {
-                await challengeActions.refetch();
+                await refetchChallenge();
                 props.onReroll?.();
               }}
             >
@@ -156,6 +172,7 @@ export async function fetchChallengeWrapper(
     smallWidth: challenge.small_width,
     smallHeight: challenge.small_height,
     logoUrl: challenge.logo_url,
+    puzzle: challenge.puzzle,
   };
 }
 
@@ -171,6 +188,9 @@ function buildChallengeUrl(challenge: Challenge, params: SearchParams): string {
   if (challenge.logoUrl) {
     url.searchParams.append("logoUrl", challenge.logoUrl);
   }
+  if (challenge.puzzle) {
+    url.searchParams.append("puzzle", challenge.puzzle);
+  }
 
   return url.toString();
 }
@@ -44,6 +44,7 @@ export function GotchaWidget(props: GotchaWidgetProps) {
         <div class={`${getBackgroundClass(state())}`}>
           <ImNotRobot
             params={props}
+            binding={props.binding}
             state={state()}
             onStateChange={setState}
             onVerificationComplete={handlePreVerificationComplete}
@@ -65,6 +66,7 @@ export function GotchaWidget(props: GotchaWidgetProps) {
                 // TODO: add branding support
                 logoUrl: null,
               }}
+              binding={props.binding}
               onComplete={handleChallengeComplete}
               onFail={handleFail}
               onError={handleError}
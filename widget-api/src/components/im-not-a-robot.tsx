@@ -1,7 +1,7 @@
-import { Interaction } from "@gotcha-widget/lib";
 import * as jose from "jose";
 import { createEffect } from "solid-js";
 import { RenderParams } from "../gotcha-captcha";
+import { Interaction } from "../interaction";
 import { PowChallenge, ProofOfWork } from "../proof-of-work";
 import {
   getProofOfWorkChallenge,
@@ -9,6 +9,7 @@ import {
   PreAnalysisResponse,
   processAccessibility,
   processPreAnalysis,
+  TokenBinding,
 } from "../server";
 import Checkbox, { CheckboxState } from "./checkbox";
 import Logo from "./logo";
@@ -16,6 +17,7 @@ import { ChallengeState } from "./types";
 
 type ImNotRobotProps = {
   params: RenderParams;
+  binding: () => Promise<TokenBinding>;
   state: ChallengeState;
   onStateChange: (state: ChallengeState) => void;
   onVerificationComplete: (response: PreAnalysisResponse) => void;
@@ -74,8 +76,13 @@ export default function ImNotRobot(props: ImNotRobotProps) {
           <Checkbox
             state={checkboxState(props.state)}
             onClick={() =>
-              handleVerification((pow) =>
-                processPreAnalysis(props.params.sitekey, pow, interactions),
+              handleVerification(async (pow) =>
+                processPreAnalysis(
+                  props.params.sitekey,
+                  pow,
+                  interactions,
+                  await props.binding(),
+                ),
               )
             }
           />
@@ -88,8 +95,12 @@ export default function ImNotRobot(props: ImNotRobotProps) {
           <button
             type="button"
             onClick={() =>
-              handleVerification((pow) =>
-                processAccessibility(props.params.sitekey, pow),
+              handleVerification(async (pow) =>
+                processAccessibility(
+                  props.params.sitekey,
+                  pow,
+                  await props.binding(),
+                ),
               )
             }
             class="text-purple-500 text-xs self-end hover:underline cursor-pointer"
@@ -227,14 +238,59 @@ function captureInteractions(interactions: Interaction[]): () => void {
         },
       });
     },
+    touchstart: (evt: TouchEvent) => pushTouch(interactions, "touchstart", evt),
+    touchmove: (evt: TouchEvent) => pushTouch(interactions, "touchmove", evt),
+    touchend: (evt: TouchEvent) => pushTouch(interactions, "touchend", evt),
+    wheel: (evt: WheelEvent) => {
+      interactions.push({
+        ts: Date.now(),
+        event: {
+          kind: "wheel",
+          deltaX: evt.deltaX,
+          deltaY: evt.deltaY,
+        },
+      });
+    },
+    scroll: () => {
+      interactions.push({
+        ts: Date.now(),
+        event: {
+          kind: "scroll",
+          x: Math.round(window.scrollX),
+          y: Math.round(window.scrollY),
+        },
+      });
+    },
+    visibilitychange: () => {
+      interactions.push({
+        ts: Date.now(),
+        event: {
+          kind: "visibilitychange",
+          visible: document.visibilityState === "visible",
+        },
+      });
+    },
+  };
+  // focus events don't bubble to the document
+  const windowHandlers = {
+    focus: () => {
+      interactions.push({ ts: Date.now(), event: { kind: "focus" } });
+    },
+    blur: () => {
+      interactions.push({ ts: Date.now(), event: { kind: "blur" } });
+    },
   };
 
   Object.entries(handlers).forEach(([event, handler]) => {
     document.addEventListener(
       event as keyof DocumentEventMap,
       handler as EventListener,
+      { passive: true },
     );
   });
+  Object.entries(windowHandlers).forEach(([event, handler]) => {
+    window.addEventListener(event as keyof WindowEventMap, handler);
+  });
 
   return () => {
     Object.entries(handlers).forEach(([event, handler]) => {
@@ -243,5 +299,28 @@ function captureInteractions(interactions: Interaction[]): () => void {
         handler as EventListener,
       );
     });
+    Object.entries(windowHandlers).forEach(([event, handler]) => {
+      window.removeEventListener(event as keyof WindowEventMap, handler);
+    });
   };
 }
+
+function pushTouch(
+  interactions: Interaction[],
+  kind: "touchstart" | "touchmove" | "touchend",
+  evt: TouchEvent,
+) {
+  const touch = evt.changedTouches[0];
+  if (!touch) return;
+
+  interactions.push({
+    ts: Date.now(),
+    event: {
+      kind,
+      x: Math.round(touch.clientX),
+      y: Math.round(touch.clientY),
+      pressure: touch.force,
+      radius: Math.max(touch.radiusX, touch.radiusY),
+    },
+  });
+}
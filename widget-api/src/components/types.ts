@@ -1,5 +1,6 @@
 import { Accessor } from "solid-js";
 import { RenderParams } from "../gotcha-captcha";
+import { TokenBinding } from "../server";
 import { LiveState } from "../widget";
 
 export type ChallengeState =
@@ -13,6 +14,7 @@ export type ChallengeState =
 
 export type GotchaWidgetProps = RenderParams & {
   liveState: Accessor<LiveState>;
+  binding: () => Promise<TokenBinding>;
 };
 
 export type Challenge = {
@@ -22,4 +24,5 @@ export type Challenge = {
   smallWidth: number;
   smallHeight: number;
   logoUrl: string | null;
+  puzzle: string | null;
 };
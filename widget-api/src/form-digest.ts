@@ -0,0 +1,37 @@
+/**
+ * Digest of the protected fields of the form the widget is in, so the response token is only valid
+ * for the submitted data. The fields are sorted by name and value and url encoded before hashing them
+ * with SHA-256, matching the digest computed by site verify from the form data.
+ */
+export async function formDigest(
+  container: Element,
+  fields: string[] | undefined,
+): Promise<string | undefined> {
+  const form = container.closest("form");
+  if (!form || !fields?.length) return undefined;
+
+  const data = new FormData(form);
+  const pairs: [string, string][] = fields.flatMap((name) =>
+    data
+      .getAll(name)
+      .filter((value): value is string => typeof value === "string")
+      .map((value): [string, string] => [name, value]),
+  );
+  pairs.sort(([n1, v1], [n2, v2]) =>
+    n1 !== n2 ? compare(n1, n2) : compare(v1, v2),
+  );
+
+  const canonical = new URLSearchParams(pairs).toString();
+  const hash = await crypto.subtle.digest(
+    "SHA-256",
+    new TextEncoder().encode(canonical),
+  );
+  return btoa(String.fromCharCode(...new Uint8Array(hash)))
+    .replace(/\+/g, "-")
+    .replace(/\//g, "_")
+    .replace(/=+$/, "");
+}
+
+function compare(a: string, b: string): number {
+  return a < b ? -1 : a > b ? 1 : 0;
+}
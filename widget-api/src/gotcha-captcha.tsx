@@ -125,6 +125,12 @@ export class GotchaCaptcha {
   private getParamsFromContainer(container: Element): RenderParams {
     return {
       sitekey: container.getAttribute("data-sitekey") ?? "",
+      action: container.getAttribute("data-action") ?? undefined,
+      "form-fields": container
+        .getAttribute("data-form-fields")
+        ?.split(",")
+        .map((field) => field.trim())
+        .filter((field) => field),
       theme: container.getAttribute("data-theme") as RenderParams["theme"],
       size: container.getAttribute("data-size") as RenderParams["size"],
       tabindex: parseInt(container.getAttribute("data-tabindex") || "0") || 0,
@@ -147,6 +153,10 @@ export class GotchaCaptcha {
 export type RenderParams = {
   /** API key for site verification */
   sitekey: string;
+  /** Action bound to the response token, checked by site verify against the expected action */
+  action?: string;
+  /** Names of the fields of the enclosing form bound to the response token, checked by site verify against the submitted form data */
+  "form-fields"?: string[];
   /** Widget theme appearance */
   theme?: "dark" | "light";
   /** Widget size configuration */
@@ -0,0 +1,23 @@
+import { Interaction as LibInteraction } from "@gotcha-widget/lib";
+
+type TouchPoint = { x: number; y: number; pressure: number; radius: number };
+
+type PointerPoint = {
+  pointerType: "mouse" | "pen" | "touch";
+  x: number;
+  y: number;
+  pressure: number;
+};
+
+export type InteractionEvent =
+  | ({ kind: "touchstart" | "touchmove" | "touchend" } & TouchPoint)
+  | ({ kind: "pointerdown" | "pointermove" | "pointerup" } & PointerPoint)
+  | { kind: "wheel"; deltaX: number; deltaY: number }
+  | { kind: "scroll"; x: number; y: number }
+  | { kind: "focus" }
+  | { kind: "blur" }
+  | { kind: "visibilitychange"; visible: boolean };
+
+export type Interaction =
+  | LibInteraction
+  | { ts: number; event: InteractionEvent };
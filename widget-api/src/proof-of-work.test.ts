@@ -0,0 +1,27 @@
+import assert from "node:assert/strict";
+import { test } from "node:test";
+import { ProofOfWork, type PowChallenge } from "./proof-of-work.ts";
+
+// same challenge as the server's `argon2id_widget_solution` test
+const argon2idChallenge: PowChallenge = {
+  nonce: 4077096492,
+  difficulty: 4,
+  timestamp: 1739555092,
+  algorithm: "argon2id",
+};
+
+const hex = (bytes: Uint8Array) =>
+  Array.from(bytes, (byte) => byte.toString(16).padStart(2, "0")).join("");
+
+test("argon2id digest matches the server", async () => {
+  const digest = await ProofOfWork.digest(argon2idChallenge, 0);
+
+  assert.equal(
+    hex(digest),
+    "f53e957da654b03bdb1a57529c8ea02e86618570abfd4680ae1b6bc4061a7664",
+  );
+});
+
+test("solves the argon2id challenge the server verifies", async () => {
+  assert.equal(await ProofOfWork.solve(argon2idChallenge), 6);
+});
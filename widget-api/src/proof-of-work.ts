@@ -1,21 +1,35 @@
+import { argon2id, type Argon2Params } from "./argon2.ts";
+
+export type PowAlgorithm = "sha256-hex" | "sha256" | "argon2id";
+
 export interface PowChallenge {
   nonce: number;
   difficulty: number;
   timestamp: number;
+  algorithm?: PowAlgorithm;
 }
 
 export class ProofOfWork {
   private static readonly HASH_ALGORITHM = "SHA-256";
+  /** Same parameters as the server's `PowAlgorithm::ARGON2_*`. */
+  private static readonly ARGON2_PARAMS: Argon2Params = {
+    memoryKib: 4 * 1024,
+    iterations: 1,
+    hashLength: 32,
+  };
 
   public static async solve(challenge: PowChallenge): Promise<number> {
     if (challenge.difficulty === 0 || challenge.difficulty > 32) {
       throw new Error("Invalid difficulty");
     }
 
+    const algorithm = challenge.algorithm ?? "sha256-hex";
+    const bits = challenge.difficulty * (algorithm === "sha256-hex" ? 4 : 1);
+
     let solution = 0;
     while (true) {
-      const hash = await this.hashSolution(challenge, solution);
-      if (this.isSolution(hash, challenge.difficulty)) {
+      const digest = await this.digest(challenge, solution);
+      if (this.leadingZeroBits(digest) >= bits) {
         return solution;
       }
 
@@ -23,32 +37,43 @@ export class ProofOfWork {
     }
   }
 
-  private static isSolution(hash: string, difficulty: number): boolean {
-    const prefix = "0".repeat(difficulty);
-    return hash.startsWith(prefix);
+  private static leadingZeroBits(digest: Uint8Array): number {
+    let bits = 0;
+    for (const byte of digest) {
+      if (byte === 0) {
+        bits += 8;
+        continue;
+      }
+      return bits + Math.clz32(byte) - 24;
+    }
+    return bits;
   }
 
-  private static async hashSolution(
+  /** For Argon2id the challenge is the salt and the solution is the password, as the server hashes them. */
+  public static async digest(
     challenge: PowChallenge,
     solution: number,
-  ): Promise<string> {
+  ): Promise<Uint8Array> {
     const nonce_bytes = this.toBeBytes(challenge.nonce, 4);
     const difficulty_bytes = this.toBeBytes(challenge.difficulty, 2);
     const timestamp_bytes = this.toBeBytes(challenge.timestamp, 8, true);
     const solution_bytes = this.toBeBytes(solution, 4);
 
-    const data = new Uint8Array([
+    const challenge_bytes = new Uint8Array([
       ...nonce_bytes,
       ...difficulty_bytes,
       ...timestamp_bytes,
-      ...solution_bytes,
     ]);
+    if (challenge.algorithm === "argon2id") {
+      // each hash blocks for a while, so let the page breathe in between
+      await new Promise((resolve) => setTimeout(resolve));
+      return argon2id(solution_bytes, challenge_bytes, this.ARGON2_PARAMS);
+    }
+
+    const data = new Uint8Array([...challenge_bytes, ...solution_bytes]);
 
     const hashBuffer = await crypto.subtle.digest(this.HASH_ALGORITHM, data);
-    const hash = Array.from(new Uint8Array(hashBuffer))
-      .map((b) => b.toString(16).padStart(2, "0"))
-      .join("");
-    return hash;
+    return new Uint8Array(hashBuffer);
   }
 
   private static toBeBytes(
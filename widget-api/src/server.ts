@@ -1,9 +1,11 @@
-import { Interaction } from "@gotcha-widget/lib";
+import { Interaction } from "./interaction";
 
 export type VerificationResponse = {
   success: boolean;
+  score?: number;
   challenge_ts: string;
   hostname: string | null;
+  action?: string;
   error_codes: ErrorCodes[] | null;
 };
 
@@ -13,11 +15,15 @@ export type ErrorCodes =
   | "missing-input-response"
   | "invalid-input-response"
   | "bad-request"
-  | "timeout-or-duplicate";
+  | "timeout-or-duplicate"
+  | "action-mismatch"
+  | "form-data-mismatch"
+  | "sitekey-secret-mismatch";
 
 export async function siteVerify(
   secret: string,
   token: string,
+  expectedAction?: string,
 ): Promise<VerificationResponse | null> {
   try {
     const origin = import.meta.env.VITE_GOTCHA_SV_ORIGIN;
@@ -26,6 +32,7 @@ export async function siteVerify(
     const formData = new URLSearchParams();
     formData.append("secret", secret);
     formData.append("response", token);
+    if (expectedAction) formData.append("expected_action", expectedAction);
 
     const response = await fetch(url, {
       method: "POST",
@@ -51,6 +58,7 @@ export type FetchChallenge = {
   small_width: number;
   small_height: number;
   logo_url: string | null;
+  puzzle: string | null;
 };
 
 export async function fetchChallenge(
@@ -70,15 +78,44 @@ export async function fetchChallenge(
   }
 }
 
+/** Data bound to the response token, checked by site verify. */
+export type TokenBinding = {
+  action?: string;
+  formDigest?: string;
+};
+
 type ChallengeResponse = {
   token: string;
+  clearance?: string;
 };
 
+const clearanceKey = (siteKey: string) => `gotcha-clearance:${siteKey}`;
+
+function loadClearance(siteKey: string): string | null {
+  try {
+    return localStorage.getItem(clearanceKey(siteKey));
+  } catch {
+    return null;
+  }
+}
+
+function storeClearance(siteKey: string, clearance: string) {
+  try {
+    localStorage.setItem(clearanceKey(siteKey), clearance);
+  } catch (e) {
+    console.error("failed to store clearance", e);
+  }
+}
+
+export type PuzzleSolution = { puzzle: string; answer: number[] };
+
 export async function processChallenge(
   siteKey: string,
   success: boolean,
   challengeUrl: string,
+  solution: PuzzleSolution | null,
   interactions: Interaction[],
+  binding: TokenBinding = {},
 ): Promise<string | null> {
   try {
     const origin = import.meta.env.VITE_GOTCHA_SV_ORIGIN;
@@ -94,14 +131,18 @@ export async function processChallenge(
         site_key: siteKey,
         hostname: window.location.hostname,
         challenge: challengeUrl,
+        solution,
         interactions,
+        action: binding.action,
+        form_digest: binding.formDigest,
       }),
     });
     if (response.status !== 200)
       throw new Error(
         `processChallenge returned status code ${response.status}`,
       );
-    const { token }: ChallengeResponse = await response.json();
+    const { token, clearance }: ChallengeResponse = await response.json();
+    if (clearance) storeClearance(siteKey, clearance);
 
     return token;
   } catch (e) {
@@ -120,6 +161,7 @@ export async function processPreAnalysis(
   siteKey: string,
   proofOfWork: PowResult,
   interactions: Interaction[],
+  binding: TokenBinding = {},
 ): Promise<PreAnalysisResponse | null> {
   try {
     const origin = import.meta.env.VITE_GOTCHA_SV_ORIGIN;
@@ -135,6 +177,9 @@ export async function processPreAnalysis(
         hostname: window.location.hostname,
         interactions,
         proof_of_work: proofOfWork,
+        clearance: loadClearance(siteKey),
+        action: binding.action,
+        form_digest: binding.formDigest,
       }),
     });
     if (response.status !== 200)
@@ -152,6 +197,7 @@ export async function processPreAnalysis(
 export async function processAccessibility(
   siteKey: string,
   proofOfWork: { challenge: string; solution: number },
+  binding: TokenBinding = {},
 ): Promise<PreAnalysisResponse | null> {
   try {
     const origin = import.meta.env.VITE_GOTCHA_SV_ORIGIN;
@@ -166,6 +212,8 @@ export async function processAccessibility(
         siteKey,
         hostname: window.location.hostname,
         proof_of_work: proofOfWork,
+        action: binding.action,
+        form_digest: binding.formDigest,
       }),
     });
     if (response.status !== 200)
@@ -2,6 +2,7 @@ import { createSignal } from "solid-js";
 import { render } from "solid-js/web";
 import { GotchaWidget } from "./components/gotcha-widget";
 import { GotchaWidgetProps } from "./components/types";
+import { formDigest } from "./form-digest";
 import { RenderParams } from "./gotcha-captcha";
 
 export interface Widget {
@@ -32,6 +33,10 @@ export function createWidget(): Widget {
         timeout = setTimeout(() => params?.["expired-callback"]?.(), 30000);
       },
       liveState: state,
+      binding: async () => ({
+        action: parameters.action,
+        formDigest: await formDigest(container, parameters["form-fields"]),
+      }),
     };
 
     render(
@@ -2,8 +2,13 @@ import { createEffect, createSignal, onCleanup } from "solid-js";
 import { createStore } from "solid-js/store";
 import type { Star, FloatingFeedback, Ripple } from "../types";
 import { onChallengeResponse } from "@gotcha-widget/lib";
+import { puzzleLayout, SELECTION_ITEMS, sendPuzzleAnswer } from "../puzzle";
 
 export const useGameLogic = () => {
+  // without a puzzle the targets are picked at random
+  const layout = puzzleLayout();
+  const starCount = layout ? SELECTION_ITEMS : 5;
+
   const [gameStarted, setGameStarted] = createSignal(false);
   const [stars, setStars] = createStore<Star[]>([]);
   const [score, setScore] = createSignal(0);
@@ -21,6 +26,10 @@ export const useGameLogic = () => {
 
   let timeoutId: number | undefined;
   let feedbackIdCounter = 0;
+  let hits: number[] = [];
+
+  const nextTarget = () =>
+    layout?.[score()] ?? Math.floor(Math.random() * starCount);
 
   const startTimeout = () => {
     if (timeoutId) {
@@ -34,7 +43,7 @@ export const useGameLogic = () => {
   const randomizeStarPositions = () => {
     if (gameState() !== "playing") return;
 
-    setTargetIdx(Math.floor(Math.random() * 5));
+    setTargetIdx(nextTarget());
     for (let i = 0; i < stars.length; i++) {
       setStars(i, {
         x: Math.random() * 80 + 10,
@@ -46,7 +55,7 @@ export const useGameLogic = () => {
 
   const initializeStars = () => {
     const newStars: Star[] = [];
-    for (let i = 0; i < 5; i++) {
+    for (let i = 0; i < starCount; i++) {
       newStars.push({
         id: i,
         x: Math.random() * 80 + 10,
@@ -90,6 +99,7 @@ export const useGameLogic = () => {
 
   const startGame = () => {
     setGameStarted(true);
+    hits = [];
     setScore(0);
     setMisses(0);
     setGameState("playing");
@@ -97,6 +107,7 @@ export const useGameLogic = () => {
   };
 
   const resetGame = () => {
+    hits = [];
     setScore(0);
     setMisses(0);
     setGameState("playing");
@@ -113,6 +124,7 @@ export const useGameLogic = () => {
     const isHit = star.id === targetIdx();
 
     if (isHit) {
+      hits.push(star.id);
       const newScore = score() + 1;
       setScore(newScore);
       setScreenFlash(true);
@@ -150,6 +162,7 @@ export const useGameLogic = () => {
   createEffect(async () => {
     switch (gameState()) {
       case "won":
+        if (layout) sendPuzzleAnswer(hits);
         onChallengeResponse(true);
         break;
       case "lost":
@@ -0,0 +1,31 @@
+/** Number of stars in a selection puzzle, as issued by the server. */
+export const SELECTION_ITEMS = 9;
+
+/**
+ * Layout of the puzzle issued with the challenge, read from the claims of the
+ * `puzzle` token in the page URL. For a selection puzzle these are the indices
+ * of the stars to pick.
+ */
+export function puzzleLayout(): number[] | null {
+  const token = new URLSearchParams(window.location.search).get("puzzle");
+  const payload = token?.split(".")[1];
+  if (!payload) return null;
+
+  try {
+    const { layout } = JSON.parse(
+      atob(payload.replace(/-/g, "+").replace(/_/g, "/")),
+    );
+    return Array.isArray(layout) ? layout : null;
+  } catch (e) {
+    console.error("malformed puzzle", e);
+    return null;
+  }
+}
+
+/**
+ * Posts the answer to the widget, which submits it along with the result.
+ * The answer is useless without the puzzle token held by the widget.
+ */
+export function sendPuzzleAnswer(answer: number[]) {
+  window.parent.postMessage({ type: "puzzle-answer", answer }, "*");
+}
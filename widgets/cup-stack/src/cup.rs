@@ -8,7 +8,7 @@ pub struct CupsPlugin;
 
 impl Plugin for CupsPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(TargetsLeft(6));
+        app.insert_resource(TargetsLeft(CUP_COUNT));
         app.add_systems(
             PostUpdate,
             (
@@ -27,8 +27,14 @@ pub struct TargetsLeft(pub u8);
 #[derive(Component)]
 pub struct Cup;
 
+/// Cup to knock off the table, with its index in the pyramid.
+#[derive(Component)]
+pub struct Target(pub u16);
+
 pub const CUP_HEIGHT: f32 = 0.10;
 pub const CUP_RADIUS: f32 = 0.04;
+/// Number of cups in the pyramid, the same as the items of a selection puzzle.
+pub const CUP_COUNT: u8 = 9;
 
 #[derive(Bundle)]
 pub struct CupBundle {
@@ -57,7 +63,10 @@ impl Default for CupBundle {
     }
 }
 
-fn update_targets_left(cups: Query<&Transform, With<Cup>>, mut targets_left: ResMut<TargetsLeft>) {
+fn update_targets_left(
+    cups: Query<&Transform, With<Target>>,
+    mut targets_left: ResMut<TargetsLeft>,
+) {
     let above_table_count = cups
         .iter()
         .filter(|cup| cup.translation.y > TABLE_POS.y)
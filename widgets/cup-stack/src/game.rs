@@ -1,9 +1,9 @@
 use bevy::{
-    color::palettes::css::{BLUE, GREEN, RED},
+    color::palettes::css::{BLUE, GREEN, RED, WHITE},
     prelude::*,
 };
 use bevy_rapier3d::prelude::*;
-use gotcha_plugin::{AttemptCount, GameplayAttempt, GotchaState};
+use gotcha_plugin::{AttemptCount, GameplayAttempt, GotchaState, Puzzle};
 
 use crate::{cup::*, throwable::ThrowablesLeftCount};
 
@@ -32,9 +32,13 @@ impl Plugin for GamePlugin {
 fn check_game_over(
     targets_left: Res<TargetsLeft>,
     throwables_left: Res<ThrowablesLeftCount>,
+    targets: Query<&Target>,
+    mut puzzle: ResMut<Puzzle>,
     mut event_w: EventWriter<GameplayAttempt>,
 ) {
     if targets_left.0 == 0 {
+        // every target was knocked off the table
+        puzzle.answer = targets.iter().map(|Target(index)| *index).collect();
         event_w.send(GameplayAttempt::Success);
     }
     if throwables_left.0 == 0 {
@@ -78,6 +82,7 @@ fn setup_entities(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    puzzle: Res<Puzzle>,
 ) {
     // Ground
     commands.spawn((
@@ -111,24 +116,40 @@ fn setup_entities(
     ));
     // Cups
     let mesh = meshes.add(Cylinder::new(CUP_RADIUS, CUP_HEIGHT));
-    let material = materials.add(StandardMaterial { base_color: RED.into(), ..Default::default() });
-    let cup_builder = |pos_x: f32, pos_y: f32, pos_z: f32| -> CupBundle {
+    let target_material =
+        materials.add(StandardMaterial { base_color: RED.into(), ..Default::default() });
+    let material =
+        materials.add(StandardMaterial { base_color: WHITE.into(), ..Default::default() });
+    let cup_builder = |pos_x: f32, pos_y: f32, pos_z: f32, is_target: bool| -> CupBundle {
         CupBundle {
             mesh: Mesh3d(mesh.clone()),
-            material: MeshMaterial3d(material.clone()),
+            material: MeshMaterial3d(match is_target {
+                true => target_material.clone(),
+                false => material.clone(),
+            }),
             transform: Transform::from_xyz(pos_x, pos_y, pos_z),
             ..default()
         }
     };
-    // Pyramide of cups
-    for level in 0..10 {
+    // Pyramide of cups, the targets are the ones in the puzzle layout or all of them without a puzzle
+    let mut index = 0;
+    for level in 0..3 {
         let x_start_pad = CUP_RADIUS * level as f32;
         let y = CUP_HEIGHT * level as f32 + TABLE_POS.y + TABLE_DIM.y;
-        for i in 0..(3 - level) {
+        for i in 0..(4 - level) {
             const GAP: f32 = CUP_RADIUS * 2. + 0.01;
             let x = GAP * i as f32 + x_start_pad;
+            let is_target = puzzle
+                .layout
+                .as_ref()
+                .is_none_or(|layout| layout.contains(&index));
             // shift everything left to center
-            commands.spawn(cup_builder(x - (GAP * 3. / 2.), y, TABLE_POS.z));
+            let cup = cup_builder(x - (GAP * 3. / 2.), y, TABLE_POS.z, is_target);
+            match is_target {
+                true => commands.spawn((cup, Target(index))),
+                false => commands.spawn(cup),
+            };
+            index += 1;
         }
     }
 }
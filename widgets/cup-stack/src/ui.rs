@@ -48,7 +48,7 @@ fn setup_ui(mut commands: Commands) {
             BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
         ))
         .with_children(|parent| {
-            parent.spawn((Text::new("Targets left: 6"), TargetsLeftText));
+            parent.spawn((Text::new(""), TargetsLeftText));
         });
     commands
         .spawn((
@@ -1,3 +1,4 @@
+use js_sys::{Array, JSON, Object, Reflect};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -26,3 +27,35 @@ pub async fn send_challenge_result(success: bool) {
 pub async fn send_challenge_error() {
     onChallengeError().await;
 }
+
+/// Layout of the puzzle issued with the challenge, read from the claims of the `puzzle` token in the page URL.
+pub fn puzzle_layout() -> Option<Vec<u16>> {
+    let window = web_sys::window()?;
+    let search = window.location().search().ok()?;
+    let token = web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get("puzzle")?;
+    let payload = token.split('.').nth(1)?.replace('-', "+").replace('_', "/");
+    let claims = JSON::parse(&window.atob(&payload).ok()?).ok()?;
+    let layout = Reflect::get(&claims, &"layout".into()).ok()?;
+    layout.is_array().then(|| {
+        Array::from(&layout)
+            .iter()
+            .filter_map(|item| item.as_f64())
+            .map(|item| item as u16)
+            .collect()
+    })
+}
+
+/// Posts the answer to the puzzle to the widget, which submits it along with the challenge result.
+/// The answer is useless without the puzzle token held by the widget.
+pub fn send_puzzle_answer(answer: &[u16]) {
+    let Some(parent) = web_sys::window().and_then(|window| window.parent().ok().flatten()) else {
+        return;
+    };
+    let message = Object::new();
+    let answer: Array = answer.iter().map(|&item| JsValue::from(item)).collect();
+    let _ = Reflect::set(&message, &"type".into(), &"puzzle-answer".into());
+    let _ = Reflect::set(&message, &"answer".into(), &answer);
+    let _ = parent.post_message(&message, "*");
+}
@@ -16,6 +16,7 @@ impl Plugin for GotchaPlugin {
         app.init_state::<GotchaState>();
         app.add_sub_state::<GameOverState>();
         app.insert_resource(AttemptCount(0));
+        app.insert_resource(Puzzle::from_page());
         app.add_event::<GameplayAttempt>();
         app.add_plugins(UiPlugin);
         // FIXME: should wait for this task to complete before continuing
@@ -70,6 +71,26 @@ impl AttemptCount {
     }
 }
 
+/// Puzzle issued with the challenge. Without one, the game picks its own targets and no answer is sent.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Puzzle {
+    /// Layout to render, as described by the kind of puzzle of the challenge.
+    pub layout: Option<Vec<u16>>,
+    /// Answer given by the user, sent along with a successful attempt.
+    pub answer: Vec<u16>,
+}
+
+impl Puzzle {
+    fn from_page() -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let layout = gotcha_lib::puzzle_layout();
+        #[cfg(not(target_arch = "wasm32"))]
+        let layout = None;
+
+        Self { layout, answer: vec![] }
+    }
+}
+
 #[derive(Event, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum GameplayAttempt {
     Success,
@@ -151,13 +172,18 @@ fn handle_gameplay_attempt_event(
     }
 }
 
-fn handle_gameover(game_over_state: Res<State<GameOverState>>) {
+fn handle_gameover(game_over_state: Res<State<GameOverState>>, puzzle: Res<Puzzle>) {
     #[cfg(target_arch = "wasm32")]
     use bevy::tasks::AsyncComputeTaskPool;
 
     match game_over_state.get() {
         GameOverState::Success => {
             info!("success");
+            debug!("answer = {:?}", puzzle.answer);
+            #[cfg(target_arch = "wasm32")]
+            if puzzle.layout.is_some() {
+                gotcha_lib::send_puzzle_answer(&puzzle.answer);
+            }
             #[cfg(target_arch = "wasm32")]
             AsyncComputeTaskPool::get().spawn(async {
                 gotcha_lib::send_challenge_result(true).await;